@@ -0,0 +1,10 @@
+//! Request and response bodies shared by the server and the Yew client.
+//!
+//! A version only ever grows. New fields may be added to it when they have a serde default, so
+//! bodies written by older clients and servers still read; renaming or removing a field, or changing
+//! its type, is a breaking change for deployed clients and goes into a new version module instead.
+//! The client is served alongside the server, so new values on an existing enum reach both together.
+
+pub mod v1;
+
+pub const API_VERSION: &str = "v1";
@@ -0,0 +1,597 @@
+use serde::{Deserialize, Serialize};
+
+use crate::StoreId;
+
+/// The body of every failed request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse {
+    pub code: String,
+    pub message: String
+}
+
+/// The code currently shown on a store's display, also sent on the code stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CodeResponse {
+    pub code: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClaimRequest {
+    pub id: String,
+    pub code: String,
+    /// The referral code from a friend's link, only counted on a customer's first claim.
+    #[serde(default)]
+    pub referral: Option<String>
+}
+
+/// What a customer's browser is given for a claimed stamp.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClaimResponse {
+    /// The key to follow the card live with, the same for every claim on the card.
+    pub card_key: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CardResponse {
+    /// The stamps on a stamp card or the points on a points card.
+    pub stamps: u32,
+    /// The stamps that fill the card, zero on a points card which has no limit.
+    #[serde(default)]
+    pub capacity: u32,
+    #[serde(default)]
+    pub kind: CardKind,
+    /// Stamps due to expire within the next 30 days, soonest first, so the customer can be warned.
+    #[serde(default)]
+    pub expiring: Vec<StampExpiry>,
+    /// 1 for January through 12 for December, set by the customer for their birthday bonus.
+    #[serde(default)]
+    pub birth_month: Option<u32>,
+    /// Every stamp the card has been given, including bonuses, less any taken off by hand.
+    #[serde(default)]
+    pub total_stamps: u32,
+    #[serde(default)]
+    pub redemptions: u32,
+    /// Left out when the card's programme has no tiers.
+    #[serde(default)]
+    pub tier: Option<TierProgress>,
+    /// Everything the card's programme offers, for the customer to see what they can afford.
+    #[serde(default)]
+    pub rewards: Vec<CatalogueReward>
+}
+
+/// What a card collects, decided by its programme.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CardKind {
+    #[default]
+    Stamps,
+    Points
+}
+
+/// Something a customer can spend their card on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CatalogueReward {
+    pub reward_id: String,
+    pub name: String,
+    pub cost: u32
+}
+
+/// A customer's spend entered by staff on a points programme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EarnRequest {
+    /// In pence, or hundredths of whatever the currency is.
+    pub spend: u32,
+    /// The programme the spend earns in.
+    #[serde(default)]
+    pub programme_id: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SpendRequest {
+    pub reward_id: String
+}
+
+/// Where a customer is in their programme's tiers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TierProgress {
+    /// Empty until the customer has redeemed enough cards for the first tier.
+    pub current: Option<Tier>,
+    /// Empty once the customer is in the top tier.
+    pub next: Option<Tier>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BirthdayRequest {
+    /// Leave out to remove the birth month from the card.
+    pub month: Option<u32>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StampExpiry {
+    pub stamps: u32,
+    pub expires: String
+}
+
+/// How a programme stops stamps being kept forever.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExpiryPolicy {
+    /// Each stamp expires this many months after it was given.
+    StampAge { months: u32 },
+    /// The whole card empties after this many months without a stamp or redemption.
+    Inactivity { months: u32 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum CardEvent {
+    Snapshot,
+    Stamped,
+    Redeemed,
+    /// Staff added or removed stamps by hand.
+    Adjusted,
+    /// Stamps ran out under the programme's expiry policy.
+    Expired,
+    /// Bonus stamps for referring a friend or being referred.
+    Referral,
+    /// Bonus stamps for a birthday or a milestone.
+    Bonus,
+    /// The customer reached a new tier, which may have changed the card's capacity.
+    Promoted,
+    /// Points from a spend.
+    Earned
+}
+
+/// Sent on a card's stream whenever it changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CardUpdate {
+    pub event: CardEvent,
+    /// The stamps on a stamp card or the points on a points card.
+    pub stamps: u32,
+    /// Zero on a points card, which has no limit.
+    pub capacity: u32,
+    #[serde(default)]
+    pub kind: CardKind
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairingCodeRequest {
+    pub store_id: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairingCodeResponse {
+    pub code: String,
+    pub expires_in: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairRequest {
+    pub code: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PairResponse {
+    pub device_key: String,
+    pub store_id: StoreId
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeviceResponse {
+    pub device_id: String,
+    pub store_id: StoreId,
+    pub created: String,
+    pub revoked: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Stamped,
+    Redeemed,
+    Adjusted,
+    /// Loaded from an export or an old paper card rather than earned.
+    Imported,
+    Expired,
+    Referral,
+    Bonus,
+    /// Points from a spend.
+    Earned
+}
+
+/// One change to a card as shown in the admin tools.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ActivityResponse {
+    pub user_id: String,
+    pub kind: ActivityKind,
+    pub delta: i32,
+    pub stamps: u32,
+    pub store_id: Option<StoreId>,
+    pub reason: Option<String>,
+    /// Who made a manual change.
+    pub staff: Option<String>,
+    /// The campaign that boosted a stamp.
+    #[serde(default)]
+    pub campaign: Option<String>,
+    pub timestamp: String
+}
+
+/// A card looked up by staff, with its most recent activity first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AdminCardResponse {
+    pub user_id: String,
+    pub stamps: u32,
+    pub capacity: u32,
+    #[serde(default)]
+    pub total_stamps: u32,
+    #[serde(default)]
+    pub redemptions: u32,
+    /// The catalogue of the card's programme, empty when it only redeems full cards.
+    #[serde(default)]
+    pub rewards: Vec<CatalogueReward>,
+    pub activity: Vec<ActivityResponse>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AdjustRequest {
+    pub delta: i32,
+    pub reason: String
+}
+
+/// Used both to list programmes and to create or replace one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProgrammeDetails {
+    pub programme_id: String,
+    pub name: String,
+    pub reward: String,
+    #[serde(default)]
+    pub expiry: Option<ExpiryPolicy>,
+    #[serde(default)]
+    pub bonuses: BonusRules,
+    /// Lowest first, each reached by redeeming more rewards than the last.
+    #[serde(default)]
+    pub tiers: Vec<Tier>,
+    #[serde(default)]
+    pub kind: CardKind,
+    /// Points earned for each whole unit of currency spent, needed by a points programme.
+    #[serde(default)]
+    pub points_rate: Option<u32>,
+    /// What a card can be spent on, costed in stamps or points. A stamp card can always be redeemed
+    /// in full for `reward` as well.
+    #[serde(default)]
+    pub catalogue: Vec<CatalogueReward>
+}
+
+/// A membership level reached by redeeming a number of rewards in a programme.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Tier {
+    pub name: String,
+    pub redemptions: u32,
+    /// The stamps needed to fill a card once the tier is reached, left out to keep the card as it is.
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Shown to the customer, such as free oat milk.
+    #[serde(default)]
+    pub perks: Vec<String>
+}
+
+/// Stamps a programme gives on top of those claimed in store.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BonusRules {
+    /// Given once a year during the customer's birthday month.
+    #[serde(default)]
+    pub birthday: Option<u32>,
+    #[serde(default)]
+    pub milestones: Vec<MilestoneBonus>
+}
+
+/// Given when a customer redeems their `cards`th reward, such as their 5th.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MilestoneBonus {
+    pub cards: u32,
+    pub stamps: u32
+}
+
+/// Used both to list stores and to create or replace one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoreDetails {
+    pub store_id: StoreId,
+    pub name: String,
+    pub programme_id: Option<String>
+}
+
+/// Stamps given on one day at one store, manual adjustments included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DailyStamps {
+    pub date: String,
+    pub store_id: Option<StoreId>,
+    pub stamps: i64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DailyRedemptions {
+    pub date: String,
+    pub redemptions: u32
+}
+
+/// Customers seen on one day, split by whether it was their first visit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DailyCustomers {
+    pub date: String,
+    pub active: u32,
+    pub new: u32,
+    pub returning: u32
+}
+
+/// How many cards currently hold a given number of stamps.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StampCount {
+    pub stamps: u32,
+    pub cards: u32
+}
+
+/// A whole card as exported, and as accepted back by the JSON import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CardRecord {
+    pub user_id: String,
+    /// The stamps on a stamp card or the points on a points card.
+    pub stamps: u32,
+    /// Left out for a points card, which has no limit.
+    pub capacity: Option<u32>,
+    #[serde(default)]
+    pub kind: CardKind,
+    /// The code in the customer's referral link, so links already shared keep working.
+    #[serde(default)]
+    pub referral_code: Option<String>,
+    /// The programme the card was last used in.
+    #[serde(default)]
+    pub programme_id: Option<String>,
+    /// When each stamp on the card was given, oldest first, so they still expire on time once imported.
+    #[serde(default)]
+    pub stamped: Vec<String>,
+    #[serde(default)]
+    pub last_activity: Option<String>,
+    #[serde(default)]
+    pub birth_month: Option<u32>,
+    /// The year the last birthday bonus was given.
+    #[serde(default)]
+    pub birthday_bonus_year: Option<i32>,
+    #[serde(default)]
+    pub total_stamps: u32,
+    #[serde(default)]
+    pub redemptions: u32,
+    /// The card's activity oldest first, left empty when it is not known.
+    #[serde(default)]
+    pub history: Vec<ActivityResponse>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportSummary {
+    pub created: u32,
+    pub updated: u32,
+    pub history: u32
+}
+
+/// Starts a request for a customer's own data, which staff must approve before it can be used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DataRequest {
+    pub user_id: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DataRequestResponse {
+    /// Kept by the customer's browser and sent as a bearer token once approved.
+    pub token: String,
+    /// Shown to staff so they can approve the request.
+    pub code: String,
+    pub expires_in: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApproveDataRequest {
+    pub user_id: String,
+    pub code: String
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub action: String,
+    pub ip: Option<String>,
+    pub detail: String
+}
+
+/// Everything stored about one customer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PersonalDataResponse {
+    pub user_id: String,
+    pub card: Option<CardRecord>,
+    /// The balance of the customer's points card, if they have one.
+    #[serde(default)]
+    pub points: Option<u32>,
+    /// The customer's points card in full.
+    #[serde(default)]
+    pub points_card: Option<CardRecord>,
+    /// Referrals the customer made or was brought in by.
+    #[serde(default)]
+    pub referrals: Vec<ReferralResponse>,
+    pub audit: Vec<AuditRecord>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErasureResponse {
+    pub card_deleted: bool,
+    pub activity_anonymised: u64,
+    pub audit_anonymised: u64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday
+}
+
+/// What a campaign does to each claimed stamp.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CampaignReward {
+    /// Each claim gives this many stamps instead of one.
+    Multiplier { factor: u32 },
+    /// Each claim gives this many stamps on top of the usual one.
+    Bonus { stamps: u32 }
+}
+
+/// A promotion such as double stamps on Mondays. Times are RFC 3339 and weekdays are in UTC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CampaignDetails {
+    pub campaign_id: String,
+    pub name: String,
+    pub starts: String,
+    pub ends: String,
+    /// Limits the campaign to these days, every day when empty.
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+    /// Limits the campaign to these stores, every store when empty.
+    #[serde(default)]
+    pub stores: Vec<StoreId>,
+    /// Limits the campaign to stores in these programmes, every programme when empty.
+    #[serde(default)]
+    pub programmes: Vec<String>,
+    pub reward: CampaignReward
+}
+
+/// The code behind a customer's referral link.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReferralLinkResponse {
+    pub code: String
+}
+
+/// A new customer who made their first claim through someone's referral link.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReferralResponse {
+    pub referrer: String,
+    pub referred: String,
+    /// Stamps given to each of them.
+    pub bonus: u32,
+    pub timestamp: String
+}
+
+/// The business a client is talking to, for its header and links.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TenantResponse {
+    /// Names the business in its path prefix and subdomain, missing for the server's own business.
+    pub tenant_id: Option<String>,
+    pub name: String
+}
+
+/// How a business's pages look. Colours are css hex colours such as `#778899`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ThemeDetails {
+    /// Shown in the card header, next to the business's name.
+    #[serde(default)]
+    pub logo_url: Option<String>,
+    pub background_colour: String,
+    /// Headings, and the dark squares of QR codes.
+    pub text_colour: String,
+    /// The inside of each stamp slot.
+    pub stamp_colour: String,
+    /// Drawn in a stamped slot when there is no stamp icon.
+    pub stamp_text: String,
+    #[serde(default)]
+    pub stamp_icon_url: Option<String>,
+    /// Drawn in each slot still to be stamped, which is left plain when missing.
+    #[serde(default)]
+    pub empty_slot_url: Option<String>,
+    pub card_title: String,
+    /// Shown to the customer when they redeem their card.
+    pub reward_text: String
+}
+
+impl Default for ThemeDetails {
+    fn default() -> Self {
+        ThemeDetails {
+            logo_url: None,
+            background_colour: String::from("#778899"),
+            text_colour: String::from("#ffffff"),
+            stamp_colour: String::from("#ffffff"),
+            stamp_text: String::from("7oz"),
+            stamp_icon_url: None,
+            empty_slot_url: None,
+            card_title: String::from("Your Loyalty Card"),
+            reward_text: String::from("Coffee on the way!")
+        }
+    }
+}
+
+/// Which stamp slot an uploaded image is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum StampSlot {
+    Stamped,
+    Empty
+}
+
+impl StampSlot {
+    /// As it appears in the image's path.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StampSlot::Stamped => "stamped",
+            StampSlot::Empty => "empty"
+        }
+    }
+}
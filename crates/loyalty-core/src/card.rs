@@ -0,0 +1,24 @@
+use crate::api::v1::CardKind;
+use crate::UserId;
+
+/// What every kind of card a programme issues has in common, so they can share storage, events and the API.
+pub trait LoyaltyCard: Sized {
+    /// An empty card for a customer seen for the first time.
+    fn new(user_id: UserId) -> Self;
+
+    fn user_id(&self) -> &UserId;
+
+    fn kind(&self) -> CardKind;
+
+    /// The stamps or points on the card now.
+    fn balance(&self) -> u32;
+
+    /// The most the card can hold, if there is a limit.
+    fn limit(&self) -> Option<u32>;
+
+    /// The card with `amount` more on it, never going over its limit.
+    fn with_earned(&self, amount: u32) -> Self;
+
+    /// The card with `cost` taken off, or nothing when it cannot afford it.
+    fn with_spent(&self, cost: u32) -> Option<Self>;
+}
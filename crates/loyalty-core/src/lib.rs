@@ -1,24 +1,34 @@
-use std::cmp::min;
 use std::fmt::{Display, Formatter};
-use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
+pub mod api;
+pub mod card;
 pub mod qr_gen;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub struct UserId(pub String);
 
+#[derive(Debug, Hash, Eq, PartialEq, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StoreId(pub String);
+
 pub struct PhoneNumber {
     number: [u32;11]
 }
 
+/// A card is always kept under the bare digits, however the number was typed.
+impl From<PhoneNumber> for UserId {
+    fn from(phone: PhoneNumber) -> Self {
+        UserId(phone.number.iter().map(|digit| char::from_digit(*digit, 10).unwrap_or('0')).collect())
+    }
+}
+
 impl TryFrom<&str> for PhoneNumber {
     type Error = &'static str;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         let num: Vec<u32> = value.chars()
-            .map(|c| c.to_digit(10))
-            .filter_map(|x| x)
+            .filter_map(|c| c.to_digit(10))
             .collect();
         
         match num.as_slice() {
@@ -40,3 +50,8 @@ impl Display for UserId {
     }
 }
 
+impl Display for StoreId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -28,7 +28,7 @@ impl CustomerQrCode {
         }
     }
     
-    pub fn render(&self) -> Renderer<Color> {
+    pub fn render(&self) -> Renderer<'_, Color<'_>> {
         self.qr.render()
     }
     
@@ -37,6 +37,12 @@ impl CustomerQrCode {
     }
 }
 
+impl Default for CustomerQrCode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl From<String> for CustomerQrCode {
     fn from(value: String) -> Self {
         Self {
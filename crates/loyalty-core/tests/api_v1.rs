@@ -0,0 +1,482 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use loyalty_core::api::v1::*;
+use loyalty_core::StoreId;
+
+/// Checks a body serialises to exactly `expected` and that `expected` reads back to the same body,
+/// so renaming, adding or removing a field on either side fails here first.
+fn assert_shape<T>(body: T, expected: Value)
+where
+    T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+{
+    assert_eq!(serde_json::to_value(&body).unwrap(), expected);
+    assert_eq!(serde_json::from_value::<T>(expected).unwrap(), body);
+}
+
+/// Bodies exactly as the first release of v1 wrote them. Later additions must leave these reading,
+/// so this test is never edited, only the shape tests below as fields are added.
+#[test]
+fn first_release_bodies_still_read() {
+    fn reads<T: DeserializeOwned>(body: Value) {
+        if let Err(err) = serde_json::from_value::<T>(body.clone()) {
+            panic!("{} no longer reads: {}", body, err);
+        }
+    }
+
+    reads::<ErrorResponse>(json!({ "code": "invalid_code", "message": "That code is not valid" }));
+    reads::<CodeResponse>(json!({ "code": "abc123" }));
+    reads::<ClaimRequest>(json!({ "id": "07715559999", "code": "abc123" }));
+    reads::<CardResponse>(json!({ "stamps": 3 }));
+    reads::<CardUpdate>(json!({ "event": "stamped", "stamps": 4, "capacity": 10 }));
+    reads::<PairingCodeRequest>(json!({ "store_id": "high-street" }));
+    reads::<PairingCodeResponse>(json!({ "code": "ABC123", "expires_in": 600 }));
+    reads::<PairRequest>(json!({ "code": "ABC123" }));
+    reads::<PairResponse>(json!({ "device_key": "key", "store_id": "high-street" }));
+    reads::<DeviceResponse>(json!({ "device_id": "d1", "store_id": "high-street", "created": "2024-01-01T00:00:00Z", "revoked": false }));
+}
+
+#[test]
+fn error_response_shape() {
+    assert_shape(
+        ErrorResponse { code: "invalid_code".into(), message: "That code is not valid".into() },
+        json!({ "code": "invalid_code", "message": "That code is not valid" }),
+    );
+}
+
+#[test]
+fn code_response_shape() {
+    assert_shape(CodeResponse { code: "abc123".into() }, json!({ "code": "abc123" }));
+}
+
+#[test]
+fn claim_request_shape() {
+    assert_shape(
+        ClaimRequest { id: "07715559999".into(), code: "abc123".into(), referral: Some("FRIEND12".into()) },
+        json!({ "id": "07715559999", "code": "abc123", "referral": "FRIEND12" }),
+    );
+    assert_shape(ClaimResponse { card_key: "k3y".into() }, json!({ "card_key": "k3y" }));
+    // older clients do not send a referral
+    assert_eq!(
+        serde_json::from_value::<ClaimRequest>(json!({ "id": "07715559999", "code": "abc123" })).unwrap(),
+        ClaimRequest { id: "07715559999".into(), code: "abc123".into(), referral: None },
+    );
+}
+
+#[test]
+fn card_response_shape() {
+    assert_shape(
+        CardResponse { stamps: 3, capacity: 10, kind: CardKind::Stamps, expiring: vec![], birth_month: None, total_stamps: 3, redemptions: 0, tier: None, rewards: vec![] },
+        json!({ "stamps": 3, "capacity": 10, "kind": "stamps", "expiring": [], "birth_month": null, "total_stamps": 3, "redemptions": 0, "tier": null, "rewards": [] }),
+    );
+    assert_shape(
+        CardResponse {
+            stamps: 3,
+            capacity: 8,
+            kind: CardKind::Stamps,
+            expiring: vec![StampExpiry { stamps: 2, expires: "2024-06-01T09:00:00Z".into() }],
+            birth_month: Some(7),
+            total_stamps: 53,
+            redemptions: 5,
+            tier: Some(TierProgress {
+                current: Some(Tier { name: "Silver".into(), redemptions: 5, capacity: None, perks: vec!["Free syrup".into()] }),
+                next: Some(Tier { name: "Gold".into(), redemptions: 10, capacity: Some(8), perks: vec![] })
+            }),
+            rewards: vec![]
+        },
+        json!({
+            "stamps": 3,
+            "capacity": 8,
+            "kind": "stamps",
+            "expiring": [{ "stamps": 2, "expires": "2024-06-01T09:00:00Z" }],
+            "birth_month": 7,
+            "total_stamps": 53,
+            "redemptions": 5,
+            "tier": {
+                "current": { "name": "Silver", "redemptions": 5, "capacity": null, "perks": ["Free syrup"] },
+                "next": { "name": "Gold", "redemptions": 10, "capacity": 8, "perks": [] }
+            },
+            "rewards": []
+        }),
+    );
+    // a stamp card by default, and cards from before tiers leave the lifetime counters and capacity out
+    assert_eq!(
+        serde_json::from_value::<CardResponse>(json!({ "stamps": 3 })).unwrap(),
+        CardResponse { stamps: 3, capacity: 0, kind: CardKind::Stamps, expiring: vec![], birth_month: None, total_stamps: 0, redemptions: 0, tier: None, rewards: vec![] },
+    );
+    assert_shape(BirthdayRequest { month: Some(7) }, json!({ "month": 7 }));
+}
+
+#[test]
+fn card_update_shape() {
+    assert_shape(
+        CardUpdate { event: CardEvent::Stamped, stamps: 4, capacity: 10, kind: CardKind::Stamps },
+        json!({ "event": "stamped", "stamps": 4, "capacity": 10, "kind": "stamps" }),
+    );
+    assert_shape(
+        CardUpdate { event: CardEvent::Earned, stamps: 120, capacity: 0, kind: CardKind::Points },
+        json!({ "event": "earned", "stamps": 120, "capacity": 0, "kind": "points" }),
+    );
+    assert_eq!(serde_json::to_value(CardEvent::Snapshot).unwrap(), json!("snapshot"));
+    assert_eq!(serde_json::to_value(CardEvent::Redeemed).unwrap(), json!("redeemed"));
+    assert_eq!(serde_json::to_value(CardEvent::Adjusted).unwrap(), json!("adjusted"));
+    assert_eq!(serde_json::to_value(CardEvent::Expired).unwrap(), json!("expired"));
+    assert_eq!(serde_json::to_value(CardEvent::Referral).unwrap(), json!("referral"));
+    assert_eq!(serde_json::to_value(CardEvent::Bonus).unwrap(), json!("bonus"));
+    assert_eq!(serde_json::to_value(CardEvent::Promoted).unwrap(), json!("promoted"));
+}
+
+#[test]
+fn points_shapes() {
+    let pastry = CatalogueReward { reward_id: "pastry".into(), name: "A pastry".into(), cost: 50 };
+    assert_shape(pastry.clone(), json!({ "reward_id": "pastry", "name": "A pastry", "cost": 50 }));
+    assert_shape(
+        ProgrammeDetails {
+            programme_id: "bakery".into(),
+            name: "Bakery Points".into(),
+            reward: "Treats".into(),
+            expiry: None,
+            bonuses: BonusRules::default(),
+            tiers: vec![],
+            kind: CardKind::Points,
+            points_rate: Some(10),
+            catalogue: vec![pastry],
+        },
+        json!({
+            "programme_id": "bakery",
+            "name": "Bakery Points",
+            "reward": "Treats",
+            "expiry": null,
+            "bonuses": { "birthday": null, "milestones": [] },
+            "tiers": [],
+            "kind": "points",
+            "points_rate": 10,
+            "catalogue": [{ "reward_id": "pastry", "name": "A pastry", "cost": 50 }]
+        }),
+    );
+    assert_shape(EarnRequest { spend: 450, programme_id: None }, json!({ "spend": 450, "programme_id": null }));
+    assert_eq!(serde_json::from_value::<EarnRequest>(json!({ "spend": 450 })).unwrap(), EarnRequest { spend: 450, programme_id: None });
+    assert_shape(SpendRequest { reward_id: "pastry".into() }, json!({ "reward_id": "pastry" }));
+    assert_eq!(serde_json::to_value(ActivityKind::Earned).unwrap(), json!("earned"));
+}
+
+#[test]
+fn pairing_shapes() {
+    assert_shape(PairingCodeRequest { store_id: "high-street".into() }, json!({ "store_id": "high-street" }));
+    assert_shape(
+        PairingCodeResponse { code: "ABC123".into(), expires_in: 600 },
+        json!({ "code": "ABC123", "expires_in": 600 }),
+    );
+    assert_shape(PairRequest { code: "ABC123".into() }, json!({ "code": "ABC123" }));
+    assert_shape(
+        PairResponse { device_key: "key".into(), store_id: StoreId("high-street".into()) },
+        json!({ "device_key": "key", "store_id": "high-street" }),
+    );
+}
+
+#[test]
+fn device_response_shape() {
+    assert_shape(
+        DeviceResponse {
+            device_id: "device".into(),
+            store_id: StoreId("high-street".into()),
+            created: "2024-03-01T09:00:00Z".into(),
+            revoked: false,
+        },
+        json!({ "device_id": "device", "store_id": "high-street", "created": "2024-03-01T09:00:00Z", "revoked": false }),
+    );
+}
+
+#[test]
+fn admin_card_shape() {
+    let activity = ActivityResponse {
+        user_id: "07715559999".into(),
+        kind: ActivityKind::Adjusted,
+        delta: -2,
+        stamps: 3,
+        store_id: Some(StoreId("high-street".into())),
+        reason: Some("Stamped twice by mistake".into()),
+        staff: Some("admin".into()),
+        campaign: None,
+        timestamp: "2024-03-01T09:00:00Z".into(),
+    };
+    let activity_json = json!({
+        "user_id": "07715559999",
+        "kind": "adjusted",
+        "delta": -2,
+        "stamps": 3,
+        "store_id": "high-street",
+        "reason": "Stamped twice by mistake",
+        "staff": "admin",
+        "campaign": null,
+        "timestamp": "2024-03-01T09:00:00Z"
+    });
+    assert_shape(activity.clone(), activity_json.clone());
+    assert_shape(
+        AdminCardResponse { user_id: "07715559999".into(), stamps: 3, capacity: 10, total_stamps: 23, redemptions: 2, rewards: vec![CatalogueReward { reward_id: "pastry".into(), name: "A pastry".into(), cost: 5 }], activity: vec![activity] },
+        json!({ "user_id": "07715559999", "stamps": 3, "capacity": 10, "total_stamps": 23, "redemptions": 2, "rewards": [{ "reward_id": "pastry", "name": "A pastry", "cost": 5 }], "activity": [activity_json] }),
+    );
+    assert_shape(
+        AdjustRequest { delta: 1, reason: "Till was down".into() },
+        json!({ "delta": 1, "reason": "Till was down" }),
+    );
+}
+
+#[test]
+fn store_and_programme_shapes() {
+    assert_shape(
+        ProgrammeDetails {
+            programme_id: "coffee".into(),
+            name: "Coffee Card".into(),
+            reward: "A free coffee".into(),
+            expiry: None,
+            bonuses: BonusRules { birthday: Some(2), milestones: vec![MilestoneBonus { cards: 5, stamps: 3 }] },
+            tiers: vec![Tier { name: "Gold".into(), redemptions: 10, capacity: Some(8), perks: vec!["Free oat milk".into()] }],
+            kind: CardKind::Stamps,
+            points_rate: None,
+            catalogue: vec![],
+        },
+        json!({
+            "programme_id": "coffee",
+            "name": "Coffee Card",
+            "reward": "A free coffee",
+            "expiry": null,
+            "bonuses": { "birthday": 2, "milestones": [{ "cards": 5, "stamps": 3 }] },
+            "tiers": [{ "name": "Gold", "redemptions": 10, "capacity": 8, "perks": ["Free oat milk"] }],
+            "kind": "stamps",
+            "points_rate": null,
+            "catalogue": []
+        }),
+    );
+    assert_shape(ExpiryPolicy::StampAge { months: 6 }, json!({ "kind": "stamp_age", "months": 6 }));
+    assert_shape(ExpiryPolicy::Inactivity { months: 12 }, json!({ "kind": "inactivity", "months": 12 }));
+    assert_shape(
+        StoreDetails { store_id: StoreId("high-street".into()), name: "High Street".into(), programme_id: None },
+        json!({ "store_id": "high-street", "name": "High Street", "programme_id": null }),
+    );
+}
+
+#[test]
+fn analytics_shapes() {
+    assert_shape(
+        DailyStamps { date: "2024-03-01".into(), store_id: Some(StoreId("high-street".into())), stamps: 12 },
+        json!({ "date": "2024-03-01", "store_id": "high-street", "stamps": 12 }),
+    );
+    assert_shape(
+        DailyRedemptions { date: "2024-03-01".into(), redemptions: 2 },
+        json!({ "date": "2024-03-01", "redemptions": 2 }),
+    );
+    assert_shape(
+        DailyCustomers { date: "2024-03-01".into(), active: 9, new: 3, returning: 6 },
+        json!({ "date": "2024-03-01", "active": 9, "new": 3, "returning": 6 }),
+    );
+    assert_shape(StampCount { stamps: 4, cards: 17 }, json!({ "stamps": 4, "cards": 17 }));
+}
+
+#[test]
+fn card_record_shape() {
+    assert_shape(
+        CardRecord {
+            user_id: "07715559999".into(),
+            stamps: 4,
+            capacity: Some(10),
+            kind: CardKind::Stamps,
+            referral_code: Some("FRIEND12".into()),
+            programme_id: Some("coffee".into()),
+            stamped: vec!["2024-03-01T09:00:00Z".into()],
+            last_activity: Some("2024-03-02T09:00:00Z".into()),
+            birth_month: None,
+            birthday_bonus_year: Some(2023),
+            total_stamps: 24,
+            redemptions: 2,
+            history: vec![]
+        },
+        json!({
+            "user_id": "07715559999",
+            "stamps": 4,
+            "capacity": 10,
+            "kind": "stamps",
+            "referral_code": "FRIEND12",
+            "programme_id": "coffee",
+            "stamped": ["2024-03-01T09:00:00Z"],
+            "last_activity": "2024-03-02T09:00:00Z",
+            "birth_month": null,
+            "birthday_bonus_year": 2023,
+            "total_stamps": 24,
+            "redemptions": 2,
+            "history": []
+        }),
+    );
+    // history, capacity and the rest may be left out of an import, which then makes a stamp card
+    assert_eq!(
+        serde_json::from_value::<CardRecord>(json!({ "user_id": "07715559999", "stamps": 4 })).unwrap(),
+        CardRecord {
+            user_id: "07715559999".into(),
+            stamps: 4,
+            capacity: None,
+            kind: CardKind::Stamps,
+            referral_code: None,
+            programme_id: None,
+            stamped: vec![],
+            last_activity: None,
+            birth_month: None,
+            birthday_bonus_year: None,
+            total_stamps: 0,
+            redemptions: 0,
+            history: vec![]
+        },
+    );
+    assert_shape(
+        ImportSummary { created: 2, updated: 1, history: 7 },
+        json!({ "created": 2, "updated": 1, "history": 7 }),
+    );
+}
+
+#[test]
+fn personal_data_shapes() {
+    assert_shape(DataRequest { user_id: "07715559999".into() }, json!({ "user_id": "07715559999" }));
+    assert_shape(
+        DataRequestResponse { token: "token".into(), code: "ABC123".into(), expires_in: 1800 },
+        json!({ "token": "token", "code": "ABC123", "expires_in": 1800 }),
+    );
+    assert_shape(
+        ApproveDataRequest { user_id: "07715559999".into(), code: "ABC123".into() },
+        json!({ "user_id": "07715559999", "code": "ABC123" }),
+    );
+    assert_shape(
+        PersonalDataResponse {
+            user_id: "07715559999".into(),
+            card: Some(CardRecord {
+                user_id: "07715559999".into(),
+                stamps: 2,
+                capacity: Some(10),
+                kind: CardKind::Stamps,
+                referral_code: None,
+                programme_id: None,
+                stamped: vec![],
+                last_activity: None,
+                birth_month: None,
+                birthday_bonus_year: None,
+                total_stamps: 2,
+                redemptions: 0,
+                history: vec![]
+            }),
+            points: Some(40),
+            points_card: None,
+            referrals: vec![ReferralResponse {
+                referrer: "07715559999".into(),
+                referred: "07700900001".into(),
+                bonus: 1,
+                timestamp: "2024-03-01T09:00:00Z".into()
+            }],
+            audit: vec![AuditRecord {
+                timestamp: "2024-03-01T09:00:00Z".into(),
+                action: "stamp_too_soon".into(),
+                ip: Some("10.0.0.1".into()),
+                detail: "card 07715559999 was stamped too recently".into(),
+            }],
+        },
+        json!({
+            "user_id": "07715559999",
+            "card": {
+                "user_id": "07715559999",
+                "stamps": 2,
+                "capacity": 10,
+                "kind": "stamps",
+                "referral_code": null,
+                "programme_id": null,
+                "stamped": [],
+                "last_activity": null,
+                "birth_month": null,
+                "birthday_bonus_year": null,
+                "total_stamps": 2,
+                "redemptions": 0,
+                "history": []
+            },
+            "points": 40,
+            "points_card": null,
+            "referrals": [{ "referrer": "07715559999", "referred": "07700900001", "bonus": 1, "timestamp": "2024-03-01T09:00:00Z" }],
+            "audit": [{
+                "timestamp": "2024-03-01T09:00:00Z",
+                "action": "stamp_too_soon",
+                "ip": "10.0.0.1",
+                "detail": "card 07715559999 was stamped too recently"
+            }]
+        }),
+    );
+    assert_shape(
+        ErasureResponse { card_deleted: true, activity_anonymised: 12, audit_anonymised: 1 },
+        json!({ "card_deleted": true, "activity_anonymised": 12, "audit_anonymised": 1 }),
+    );
+}
+
+#[test]
+fn campaign_shape() {
+    assert_shape(
+        CampaignDetails {
+            campaign_id: "double-mondays".into(),
+            name: "Double stamps on Mondays".into(),
+            starts: "2024-03-01T00:00:00Z".into(),
+            ends: "2024-04-01T00:00:00Z".into(),
+            weekdays: vec![Weekday::Monday],
+            stores: vec![StoreId("high-street".into())],
+            programmes: vec![],
+            reward: CampaignReward::Multiplier { factor: 2 },
+        },
+        json!({
+            "campaign_id": "double-mondays",
+            "name": "Double stamps on Mondays",
+            "starts": "2024-03-01T00:00:00Z",
+            "ends": "2024-04-01T00:00:00Z",
+            "weekdays": ["monday"],
+            "stores": ["high-street"],
+            "programmes": [],
+            "reward": { "kind": "multiplier", "factor": 2 }
+        }),
+    );
+    assert_shape(CampaignReward::Bonus { stamps: 1 }, json!({ "kind": "bonus", "stamps": 1 }));
+}
+
+#[test]
+fn referral_shapes() {
+    assert_shape(ReferralLinkResponse { code: "FRIEND12".into() }, json!({ "code": "FRIEND12" }));
+    assert_shape(
+        ReferralResponse {
+            referrer: "07715559999".into(),
+            referred: "07715558888".into(),
+            bonus: 1,
+            timestamp: "2024-03-01T09:00:00Z".into(),
+        },
+        json!({ "referrer": "07715559999", "referred": "07715558888", "bonus": 1, "timestamp": "2024-03-01T09:00:00Z" }),
+    );
+}
+
+#[test]
+fn tenant_shapes() {
+    assert_shape(
+        TenantResponse { tenant_id: Some("bakery".into()), name: "The Bakery".into() },
+        json!({ "tenant_id": "bakery", "name": "The Bakery" }),
+    );
+    assert_shape(TenantResponse { tenant_id: None, name: "7oz".into() }, json!({ "tenant_id": null, "name": "7oz" }));
+}
+
+#[test]
+fn theme_shapes() {
+    assert_shape(
+        ThemeDetails::default(),
+        json!({
+            "logo_url": null,
+            "background_colour": "#778899",
+            "text_colour": "#ffffff",
+            "stamp_colour": "#ffffff",
+            "stamp_text": "7oz",
+            "stamp_icon_url": null,
+            "empty_slot_url": null,
+            "card_title": "Your Loyalty Card",
+            "reward_text": "Coffee on the way!"
+        }),
+    );
+    assert_shape(StampSlot::Empty, json!("empty"));
+    assert_eq!(StampSlot::Stamped.as_str(), "stamped");
+}
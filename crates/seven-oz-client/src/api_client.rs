@@ -0,0 +1,360 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use reqwasm::http::{Request, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use web_sys::File;
+use yew::platform::time::sleep;
+
+use loyalty_core::api::v1::{ActivityResponse, AdjustRequest, AdminCardResponse, ApproveDataRequest, BirthdayRequest, CampaignDetails, CardResponse, ClaimRequest, ClaimResponse, CodeResponse, DataRequest, DataRequestResponse, EarnRequest, ErasureResponse, ErrorResponse, PairRequest, PairResponse, PersonalDataResponse, ProgrammeDetails, ReferralLinkResponse, ReferralResponse, SpendRequest, StampSlot, StoreDetails, TenantResponse, ThemeDetails};
+
+use crate::config;
+
+const MAX_ATTEMPTS: u32 = 3;
+const FIRST_BACKOFF: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiClientError {
+    /// The request never got a response, usually because the device is offline.
+    Network(String),
+    /// The server answered with an error body.
+    Api { status: u16, error: ErrorResponse },
+    /// The server answered but the body was not what this client expected.
+    Decode(String),
+}
+
+impl ApiClientError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiClientError::Api { status, .. } => Some(*status),
+            _ => None
+        }
+    }
+
+    /// Text that can be shown to the customer as is.
+    pub fn message(&self) -> String {
+        match self {
+            ApiClientError::Network(_) => String::from("Could not reach the server, please check your connection"),
+            ApiClientError::Api { error, .. } => error.message.clone(),
+            ApiClientError::Decode(_) => String::from("Something went wrong, please try again"),
+        }
+    }
+
+    fn is_retryable(&self) -> bool {
+        match self {
+            ApiClientError::Network(_) => true,
+            ApiClientError::Api { status, .. } => *status >= 500,
+            ApiClientError::Decode(_) => false
+        }
+    }
+}
+
+impl Display for ApiClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiClientError::Network(err) => write!(f, "network error: {}", err),
+            ApiClientError::Api { status, error } => write!(f, "{} {}: {}", status, error.code, error.message),
+            ApiClientError::Decode(err) => write!(f, "unexpected response: {}", err),
+        }
+    }
+}
+
+/// Typed access to the loyalty api so pages never build urls or requests themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoyaltyApiClient {
+    base: String,
+    device_key: Option<String>,
+    admin_key: Option<String>
+}
+
+impl Default for LoyaltyApiClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoyaltyApiClient {
+    pub fn new() -> Self {
+        LoyaltyApiClient {
+            base: config::api_base().to_string(),
+            device_key: None,
+            admin_key: None
+        }
+    }
+
+    /// Authenticates display requests with the key handed out when the display was paired.
+    pub fn with_device_key(mut self, device_key: &str) -> Self {
+        self.device_key = Some(device_key.to_string());
+        self
+    }
+
+    /// Authenticates requests from the admin pages with the owner's admin key.
+    pub fn with_admin_key(mut self, admin_key: &str) -> Self {
+        self.admin_key = Some(admin_key.to_string());
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}/api{}", self.base, config::tenant_prefix(), path)
+    }
+
+    pub async fn get_tenant(&self) -> Result<TenantResponse, ApiClientError> {
+        let url = self.url("/tenant");
+        let resp = self.send_with_retry(|| Request::get(&url)).await?;
+        decode(resp).await
+    }
+
+    pub async fn get_theme(&self) -> Result<ThemeDetails, ApiClientError> {
+        let url = self.url("/theme");
+        let resp = self.send_with_retry(|| Request::get(&url)).await?;
+        decode(resp).await
+    }
+
+    pub async fn save_theme(&self, theme: &ThemeDetails) -> Result<ThemeDetails, ApiClientError> {
+        let resp = send(self.authorise(post_json(&self.url("/admin/theme"), theme))).await?;
+        decode(resp).await
+    }
+
+    /// Sends the image file as is; the server cleans SVGs and points the theme at the stored image.
+    pub async fn upload_stamp_image(&self, slot: StampSlot, file: &File) -> Result<ThemeDetails, ApiClientError> {
+        let request = Request::post(&self.url(&format!("/admin/theme/images/{}", slot.as_str())))
+            .header("Content-Type", &file.type_())
+            .body(file.clone());
+        let resp = send(self.authorise(request)).await?;
+        decode(resp).await
+    }
+
+    pub async fn get_code(&self) -> Result<CodeResponse, ApiClientError> {
+        let url = self.url("/customercode");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    /// The event stream of new codes; EventSource cannot send headers so the key goes in the query.
+    pub fn code_stream_url(&self) -> String {
+        let key = self.device_key.as_deref().unwrap_or_default();
+        format!("{}?access_token={}", self.url("/customercode/stream"), key)
+    }
+
+    pub async fn claim_code(&self, claim: &ClaimRequest) -> Result<ClaimResponse, ApiClientError> {
+        let resp = send(post_json(&self.url("/customercode/claim"), claim)).await?;
+        decode(resp).await
+    }
+
+    pub async fn get_card(&self, id: &str) -> Result<CardResponse, ApiClientError> {
+        let url = self.url(&format!("/stampcard/{}", id));
+        let resp = self.send_with_retry(|| Request::get(&url)).await?;
+        decode(resp).await
+    }
+
+    pub async fn referral_link(&self, id: &str) -> Result<ReferralLinkResponse, ApiClientError> {
+        let url = self.url(&format!("/stampcard/{}/referral", id));
+        let resp = self.send_with_retry(|| Request::get(&url)).await?;
+        decode(resp).await
+    }
+
+    pub async fn set_birthday(&self, id: &str, month: Option<u32>) -> Result<CardResponse, ApiClientError> {
+        let resp = send(post_json(&self.url(&format!("/stampcard/{}/birthday", id)), &BirthdayRequest { month })).await?;
+        decode(resp).await
+    }
+
+    /// The event stream of changes to a card, opened with the key its claims were answered with.
+    pub fn card_stream_url(&self, id: &str, card_key: &str) -> String {
+        format!("{}?access_token={}", self.url(&format!("/stampcard/{}/stream", id)), card_key)
+    }
+
+    pub async fn redeem_card(&self, id: &str) -> Result<(), ApiClientError> {
+        send(self.authorise(Request::post(&self.url(&format!("/stampcard/{}/reset", id))))).await?;
+        Ok(())
+    }
+
+    pub async fn redeem_reward(&self, id: &str, reward_id: &str) -> Result<CardResponse, ApiClientError> {
+        let body = SpendRequest { reward_id: reward_id.to_string() };
+        let request = post_json(&self.url(&format!("/stampcard/{}/redeem", id)), &body);
+        let resp = send(self.authorise(request)).await?;
+        decode(resp).await
+    }
+
+    pub async fn pair_device(&self, code: &str) -> Result<PairResponse, ApiClientError> {
+        let body = PairRequest { code: code.to_string() };
+        let resp = send(post_json(&self.url("/devices/pair"), &body)).await?;
+        decode(resp).await
+    }
+
+    pub async fn lookup_card(&self, id: &str) -> Result<AdminCardResponse, ApiClientError> {
+        let url = self.url(&format!("/admin/cards/{}", id));
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn adjust_card(&self, id: &str, adjustment: &AdjustRequest) -> Result<CardResponse, ApiClientError> {
+        let request = post_json(&self.url(&format!("/stampcard/{}/adjust", id)), adjustment);
+        let resp = send(self.authorise(request)).await?;
+        decode(resp).await
+    }
+
+    pub async fn get_points(&self, id: &str) -> Result<CardResponse, ApiClientError> {
+        let url = self.url(&format!("/points/{}", id));
+        let resp = self.send_with_retry(|| Request::get(&url)).await?;
+        decode(resp).await
+    }
+
+    pub async fn earn_points(&self, id: &str, earn: &EarnRequest) -> Result<CardResponse, ApiClientError> {
+        let request = post_json(&self.url(&format!("/points/{}/earn", id)), earn);
+        let resp = send(self.authorise(request)).await?;
+        decode(resp).await
+    }
+
+    pub async fn redeem_points(&self, id: &str, reward_id: &str) -> Result<CardResponse, ApiClientError> {
+        let body = SpendRequest { reward_id: reward_id.to_string() };
+        let request = post_json(&self.url(&format!("/points/{}/redeem", id)), &body);
+        let resp = send(self.authorise(request)).await?;
+        decode(resp).await
+    }
+
+    pub async fn list_activity(&self) -> Result<Vec<ActivityResponse>, ApiClientError> {
+        let url = self.url("/admin/activity");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn list_referrals(&self) -> Result<Vec<ReferralResponse>, ApiClientError> {
+        let url = self.url("/admin/referrals");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn list_programmes(&self) -> Result<Vec<ProgrammeDetails>, ApiClientError> {
+        let url = self.url("/admin/programmes");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn save_programme(&self, programme: &ProgrammeDetails) -> Result<ProgrammeDetails, ApiClientError> {
+        let resp = send(self.authorise(post_json(&self.url("/admin/programmes"), programme))).await?;
+        decode(resp).await
+    }
+
+    pub async fn delete_programme(&self, programme_id: &str) -> Result<(), ApiClientError> {
+        let url = self.url(&format!("/admin/programmes/{}", programme_id));
+        send(self.authorise(Request::delete(&url))).await?;
+        Ok(())
+    }
+
+    pub async fn list_stores(&self) -> Result<Vec<StoreDetails>, ApiClientError> {
+        let url = self.url("/admin/stores");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn save_store(&self, store: &StoreDetails) -> Result<StoreDetails, ApiClientError> {
+        let resp = send(self.authorise(post_json(&self.url("/admin/stores"), store))).await?;
+        decode(resp).await
+    }
+
+    pub async fn delete_store(&self, store_id: &str) -> Result<(), ApiClientError> {
+        let url = self.url(&format!("/admin/stores/{}", store_id));
+        send(self.authorise(Request::delete(&url))).await?;
+        Ok(())
+    }
+
+    pub async fn list_campaigns(&self) -> Result<Vec<CampaignDetails>, ApiClientError> {
+        let url = self.url("/admin/campaigns");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn save_campaign(&self, campaign: &CampaignDetails) -> Result<CampaignDetails, ApiClientError> {
+        let resp = send(self.authorise(post_json(&self.url("/admin/campaigns"), campaign))).await?;
+        decode(resp).await
+    }
+
+    pub async fn delete_campaign(&self, campaign_id: &str) -> Result<(), ApiClientError> {
+        let url = self.url(&format!("/admin/campaigns/{}", campaign_id));
+        send(self.authorise(Request::delete(&url))).await?;
+        Ok(())
+    }
+
+    /// Campaigns running at the display's store right now.
+    pub async fn live_campaigns(&self) -> Result<Vec<CampaignDetails>, ApiClientError> {
+        let url = self.url("/campaigns/live");
+        let resp = self.send_with_retry(|| self.authorise(Request::get(&url))).await?;
+        decode(resp).await
+    }
+
+    pub async fn request_data(&self, id: &str) -> Result<DataRequestResponse, ApiClientError> {
+        let body = DataRequest { user_id: id.to_string() };
+        let resp = send(post_json(&self.url("/privacy/requests"), &body)).await?;
+        decode(resp).await
+    }
+
+    pub async fn approve_data_request(&self, approval: &ApproveDataRequest) -> Result<(), ApiClientError> {
+        send(self.authorise(post_json(&self.url("/privacy/approve"), approval))).await?;
+        Ok(())
+    }
+
+    /// Fails with a 403 until staff have approved the request behind `token`.
+    pub async fn personal_data(&self, token: &str) -> Result<PersonalDataResponse, ApiClientError> {
+        let request = Request::get(&self.url("/privacy/data"))
+            .header("Authorization", &format!("Bearer {}", token));
+        decode(send(request).await?).await
+    }
+
+    pub async fn erase_data(&self, token: &str) -> Result<ErasureResponse, ApiClientError> {
+        let request = Request::post(&self.url("/privacy/erase"))
+            .header("Authorization", &format!("Bearer {}", token));
+        decode(send(request).await?).await
+    }
+
+    fn authorise(&self, request: Request) -> Request {
+        match self.admin_key.as_ref().or(self.device_key.as_ref()) {
+            Some(key) => request.header("Authorization", &format!("Bearer {}", key)),
+            None => request
+        }
+    }
+
+    /// Sends a request that is safe to repeat, backing off between attempts while the failure looks temporary.
+    async fn send_with_retry(&self, build: impl Fn() -> Request) -> Result<Response, ApiClientError> {
+        let mut backoff = FIRST_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match send(build()).await {
+                Err(err) if err.is_retryable() && attempt < MAX_ATTEMPTS => {
+                    sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                },
+                result => return result
+            }
+        }
+    }
+}
+
+fn post_json<T: Serialize>(url: &str, body: &T) -> Request {
+    Request::post(url)
+        .body(serde_json::to_string(body).unwrap())
+        .header("Content-Type", "application/json")
+}
+
+/// Sends a request, turning any non 2xx response into an error.
+async fn send(request: Request) -> Result<Response, ApiClientError> {
+    let resp = request.send().await
+        .map_err(|err| ApiClientError::Network(err.to_string()))?;
+
+    if resp.ok() {
+        return Ok(resp);
+    }
+
+    let status = resp.status();
+    let error = resp.json::<ErrorResponse>().await.unwrap_or_else(|_| ErrorResponse {
+        code: String::from("unknown"),
+        message: format!("Something went wrong ({}), please try again", status)
+    });
+    Err(ApiClientError::Api { status, error })
+}
+
+async fn decode<T: DeserializeOwned>(resp: Response) -> Result<T, ApiClientError> {
+    resp.json::<T>().await
+        .map_err(|err| ApiClientError::Decode(err.to_string()))
+}
@@ -0,0 +1,29 @@
+use yew::{function_component, html, Html, Properties};
+
+use loyalty_core::api::v1::{CampaignDetails, CampaignReward};
+
+#[derive(Properties, PartialEq)]
+pub struct CampaignBannerProps {
+    pub campaign: CampaignDetails
+}
+
+/// Shown on a display while a campaign covers claims at its store.
+#[function_component]
+pub fn CampaignBanner(props: &CampaignBannerProps) -> Html {
+    html! {
+        <div class="alert alert-warning my-3" role="status">
+            <h2 class="mb-0">{ &props.campaign.name }</h2>
+            <p class="lead mb-0">{ reward_label(&props.campaign.reward) }</p>
+        </div>
+    }
+}
+
+pub fn reward_label(reward: &CampaignReward) -> String {
+    match *reward {
+        CampaignReward::Multiplier { factor: 2 } => String::from("Double stamps"),
+        CampaignReward::Multiplier { factor: 3 } => String::from("Triple stamps"),
+        CampaignReward::Multiplier { factor } => format!("{}x stamps", factor),
+        CampaignReward::Bonus { stamps: 1 } => String::from("A bonus stamp with every coffee"),
+        CampaignReward::Bonus { stamps } => format!("{} bonus stamps with every coffee", stamps)
+    }
+}
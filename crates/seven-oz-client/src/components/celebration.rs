@@ -0,0 +1,22 @@
+use stylist::Style;
+use yew::{AttrValue, function_component, html, Html, Properties};
+
+const STYLE: &str = include_str!("celebration.css");
+
+#[derive(Properties, PartialEq)]
+pub struct CelebrationProps {
+    pub icon: AttrValue,
+    pub message: AttrValue
+}
+
+#[function_component]
+pub fn Celebration(props: &CelebrationProps) -> Html {
+    let stylesheet = Style::new(STYLE).unwrap();
+
+    html! {
+        <div class={ stylesheet } role="status">
+            <span>{ props.icon.clone() }</span>
+            <h3>{ props.message.clone() }</h3>
+        </div>
+    }
+}
@@ -1,3 +1,4 @@
-
+pub mod campaign_banner;
+pub mod celebration;
 pub mod qrcode_image;
 pub mod stamp_area;
\ No newline at end of file
@@ -2,6 +2,8 @@ use yew::{AttrValue, function_component, Html, html, Properties};
 use loyalty_core::qr_gen;
 use loyalty_core::qr_gen::CustomerQrCode;
 
+use crate::theme;
+
 #[derive(Properties, PartialEq)]
 pub struct QrCodeImageProps {
     pub link: AttrValue,
@@ -14,13 +16,14 @@ pub struct QrCodeImageProps {
 pub fn QrCodeImage(props: &QrCodeImageProps) -> Html {
 
     let qr: CustomerQrCode = String::from(props.link.as_str()).into();
-    
+    let theme = theme::current();
+
     let image = qr
         .render()
         .min_dimensions(props.dim, props.dim)
         .module_dimensions(props.module_dim, props.module_dim)
-        .dark_color(qr_gen::Color("#ffffff"))
-        .light_color(qr_gen::Color("#778899"))
+        .dark_color(qr_gen::Color(&theme.text_colour))
+        .light_color(qr_gen::Color(&theme.background_colour))
         .build();
 
     html! {
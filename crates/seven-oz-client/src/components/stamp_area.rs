@@ -1,6 +1,8 @@
 use stylist::Style;
 use yew::{function_component, html, Html, Properties};
 
+use crate::{config, theme};
+
 const STYLE: &str = include_str!("stamp_area.css");
 
 #[derive(Properties, PartialEq)]
@@ -11,18 +13,17 @@ pub struct StampAreaProps {
 #[function_component]
 pub fn StampArea(props: &StampAreaProps) -> Html {
     let stylesheet = Style::new(STYLE).unwrap();
-    
+    let theme = theme::current();
+
     html! {
         <div class={ stylesheet }>
             <div>
-                { 
-                    if props.is_stamped {
-                        html!{
-                            <p>{ "7oz" }</p>
-                        }
-                    }
-                    else {
-                        html!{}
+                {
+                    match (props.is_stamped, &theme.stamp_icon_url, &theme.empty_slot_url) {
+                        (true, Some(icon), _) => html!{ <img src={config::asset_url(icon)} alt={theme.stamp_text.clone()} /> },
+                        (true, None, _) => html!{ <p>{ &theme.stamp_text }</p> },
+                        (false, _, Some(empty)) => html!{ <img src={config::asset_url(empty)} alt="" /> },
+                        (false, _, None) => html!{}
                     }
                 }
             </div>
@@ -0,0 +1,91 @@
+use std::sync::OnceLock;
+
+use reqwasm::http::Request;
+use serde::Deserialize;
+use web_sys::window;
+
+const CONFIG_PATH: &str = "/config.json";
+const API_BASE_META: &str = "loyalty-api-base";
+/// Pages for a tenant are served under `/t/<tenant>`, the same prefix as its api.
+const TENANT_PATH: &str = "/t/";
+
+static API_BASE: OnceLock<String> = OnceLock::new();
+static TENANT_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Settings a deployment can change without rebuilding the client.
+#[derive(Deserialize)]
+struct ClientConfig {
+    api_base: String
+}
+
+/// Works out where the api lives, checking `/config.json`, then a
+/// `<meta name="loyalty-api-base">` tag, then the value baked in at compile time.
+/// Must finish before any page talks to the api.
+pub async fn load() {
+    let api_base = match fetch_config().await {
+        Some(config) => config.api_base,
+        None => meta_api_base().unwrap_or_else(|| compile_time_api_base().to_string())
+    };
+
+    _ = API_BASE.set(api_base.trim_end_matches('/').to_string());
+    _ = TENANT_PREFIX.set(current_path().as_deref().and_then(tenant_prefix_of).unwrap_or_default());
+}
+
+pub fn api_base() -> &'static str {
+    API_BASE.get().map(String::as_str).unwrap_or_else(compile_time_api_base)
+}
+
+/// The `/t/<tenant>` the page was opened under, empty for the server's own business or a tenant on its own subdomain.
+pub fn tenant_prefix() -> &'static str {
+    TENANT_PREFIX.get().map(String::as_str).unwrap_or_default()
+}
+
+/// Where a themed image lives, as paths such as `/assets/logo.png` are served alongside the api.
+pub fn asset_url(url: &str) -> String {
+    match url.starts_with('/') {
+        true => format!("{}{}", api_base(), url),
+        false => url.to_string()
+    }
+}
+
+/// A local storage key kept apart per tenant, as tenants on paths share one origin's storage.
+pub fn storage_key(name: &str) -> String {
+    format!("{}{}", name, tenant_prefix())
+}
+
+fn current_path() -> Option<String> {
+    window()?.location().pathname().ok()
+}
+
+fn tenant_prefix_of(path: &str) -> Option<String> {
+    let tenant = path.strip_prefix(TENANT_PATH)?.split('/').next()?;
+    (!tenant.is_empty()).then(|| format!("{}{}", TENANT_PATH, tenant))
+}
+
+async fn fetch_config() -> Option<ClientConfig> {
+    let resp = Request::get(CONFIG_PATH).send().await.ok()?;
+    if !resp.ok() {
+        return None;
+    }
+
+    // unknown paths are answered with index.html so a missing file shows up as bad json
+    resp.json::<ClientConfig>().await.ok()
+}
+
+fn meta_api_base() -> Option<String> {
+    let selector = format!("meta[name=\"{}\"]", API_BASE_META);
+    window()?.document()?
+        .query_selector(&selector).ok()??
+        .get_attribute("content")
+        .filter(|content| !content.trim().is_empty())
+}
+
+// because this is a WASM app we cannot read environment variables at runtime
+// so the last resort is a value picked by compile time flags.
+fn compile_time_api_base() -> &'static str {
+    if cfg!(feature = "prod") {
+        "https://7oz-loyalty.shuttleapp.rs"
+    } else {
+        "http://localhost:8000"
+    }
+}
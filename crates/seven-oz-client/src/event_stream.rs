@@ -0,0 +1,45 @@
+use wasm_bindgen_futures::wasm_bindgen::closure::Closure;
+use wasm_bindgen_futures::wasm_bindgen::JsCast;
+use web_sys::{Event, EventSource, MessageEvent};
+use yew::Callback;
+
+/// Holds an open EventSource and its handlers, closing the connection when dropped.
+pub struct EventStream {
+    source: EventSource,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_error: Closure<dyn FnMut(Event)>
+}
+
+impl EventStream {
+    /// Subscribes to the server-sent events at `url`, returning `None` if the browser refuses to.
+    pub fn open(url: &str, message_cb: Callback<String>, error_cb: Callback<()>) -> Option<Self> {
+        let source = EventSource::new(url).ok()?;
+
+        let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(data) = event.data().as_string() {
+                message_cb.emit(data);
+            }
+        });
+
+        let on_error = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            // handling the error may drop this closure so let it return first
+            let error_cb = error_cb.clone();
+            wasm_bindgen_futures::spawn_local(async move { error_cb.emit(()) });
+        });
+
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Some(Self {
+            source,
+            _on_message: on_message,
+            _on_error: on_error
+        })
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        self.source.close();
+    }
+}
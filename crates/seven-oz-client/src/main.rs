@@ -3,12 +3,20 @@ use stylist::{css};
 use stylist::yew::Global;
 use yew_router::prelude::*;
 
+use crate::pages::admin::Admin;
 use crate::pages::collect::Collect;
 use crate::pages::display::Display;
+use crate::pages::points_card::PointsCard;
+use crate::pages::privacy::Privacy;
+use crate::pages::refer::Refer;
 use crate::pages::stamp_card::StampCard;
 
 mod pages;
 mod components;
+mod event_stream;
+mod api_client;
+mod config;
+mod theme;
 
 #[derive(Clone, Routable, PartialEq)]
 enum Route {
@@ -18,6 +26,14 @@ enum Route {
     Collect{ code: String },
     #[at("/my-stamp-card/:id")]
     StampCard{ id: String },
+    #[at("/my-stamp-card/:id/privacy")]
+    Privacy{ id: String },
+    #[at("/my-points/:id")]
+    Points{ id: String },
+    #[at("/refer/:code")]
+    Refer{ code: String },
+    #[at("/admin")]
+    Admin,
     #[not_found]
     #[at("/404")]
     NotFound,
@@ -34,36 +50,45 @@ fn switch(routes: Route) -> Html {
         Route::StampCard{id} => html!{
             <StampCard id={id}/>
         },
+        Route::Privacy{id} => html!{
+            <Privacy id={id}/>
+        },
+        Route::Points{id} => html!{
+            <PointsCard id={id}/>
+        },
+        Route::Refer{code} => html!{
+            <Refer code={code}/>
+        },
+        Route::Admin => html!{
+            <Admin />
+        },
         Route::NotFound => html! { <h1>{ "404" }</h1> },
     }
 }
 
-// because this is a WASM app we cannot read environment variables at runtime 
-// which forces us to configure based on compile time flags.
-fn get_api_base() -> &'static str {
-    return if cfg!(feature = "prod") {
-        "https://7oz-loyalty.shuttleapp.rs"
-    } else {
-        "http://localhost:8000"
-    };
-}
-
 #[function_component(App)]
 fn app() -> Html {
-    //let stylesheet = Style::new("body {background-color: lightslategrey;}").unwrap();
+    let theme = theme::current();
     html! {
         <>
+        // components take their colours from these variables so they follow the business's theme
         <Global css={css!(r#"
+        :root {
+            --loyalty-background: ${background};
+            --loyalty-text: ${text};
+            --loyalty-stamp: ${stamp};
+        }
+
         body {
-            background-color: lightslategrey;
+            background-color: var(--loyalty-background);
         }
         
         h1,h3,label {
-            color: white
+            color: var(--loyalty-text)
         }
         
-        "#)} />
-        <BrowserRouter>
+        "#, background = theme.background_colour, text = theme.text_colour, stamp = theme.stamp_colour)} />
+        <BrowserRouter basename={(!config::tenant_prefix().is_empty()).then_some(AttrValue::Static(config::tenant_prefix()))}>
             <Switch<Route> render={switch} />
         </BrowserRouter>
         </>
@@ -71,5 +96,9 @@ fn app() -> Html {
 }
 
 fn main() {
-    yew::Renderer::<App>::new().render();
+    wasm_bindgen_futures::spawn_local(async {
+        config::load().await;
+        theme::load().await;
+        yew::Renderer::<App>::new().render();
+    });
 }
\ No newline at end of file
@@ -0,0 +1,128 @@
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{ActivityKind, ActivityResponse};
+
+use crate::api_client::ApiClientError;
+use super::AdminSectionProps;
+
+pub struct RecentActivity {
+    activity: Vec<ActivityResponse>,
+    error_msg: Option<AttrValue>
+}
+
+pub enum RecentActivityMsg {
+    Refresh,
+    Loaded(Vec<ActivityResponse>),
+    Failed(ApiClientError)
+}
+
+impl Component for RecentActivity {
+    type Message = RecentActivityMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(RecentActivityMsg::Refresh);
+        Self {
+            activity: Vec::new(),
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            RecentActivityMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.list_activity().await {
+                        Ok(activity) => RecentActivityMsg::Loaded(activity),
+                        Err(err) => RecentActivityMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            RecentActivityMsg::Loaded(activity) => {
+                self.activity = activity;
+                self.error_msg = None;
+                true
+            },
+            RecentActivityMsg::Failed(err) => {
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <div class="d-flex justify-content-between align-items-center">
+                <h3>{"Recent Activity"}</h3>
+                <button type="button" class="btn btn-outline-light"
+                    onclick={ctx.link().callback(|_| RecentActivityMsg::Refresh)}>
+                    {"Refresh"}
+                </button>
+            </div>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            { activity_table(&self.activity) }
+            </>
+        }
+    }
+}
+
+/// Newest first list of card changes, shared with the card lookup.
+pub fn activity_table(activity: &[ActivityResponse]) -> Html {
+    if activity.is_empty() {
+        return html! { <p class="text-white">{"No activity yet"}</p> };
+    }
+
+    html! {
+        <table class="table table-light table-striped">
+            <thead>
+                <tr>
+                    <th>{"When"}</th>
+                    <th>{"Card"}</th>
+                    <th>{"What"}</th>
+                    <th>{"Change"}</th>
+                    <th>{"Stamps"}</th>
+                    <th>{"Store"}</th>
+                    <th>{"Reason"}</th>
+                    <th>{"By"}</th>
+                </tr>
+            </thead>
+            <tbody>
+                { for activity.iter().map(|entry| html! {
+                    <tr>
+                        <td>{ entry.timestamp.replace('T', " ").trim_end_matches('Z').to_string() }</td>
+                        <td>{ &entry.user_id }</td>
+                        <td>
+                            { kind_label(entry.kind) }
+                            if let Some(campaign) = entry.campaign.clone() {
+                                <span class="badge text-bg-warning ms-2">{ campaign }</span>
+                            }
+                        </td>
+                        <td>{ format!("{:+}", entry.delta) }</td>
+                        <td>{ entry.stamps }</td>
+                        <td>{ entry.store_id.as_ref().map(|store_id| store_id.to_string()).unwrap_or_default() }</td>
+                        <td>{ entry.reason.clone().unwrap_or_default() }</td>
+                        <td>{ entry.staff.clone().unwrap_or_default() }</td>
+                    </tr>
+                }) }
+            </tbody>
+        </table>
+    }
+}
+
+fn kind_label(kind: ActivityKind) -> &'static str {
+    match kind {
+        ActivityKind::Stamped => "Stamped",
+        ActivityKind::Redeemed => "Redeemed",
+        ActivityKind::Adjusted => "Adjusted by hand",
+        ActivityKind::Imported => "Imported",
+        ActivityKind::Expired => "Expired",
+        ActivityKind::Referral => "Referral bonus",
+        ActivityKind::Bonus => "Bonus",
+        ActivityKind::Earned => "Points earned"
+    }
+}
@@ -0,0 +1,316 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{CampaignDetails, CampaignReward, Weekday};
+use loyalty_core::StoreId;
+
+use crate::api_client::ApiClientError;
+use crate::components::campaign_banner::reward_label;
+use super::{input_value, set_input_value, AdminSectionProps};
+
+const WEEKDAYS: [(Weekday, &str); 7] = [
+    (Weekday::Monday, "Mon"),
+    (Weekday::Tuesday, "Tue"),
+    (Weekday::Wednesday, "Wed"),
+    (Weekday::Thursday, "Thu"),
+    (Weekday::Friday, "Fri"),
+    (Weekday::Saturday, "Sat"),
+    (Weekday::Sunday, "Sun")
+];
+
+pub struct CampaignManager {
+    campaigns: Vec<CampaignDetails>,
+    id_ref: NodeRef,
+    name_ref: NodeRef,
+    starts_ref: NodeRef,
+    ends_ref: NodeRef,
+    weekday_refs: [NodeRef; 7],
+    stores_ref: NodeRef,
+    programmes_ref: NodeRef,
+    reward_ref: NodeRef,
+    amount_ref: NodeRef,
+    error_msg: Option<AttrValue>
+}
+
+pub enum CampaignManagerMsg {
+    Refresh,
+    Loaded(Vec<CampaignDetails>),
+    Edit(CampaignDetails),
+    Save,
+    Delete(String),
+    Saved,
+    Failed(ApiClientError)
+}
+
+impl Component for CampaignManager {
+    type Message = CampaignManagerMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(CampaignManagerMsg::Refresh);
+        Self {
+            campaigns: Vec::new(),
+            id_ref: NodeRef::default(),
+            name_ref: NodeRef::default(),
+            starts_ref: NodeRef::default(),
+            ends_ref: NodeRef::default(),
+            weekday_refs: Default::default(),
+            stores_ref: NodeRef::default(),
+            programmes_ref: NodeRef::default(),
+            reward_ref: NodeRef::default(),
+            amount_ref: NodeRef::default(),
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            CampaignManagerMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.list_campaigns().await {
+                        Ok(campaigns) => CampaignManagerMsg::Loaded(campaigns),
+                        Err(err) => CampaignManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CampaignManagerMsg::Loaded(campaigns) => {
+                self.campaigns = campaigns;
+                true
+            },
+            CampaignManagerMsg::Edit(campaign) => {
+                set_input_value(&self.id_ref, &campaign.campaign_id);
+                set_input_value(&self.name_ref, &campaign.name);
+                set_input_value(&self.starts_ref, local_time(&campaign.starts));
+                set_input_value(&self.ends_ref, local_time(&campaign.ends));
+                for ((weekday, _), weekday_ref) in WEEKDAYS.iter().zip(&self.weekday_refs) {
+                    set_checked(weekday_ref, campaign.weekdays.contains(weekday));
+                }
+                let stores: Vec<String> = campaign.stores.iter().map(|store_id| store_id.to_string()).collect();
+                set_input_value(&self.stores_ref, &stores.join(", "));
+                set_input_value(&self.programmes_ref, &campaign.programmes.join(", "));
+                let (kind, amount) = match campaign.reward {
+                    CampaignReward::Multiplier { factor } => ("multiplier", factor),
+                    CampaignReward::Bonus { stamps } => ("bonus", stamps)
+                };
+                if let Some(select) = self.reward_ref.cast::<HtmlSelectElement>() {
+                    select.set_value(kind);
+                }
+                set_input_value(&self.amount_ref, &amount.to_string());
+                false
+            },
+            CampaignManagerMsg::Save => {
+                let campaign = self.campaign();
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.save_campaign(&campaign).await {
+                        Ok(_) => CampaignManagerMsg::Saved,
+                        Err(err) => CampaignManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CampaignManagerMsg::Delete(campaign_id) => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.delete_campaign(&campaign_id).await {
+                        Ok(()) => CampaignManagerMsg::Saved,
+                        Err(err) => CampaignManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CampaignManagerMsg::Saved => {
+                for input_ref in [&self.id_ref, &self.name_ref, &self.starts_ref, &self.ends_ref, &self.stores_ref, &self.programmes_ref, &self.amount_ref] {
+                    set_input_value(input_ref, "");
+                }
+                for weekday_ref in &self.weekday_refs {
+                    set_checked(weekday_ref, false);
+                }
+                self.error_msg = None;
+                ctx.link().send_message(CampaignManagerMsg::Refresh);
+                true
+            },
+            CampaignManagerMsg::Failed(err) => {
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Campaigns"}</h3>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            <table class="table table-light table-striped">
+                <thead>
+                    <tr>
+                        <th>{"Id"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Runs"}</th>
+                        <th>{"Days"}</th>
+                        <th>{"Where"}</th>
+                        <th>{"Reward"}</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for self.campaigns.iter().map(|campaign| {
+                        let (edit, delete) = (campaign.clone(), campaign.campaign_id.clone());
+                        html! {
+                            <tr>
+                                <td>{ &campaign.campaign_id }</td>
+                                <td>{ &campaign.name }</td>
+                                <td>{ format!("{} to {}", local_time(&campaign.starts).replace('T', " "), local_time(&campaign.ends).replace('T', " ")) }</td>
+                                <td>{ days_label(&campaign.weekdays) }</td>
+                                <td>{ where_label(campaign) }</td>
+                                <td>{ reward_label(&campaign.reward) }</td>
+                                <td class="text-end">
+                                    <button type="button" class="btn btn-sm btn-outline-secondary me-2"
+                                        onclick={ctx.link().callback(move |_| CampaignManagerMsg::Edit(edit.clone()))}>
+                                        {"Edit"}
+                                    </button>
+                                    <button type="button" class="btn btn-sm btn-outline-danger"
+                                        onclick={ctx.link().callback(move |_| CampaignManagerMsg::Delete(delete.clone()))}>
+                                        {"Delete"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </tbody>
+            </table>
+
+            <form novalidate=true class="row g-2">
+                <div class="col-sm-3">
+                    <label for="campaign_id" class="form-label">{"Id"}</label>
+                    <input type="text" class="form-control" id="campaign_id" ref={&self.id_ref} placeholder="double-mondays"/>
+                </div>
+                <div class="col-sm-5">
+                    <label for="campaign_name" class="form-label">{"Name"}</label>
+                    <input type="text" class="form-control" id="campaign_name" ref={&self.name_ref} placeholder="Double stamps on Mondays"/>
+                </div>
+                <div class="col-sm-2">
+                    <label for="campaign_reward" class="form-label">{"Reward"}</label>
+                    <select class="form-select" id="campaign_reward" ref={&self.reward_ref}>
+                        <option value="multiplier">{"Multiply stamps"}</option>
+                        <option value="bonus">{"Bonus stamps"}</option>
+                    </select>
+                </div>
+                <div class="col-sm-2">
+                    <label for="campaign_amount" class="form-label">{"By"}</label>
+                    <input type="number" min="1" class="form-control" id="campaign_amount" ref={&self.amount_ref} placeholder="2"/>
+                </div>
+                <div class="col-sm-3">
+                    <label for="campaign_starts" class="form-label">{"Starts (UTC)"}</label>
+                    <input type="datetime-local" class="form-control" id="campaign_starts" ref={&self.starts_ref}/>
+                </div>
+                <div class="col-sm-3">
+                    <label for="campaign_ends" class="form-label">{"Ends (UTC)"}</label>
+                    <input type="datetime-local" class="form-control" id="campaign_ends" ref={&self.ends_ref}/>
+                </div>
+                <div class="col-sm-6">
+                    <span class="form-label d-block">{"Only on"}</span>
+                    { for WEEKDAYS.iter().zip(&self.weekday_refs).map(|((_, label), weekday_ref)| {
+                        let id = format!("campaign_{}", label.to_lowercase());
+                        html! {
+                            <div class="form-check form-check-inline">
+                                <input type="checkbox" class="form-check-input" id={id.clone()} ref={weekday_ref}/>
+                                <label class="form-check-label" for={id}>{ *label }</label>
+                            </div>
+                        }
+                    }) }
+                </div>
+                <div class="col-sm-6">
+                    <label for="campaign_stores" class="form-label">{"Stores"}</label>
+                    <input type="text" class="form-control" id="campaign_stores" ref={&self.stores_ref} placeholder="Every store"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="campaign_programmes" class="form-label">{"Programmes"}</label>
+                    <input type="text" class="form-control" id="campaign_programmes" ref={&self.programmes_ref} placeholder="Every programme"/>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-primary"
+                        onclick={ctx.link().callback(|_| CampaignManagerMsg::Save)}>
+                        {"Save campaign"}
+                    </button>
+                </div>
+            </form>
+            </>
+        }
+    }
+}
+
+impl CampaignManager {
+    /// The campaign in the form; an unreadable amount is sent as zero for the server to reject.
+    fn campaign(&self) -> CampaignDetails {
+        let amount = input_value(&self.amount_ref).parse().unwrap_or_default();
+        let reward = match self.reward_ref.cast::<HtmlSelectElement>().map(|select| select.value()).as_deref() {
+            Some("bonus") => CampaignReward::Bonus { stamps: amount },
+            _ => CampaignReward::Multiplier { factor: amount }
+        };
+
+        CampaignDetails {
+            campaign_id: input_value(&self.id_ref),
+            name: input_value(&self.name_ref),
+            starts: utc_time(&input_value(&self.starts_ref)),
+            ends: utc_time(&input_value(&self.ends_ref)),
+            weekdays: WEEKDAYS.iter().zip(&self.weekday_refs)
+                .filter(|(_, weekday_ref)| weekday_ref.cast::<HtmlInputElement>().is_some_and(|input| input.checked()))
+                .map(|((weekday, _), _)| *weekday)
+                .collect(),
+            stores: list(&input_value(&self.stores_ref)).into_iter().map(StoreId).collect(),
+            programmes: list(&input_value(&self.programmes_ref)),
+            reward
+        }
+    }
+}
+
+fn set_checked(input_ref: &NodeRef, checked: bool) {
+    if let Some(input) = input_ref.cast::<HtmlInputElement>() {
+        input.set_checked(checked);
+    }
+}
+
+/// A datetime-local input value has no seconds or zone, the campaign times are UTC.
+fn utc_time(value: &str) -> String {
+    match value.is_empty() {
+        true => String::new(),
+        false => format!("{}:00Z", value)
+    }
+}
+
+fn local_time(value: &str) -> &str {
+    value.get(..16).unwrap_or(value)
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn days_label(weekdays: &[Weekday]) -> String {
+    if weekdays.is_empty() {
+        return String::from("Every day");
+    }
+    WEEKDAYS.iter()
+        .filter(|(weekday, _)| weekdays.contains(weekday))
+        .map(|(_, label)| *label)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn where_label(campaign: &CampaignDetails) -> String {
+    let mut places: Vec<String> = campaign.stores.iter().map(|store_id| store_id.to_string()).collect();
+    places.extend(campaign.programmes.iter().cloned());
+    match places.is_empty() {
+        true => String::from("Everywhere"),
+        false => places.join(", ")
+    }
+}
@@ -0,0 +1,229 @@
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{AdjustRequest, AdminCardResponse};
+use loyalty_core::PhoneNumber;
+
+use crate::api_client::ApiClientError;
+use super::{input_value, set_input_value, AdminSectionProps};
+use super::activity::activity_table;
+
+pub struct CardLookup {
+    phone_ref: NodeRef,
+    amount_ref: NodeRef,
+    reason_ref: NodeRef,
+    card: Option<AdminCardResponse>,
+    error_msg: Option<AttrValue>,
+    notice: Option<AttrValue>
+}
+
+pub enum CardLookupMsg {
+    Lookup,
+    Load(String),
+    Loaded(AdminCardResponse),
+    /// Adds stamps when positive, removes them when negative.
+    Adjust(i32),
+    /// Empties a full card for the programme's reward.
+    RedeemCard,
+    /// Takes a catalogue reward's cost off the card.
+    Redeem(String),
+    Changed(AttrValue),
+    Failed(ApiClientError)
+}
+
+impl Component for CardLookup {
+    type Message = CardLookupMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            phone_ref: NodeRef::default(),
+            amount_ref: NodeRef::default(),
+            reason_ref: NodeRef::default(),
+            card: None,
+            error_msg: None,
+            notice: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            CardLookupMsg::Lookup => {
+                let id = input_value(&self.phone_ref);
+                if let Err(message) = PhoneNumber::try_from(id.as_str()) {
+                    self.error_msg = Some(AttrValue::from(message));
+                    return true;
+                }
+                self.notice = None;
+                ctx.link().send_message(CardLookupMsg::Load(id));
+                false
+            },
+            CardLookupMsg::Load(id) => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.lookup_card(&id).await {
+                        Ok(card) => CardLookupMsg::Loaded(card),
+                        Err(err) => CardLookupMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CardLookupMsg::Loaded(card) => {
+                self.card = Some(card);
+                self.error_msg = None;
+                true
+            },
+            CardLookupMsg::Adjust(sign) => {
+                let Some(card) = &self.card else { return false };
+
+                let amount = input_value(&self.amount_ref).parse::<i32>().unwrap_or(0);
+                let adjustment = AdjustRequest {
+                    delta: sign * amount,
+                    reason: input_value(&self.reason_ref)
+                };
+                if amount < 1 || adjustment.reason.is_empty() {
+                    self.error_msg = Some(AttrValue::from("Enter how many stamps and why"));
+                    return true;
+                }
+
+                let (api, id) = (ctx.props().api.clone(), card.user_id.clone());
+                ctx.link().send_future(async move {
+                    match api.adjust_card(&id, &adjustment).await {
+                        Ok(resp) => CardLookupMsg::Changed(format!("Card now has {} stamps", resp.stamps).into()),
+                        Err(err) => CardLookupMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CardLookupMsg::RedeemCard => {
+                let Some(card) = &self.card else { return false };
+
+                let (api, id) = (ctx.props().api.clone(), card.user_id.clone());
+                ctx.link().send_future(async move {
+                    match api.redeem_card(&id).await {
+                        Ok(()) => CardLookupMsg::Changed(AttrValue::from("Reward redeemed")),
+                        Err(err) => CardLookupMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CardLookupMsg::Redeem(reward_id) => {
+                let Some(card) = &self.card else { return false };
+
+                let (api, id) = (ctx.props().api.clone(), card.user_id.clone());
+                ctx.link().send_future(async move {
+                    match api.redeem_reward(&id, &reward_id).await {
+                        Ok(resp) => CardLookupMsg::Changed(format!("Reward redeemed, card now has {} stamps", resp.stamps).into()),
+                        Err(err) => CardLookupMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            CardLookupMsg::Changed(notice) => {
+                set_input_value(&self.reason_ref, "");
+                self.notice = Some(notice);
+                if let Some(card) = &self.card {
+                    ctx.link().send_message(CardLookupMsg::Load(card.user_id.clone()));
+                }
+                true
+            },
+            CardLookupMsg::Failed(err) => {
+                self.notice = None;
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Look up a card"}</h3>
+            <form novalidate=true class="row g-2 mb-3">
+                <div class="col-sm-8">
+                    <label for="lookup_phone" class="form-label visually-hidden">{"Phone Number"}</label>
+                    <input type="tel" class="form-control" id="lookup_phone" ref={&self.phone_ref} placeholder="07715559999"/>
+                </div>
+                <div class="col-sm-4">
+                    <button type="button" class="btn btn-primary w-100"
+                        onclick={ctx.link().callback(|_| CardLookupMsg::Lookup)}>
+                        {"Look up"}
+                    </button>
+                </div>
+            </form>
+
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            if let Some(notice) = self.notice.clone() {
+                <div class="alert alert-success" role="status">{ notice }</div>
+            }
+
+            if let Some(card) = &self.card {
+                { self.view_card(ctx, card) }
+            }
+            </>
+        }
+    }
+}
+
+impl CardLookup {
+    fn view_card(&self, ctx: &Context<Self>, card: &AdminCardResponse) -> Html {
+        html! {
+            <>
+            <h3>{ format!("{} has {} of {} stamps", card.user_id, card.stamps, card.capacity) }</h3>
+            <p class="text-muted">{ format!("{} stamps and {} cards redeemed in total", card.total_stamps, card.redemptions) }</p>
+
+            <form novalidate=true class="row g-2 mb-3">
+                <div class="col-sm-2">
+                    <label for="adjust_amount" class="form-label">{"Stamps"}</label>
+                    <input type="number" min="1" value="1" class="form-control" id="adjust_amount" ref={&self.amount_ref}/>
+                </div>
+                <div class="col-sm-10">
+                    <label for="adjust_reason" class="form-label">{"Reason"}</label>
+                    <input type="text" class="form-control" id="adjust_reason" ref={&self.reason_ref}
+                        placeholder="Till was down"/>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-success"
+                        onclick={ctx.link().callback(|_| CardLookupMsg::Adjust(1))}>
+                        {"Add stamps"}
+                    </button>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-warning"
+                        onclick={ctx.link().callback(|_| CardLookupMsg::Adjust(-1))}>
+                        {"Remove stamps"}
+                    </button>
+                </div>
+                <div class="col-auto ms-auto">
+                    <button type="button" class="btn btn-danger"
+                        disabled={card.stamps < card.capacity}
+                        onclick={ctx.link().callback(|_| CardLookupMsg::RedeemCard)}>
+                        {"Redeem full card"}
+                    </button>
+                </div>
+            </form>
+
+            if !card.rewards.is_empty() {
+                <ul class="list-group mb-3">
+                    { for card.rewards.iter().map(|reward| {
+                        let reward_id = reward.reward_id.clone();
+                        html! {
+                            <li class="list-group-item d-flex justify-content-between align-items-center">
+                                { format!("{} for {} stamps", reward.name, reward.cost) }
+                                <button type="button" class="btn btn-danger btn-sm"
+                                    disabled={card.stamps < reward.cost}
+                                    onclick={ctx.link().callback(move |_| CardLookupMsg::Redeem(reward_id.clone()))}>
+                                    {"Redeem"}
+                                </button>
+                            </li>
+                        }
+                    }) }
+                </ul>
+            }
+
+            { activity_table(&card.activity) }
+            </>
+        }
+    }
+}
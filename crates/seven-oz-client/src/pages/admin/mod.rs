@@ -0,0 +1,261 @@
+use wasm_bindgen_futures::wasm_bindgen::JsValue;
+use web_sys::{console, window, HtmlInputElement};
+use yew::prelude::*;
+
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::config;
+
+use activity::RecentActivity;
+use campaigns::CampaignManager;
+use cards::CardLookup;
+use points::PointsTill;
+use privacy::DataRequestApproval;
+use programmes::ProgrammeManager;
+use referrals::ReferralList;
+use stores::StoreManager;
+use theme::ThemeEditor;
+
+mod activity;
+mod campaigns;
+mod cards;
+mod points;
+mod privacy;
+mod programmes;
+mod referrals;
+mod stores;
+mod theme;
+
+const ADMIN_KEY_STORAGE: &str = "loyalty-admin-key";
+
+/// Handed to every admin section so they share the signed in client.
+#[derive(Properties, PartialEq)]
+pub struct AdminSectionProps {
+    pub api: LoyaltyApiClient,
+    /// Called when the server stops accepting the admin key.
+    pub on_unauthorised: Callback<()>
+}
+
+impl AdminSectionProps {
+    /// Signs out on a rejected key, otherwise returns the message to show.
+    pub fn handle_error(&self, err: ApiClientError) -> AttrValue {
+        console::log_1(&JsValue::from(format!("Admin request failed. {}", err)));
+        if err.status() == Some(401) {
+            self.on_unauthorised.emit(());
+        }
+        AttrValue::from(err.message())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AdminTab {
+    Cards,
+    Activity,
+    Stores,
+    Programmes,
+    Campaigns,
+    Referrals,
+    Points,
+    Theme,
+    DataRequests
+}
+
+impl AdminTab {
+    const ALL: [AdminTab; 9] = [
+        AdminTab::Cards, AdminTab::Points, AdminTab::Activity, AdminTab::Stores, AdminTab::Programmes, AdminTab::Campaigns, AdminTab::Referrals, AdminTab::Theme, AdminTab::DataRequests
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            AdminTab::Cards => "Cards",
+            AdminTab::Activity => "Recent Activity",
+            AdminTab::Stores => "Stores",
+            AdminTab::Programmes => "Programmes",
+            AdminTab::Campaigns => "Campaigns",
+            AdminTab::Referrals => "Referrals",
+            AdminTab::Points => "Points",
+            AdminTab::Theme => "Theme",
+            AdminTab::DataRequests => "Data Requests"
+        }
+    }
+}
+
+pub struct Admin {
+    /// Set once signed in, carrying the admin key.
+    api: Option<LoyaltyApiClient>,
+    tab: AdminTab,
+    key_ref: NodeRef,
+    login_error: Option<AttrValue>
+}
+
+pub enum AdminMsg {
+    LoginSubmit,
+    LoggedIn(String),
+    LoginFail(ApiClientError),
+    SignOut,
+    Show(AdminTab)
+}
+
+impl Component for Admin {
+    type Message = AdminMsg;
+    type Properties = ();
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            api: load_admin_key().map(|key| LoyaltyApiClient::new().with_admin_key(&key)),
+            tab: AdminTab::Cards,
+            key_ref: NodeRef::default(),
+            login_error: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            AdminMsg::LoginSubmit => {
+                if let Some(input) = self.key_ref.cast::<HtmlInputElement>() {
+                    let key = input.value().trim().to_string();
+                    ctx.link().send_future(async move {
+                        // any admin request will do to check the key is accepted
+                        match LoyaltyApiClient::new().with_admin_key(&key).list_stores().await {
+                            Ok(_) => AdminMsg::LoggedIn(key),
+                            Err(err) => AdminMsg::LoginFail(err),
+                        }
+                    });
+                }
+                false
+            },
+            AdminMsg::LoggedIn(key) => {
+                save_admin_key(Some(&key));
+                self.api = Some(LoyaltyApiClient::new().with_admin_key(&key));
+                self.login_error = None;
+                true
+            },
+            AdminMsg::LoginFail(err) => {
+                console::log_1(&JsValue::from(format!("Admin login failed. {}", err)));
+                self.login_error = Some(match err.status() {
+                    Some(401) => AttrValue::from("That admin key was not accepted"),
+                    _ => AttrValue::from(err.message())
+                });
+                true
+            },
+            AdminMsg::SignOut => {
+                save_admin_key(None);
+                self.api = None;
+                true
+            },
+            AdminMsg::Show(tab) => {
+                self.tab = tab;
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="container py-3">
+                <h1 class="display-4">{"Admin"}</h1>
+                {
+                    match &self.api {
+                        Some(api) => self.view_dashboard(ctx, api),
+                        None => self.view_login(ctx)
+                    }
+                }
+            </div>
+        }
+    }
+}
+
+impl Admin {
+    fn view_dashboard(&self, ctx: &Context<Self>, api: &LoyaltyApiClient) -> Html {
+        let api = api.clone();
+        let on_unauthorised = ctx.link().callback(|_| AdminMsg::SignOut);
+
+        html! {
+            <>
+            <ul class="nav nav-pills my-3">
+                { for AdminTab::ALL.iter().map(|&tab| {
+                    let class = if tab == self.tab { "nav-link active" } else { "nav-link text-white" };
+                    html! {
+                        <li class="nav-item">
+                            <button type="button" {class} onclick={ctx.link().callback(move |_| AdminMsg::Show(tab))}>
+                                { tab.title() }
+                            </button>
+                        </li>
+                    }
+                }) }
+                <li class="nav-item ms-auto">
+                    <button type="button" class="btn btn-outline-light" onclick={ctx.link().callback(|_| AdminMsg::SignOut)}>
+                        {"Sign out"}
+                    </button>
+                </li>
+            </ul>
+            {
+                match self.tab {
+                    AdminTab::Cards => html! { <CardLookup {api} {on_unauthorised} /> },
+                    AdminTab::Activity => html! { <RecentActivity {api} {on_unauthorised} /> },
+                    AdminTab::Stores => html! { <StoreManager {api} {on_unauthorised} /> },
+                    AdminTab::Programmes => html! { <ProgrammeManager {api} {on_unauthorised} /> },
+                    AdminTab::Campaigns => html! { <CampaignManager {api} {on_unauthorised} /> },
+                    AdminTab::Referrals => html! { <ReferralList {api} {on_unauthorised} /> },
+                    AdminTab::Points => html! { <PointsTill {api} {on_unauthorised} /> },
+                    AdminTab::Theme => html! { <ThemeEditor {api} {on_unauthorised} /> },
+                    AdminTab::DataRequests => html! { <DataRequestApproval {api} {on_unauthorised} /> }
+                }
+            }
+            </>
+        }
+    }
+
+    fn view_login(&self, ctx: &Context<Self>) -> Html {
+        let input_class = if self.login_error.is_some() { "form-control is-invalid" } else { "form-control" };
+
+        html! {
+            <form novalidate=true>
+                <div class="mb-3">
+                    <label for="admin_key" class="form-label">{"Admin Key"}</label>
+                    <input type="password"
+                        class={input_class}
+                        id="admin_key"
+                        name="admin_key"
+                        ref={&self.key_ref}/>
+                    <div class="invalid-feedback">
+                        { self.login_error.clone() }
+                    </div>
+                </div>
+
+                <button type="button"
+                    class="btn btn-primary"
+                    onclick={ctx.link().callback(|_| AdminMsg::LoginSubmit)}>
+                    {"Sign in"}
+                </button>
+            </form>
+        }
+    }
+}
+
+/// The admin key this browser signed in to the dashboard with, if any.
+pub fn load_admin_key() -> Option<String> {
+    window()?.local_storage().ok()??.get_item(&config::storage_key(ADMIN_KEY_STORAGE)).ok()?
+}
+
+fn save_admin_key(key: Option<&str>) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten())
+        else { return };
+
+    _ = match key {
+        Some(key) => storage.set_item(&config::storage_key(ADMIN_KEY_STORAGE), key),
+        None => storage.remove_item(&config::storage_key(ADMIN_KEY_STORAGE))
+    };
+}
+
+/// Reads and trims a text input, empty when the input is not mounted.
+fn input_value(input_ref: &NodeRef) -> String {
+    input_ref.cast::<HtmlInputElement>()
+        .map(|input| input.value().trim().to_string())
+        .unwrap_or_default()
+}
+
+fn set_input_value(input_ref: &NodeRef, value: &str) {
+    if let Some(input) = input_ref.cast::<HtmlInputElement>() {
+        input.set_value(value);
+    }
+}
@@ -0,0 +1,169 @@
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{CardResponse, EarnRequest};
+use loyalty_core::PhoneNumber;
+
+use crate::api_client::ApiClientError;
+use super::{input_value, AdminSectionProps};
+
+/// Staff enter a customer's spend to earn points, or spend points on a reward.
+pub struct PointsTill {
+    phone_ref: NodeRef,
+    spend_ref: NodeRef,
+    programme_ref: NodeRef,
+    card: Option<(String, CardResponse)>,
+    error_msg: Option<AttrValue>,
+    notice: Option<AttrValue>
+}
+
+pub enum PointsTillMsg {
+    Lookup,
+    Earn,
+    Redeem(String),
+    Loaded(String, CardResponse, Option<AttrValue>),
+    Failed(ApiClientError)
+}
+
+impl Component for PointsTill {
+    type Message = PointsTillMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            phone_ref: NodeRef::default(),
+            spend_ref: NodeRef::default(),
+            programme_ref: NodeRef::default(),
+            card: None,
+            error_msg: None,
+            notice: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            PointsTillMsg::Lookup => {
+                let id = input_value(&self.phone_ref);
+                if let Err(message) = PhoneNumber::try_from(id.as_str()) {
+                    self.error_msg = Some(AttrValue::from(message));
+                    return true;
+                }
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.get_points(&id).await {
+                        Ok(card) => PointsTillMsg::Loaded(id, card, None),
+                        Err(err) => PointsTillMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            PointsTillMsg::Earn => {
+                let Some((id, _)) = self.card.clone() else { return false };
+                let Some(spend) = pence(&input_value(&self.spend_ref)) else {
+                    self.error_msg = Some(AttrValue::from("Enter how much the customer spent, such as 4.50"));
+                    return true;
+                };
+                let programme_id = input_value(&self.programme_ref);
+                let earn = EarnRequest { spend, programme_id: (!programme_id.is_empty()).then_some(programme_id) };
+
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.earn_points(&id, &earn).await {
+                        Ok(card) => {
+                            let notice = format!("Card now has {} points", card.stamps);
+                            PointsTillMsg::Loaded(id, card, Some(notice.into()))
+                        },
+                        Err(err) => PointsTillMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            PointsTillMsg::Redeem(reward_id) => {
+                let Some((id, _)) = self.card.clone() else { return false };
+
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.redeem_points(&id, &reward_id).await {
+                        Ok(card) => PointsTillMsg::Loaded(id, card, Some(AttrValue::from("Reward redeemed"))),
+                        Err(err) => PointsTillMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            PointsTillMsg::Loaded(id, card, notice) => {
+                self.card = Some((id, card));
+                self.error_msg = None;
+                self.notice = notice;
+                true
+            },
+            PointsTillMsg::Failed(err) => {
+                self.notice = None;
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Points"}</h3>
+            <form novalidate=true class="row g-2 mb-3">
+                <div class="col-sm-8">
+                    <label for="points_phone" class="form-label visually-hidden">{"Phone Number"}</label>
+                    <input type="tel" class="form-control" id="points_phone" ref={&self.phone_ref} placeholder="07715559999"/>
+                </div>
+                <div class="col-sm-4">
+                    <button type="button" class="btn btn-primary w-100"
+                        onclick={ctx.link().callback(|_| PointsTillMsg::Lookup)}>
+                        {"Look up"}
+                    </button>
+                </div>
+            </form>
+
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            if let Some(notice) = self.notice.clone() {
+                <div class="alert alert-success" role="status">{ notice }</div>
+            }
+
+            if let Some((id, card)) = &self.card {
+                <h3>{ format!("{} has {} points", id, card.stamps) }</h3>
+                <form novalidate=true class="row g-2 mb-3">
+                    <div class="col-sm-4">
+                        <label for="points_spend" class="form-label">{"Spend"}</label>
+                        <input type="text" inputmode="decimal" class="form-control" id="points_spend" ref={&self.spend_ref} placeholder="4.50"/>
+                    </div>
+                    <div class="col-sm-4">
+                        <label for="points_programme" class="form-label">{"Programme"}</label>
+                        <input type="text" class="form-control" id="points_programme" ref={&self.programme_ref} placeholder="bakery"/>
+                    </div>
+                    <div class="col-sm-4 d-flex align-items-end">
+                        <button type="button" class="btn btn-primary w-100"
+                            onclick={ctx.link().callback(|_| PointsTillMsg::Earn)}>
+                            {"Earn points"}
+                        </button>
+                    </div>
+                </form>
+                <div class="d-flex flex-wrap gap-2">
+                    { for card.rewards.iter().map(|reward| {
+                        let reward_id = reward.reward_id.clone();
+                        html! {
+                            <button type="button" class="btn btn-outline-light" disabled={reward.cost > card.stamps}
+                                onclick={ctx.link().callback(move |_| PointsTillMsg::Redeem(reward_id.clone()))}>
+                                { format!("{} for {} points", reward.name, reward.cost) }
+                            </button>
+                        }
+                    }) }
+                </div>
+            }
+            </>
+        }
+    }
+}
+
+/// A spend typed as pounds and pence, such as 4.50, in pence.
+fn pence(value: &str) -> Option<u32> {
+    let amount = value.trim_start_matches('£').parse::<f64>().ok()?;
+    (amount > 0.0).then(|| (amount * 100.0).round() as u32)
+}
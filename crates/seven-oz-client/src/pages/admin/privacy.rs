@@ -0,0 +1,98 @@
+use yew::prelude::*;
+
+use loyalty_core::api::v1::ApproveDataRequest;
+
+use crate::api_client::ApiClientError;
+use super::{input_value, set_input_value, AdminSectionProps};
+
+/// Lets staff approve a customer's request to see or erase their data once they have checked the customer's phone.
+pub struct DataRequestApproval {
+    phone_ref: NodeRef,
+    code_ref: NodeRef,
+    error_msg: Option<AttrValue>,
+    notice: Option<AttrValue>
+}
+
+pub enum DataRequestApprovalMsg {
+    Approve,
+    Approved,
+    Failed(ApiClientError)
+}
+
+impl Component for DataRequestApproval {
+    type Message = DataRequestApprovalMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            phone_ref: NodeRef::default(),
+            code_ref: NodeRef::default(),
+            error_msg: None,
+            notice: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            DataRequestApprovalMsg::Approve => {
+                let approval = ApproveDataRequest {
+                    user_id: input_value(&self.phone_ref),
+                    code: input_value(&self.code_ref)
+                };
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.approve_data_request(&approval).await {
+                        Ok(()) => DataRequestApprovalMsg::Approved,
+                        Err(err) => DataRequestApprovalMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            DataRequestApprovalMsg::Approved => {
+                set_input_value(&self.phone_ref, "");
+                set_input_value(&self.code_ref, "");
+                self.error_msg = None;
+                self.notice = Some(AttrValue::from("Approved, the customer's data is now on their phone"));
+                true
+            },
+            DataRequestApprovalMsg::Failed(err) => {
+                self.notice = None;
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Data Requests"}</h3>
+            <p class="text-white">
+                {"Before approving, check the phone number really belongs to the customer, for example by ringing it."}
+            </p>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            if let Some(notice) = self.notice.clone() {
+                <div class="alert alert-success" role="status">{ notice }</div>
+            }
+            <form novalidate=true class="row g-2">
+                <div class="col-sm-6">
+                    <label for="request_phone" class="form-label">{"Phone Number"}</label>
+                    <input type="tel" class="form-control" id="request_phone" ref={&self.phone_ref} placeholder="07715559999"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="request_code" class="form-label">{"Code"}</label>
+                    <input type="text" class="form-control" id="request_code" ref={&self.code_ref} placeholder="ABC123"/>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-primary"
+                        onclick={ctx.link().callback(|_| DataRequestApprovalMsg::Approve)}>
+                        {"Approve"}
+                    </button>
+                </div>
+            </form>
+            </>
+        }
+    }
+}
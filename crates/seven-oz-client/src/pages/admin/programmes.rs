@@ -0,0 +1,419 @@
+use web_sys::{HtmlSelectElement, HtmlTextAreaElement};
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{BonusRules, CardKind, CatalogueReward, ExpiryPolicy, MilestoneBonus, ProgrammeDetails, Tier};
+
+use crate::api_client::ApiClientError;
+use super::{input_value, set_input_value, AdminSectionProps};
+
+pub struct ProgrammeManager {
+    programmes: Vec<ProgrammeDetails>,
+    id_ref: NodeRef,
+    name_ref: NodeRef,
+    reward_ref: NodeRef,
+    expiry_ref: NodeRef,
+    months_ref: NodeRef,
+    birthday_ref: NodeRef,
+    milestones_ref: NodeRef,
+    tiers_ref: NodeRef,
+    kind_ref: NodeRef,
+    rate_ref: NodeRef,
+    catalogue_ref: NodeRef,
+    error_msg: Option<AttrValue>
+}
+
+pub enum ProgrammeManagerMsg {
+    Refresh,
+    Loaded(Vec<ProgrammeDetails>),
+    Edit(ProgrammeDetails),
+    Save,
+    Delete(String),
+    Saved,
+    Failed(ApiClientError)
+}
+
+impl Component for ProgrammeManager {
+    type Message = ProgrammeManagerMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ProgrammeManagerMsg::Refresh);
+        Self {
+            programmes: Vec::new(),
+            id_ref: NodeRef::default(),
+            name_ref: NodeRef::default(),
+            reward_ref: NodeRef::default(),
+            expiry_ref: NodeRef::default(),
+            months_ref: NodeRef::default(),
+            birthday_ref: NodeRef::default(),
+            milestones_ref: NodeRef::default(),
+            tiers_ref: NodeRef::default(),
+            kind_ref: NodeRef::default(),
+            rate_ref: NodeRef::default(),
+            catalogue_ref: NodeRef::default(),
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ProgrammeManagerMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.list_programmes().await {
+                        Ok(programmes) => ProgrammeManagerMsg::Loaded(programmes),
+                        Err(err) => ProgrammeManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ProgrammeManagerMsg::Loaded(programmes) => {
+                self.programmes = programmes;
+                true
+            },
+            ProgrammeManagerMsg::Edit(programme) => {
+                set_input_value(&self.id_ref, &programme.programme_id);
+                set_input_value(&self.name_ref, &programme.name);
+                set_input_value(&self.reward_ref, &programme.reward);
+                let (kind, months) = match programme.expiry {
+                    Some(ExpiryPolicy::StampAge { months }) => ("stamp_age", months.to_string()),
+                    Some(ExpiryPolicy::Inactivity { months }) => ("inactivity", months.to_string()),
+                    None => ("", String::new())
+                };
+                if let Some(select) = self.expiry_ref.cast::<HtmlSelectElement>() {
+                    select.set_value(kind);
+                }
+                set_input_value(&self.months_ref, &months);
+                set_input_value(&self.birthday_ref, &programme.bonuses.birthday.map(|stamps| stamps.to_string()).unwrap_or_default());
+                set_input_value(&self.milestones_ref, &milestones_text(&programme.bonuses.milestones));
+                set_text_value(&self.tiers_ref, &tiers_text(&programme.tiers));
+                if let Some(select) = self.kind_ref.cast::<HtmlSelectElement>() {
+                    select.set_value(match programme.kind {
+                        CardKind::Stamps => "stamps",
+                        CardKind::Points => "points"
+                    });
+                }
+                set_input_value(&self.rate_ref, &programme.points_rate.map(|rate| rate.to_string()).unwrap_or_default());
+                set_text_value(&self.catalogue_ref, &catalogue_text(&programme.catalogue));
+                false
+            },
+            ProgrammeManagerMsg::Save => {
+                let programme = ProgrammeDetails {
+                    programme_id: input_value(&self.id_ref),
+                    name: input_value(&self.name_ref),
+                    reward: input_value(&self.reward_ref),
+                    expiry: self.expiry(),
+                    bonuses: self.bonuses(),
+                    tiers: self.tiers(),
+                    kind: self.kind(),
+                    points_rate: input_value(&self.rate_ref).parse().ok(),
+                    catalogue: self.catalogue()
+                };
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.save_programme(&programme).await {
+                        Ok(_) => ProgrammeManagerMsg::Saved,
+                        Err(err) => ProgrammeManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ProgrammeManagerMsg::Delete(programme_id) => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.delete_programme(&programme_id).await {
+                        Ok(()) => ProgrammeManagerMsg::Saved,
+                        Err(err) => ProgrammeManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ProgrammeManagerMsg::Saved => {
+                for input_ref in [&self.id_ref, &self.name_ref, &self.reward_ref, &self.months_ref, &self.birthday_ref, &self.milestones_ref, &self.rate_ref] {
+                    set_input_value(input_ref, "");
+                }
+                if let Some(select) = self.expiry_ref.cast::<HtmlSelectElement>() {
+                    select.set_value("");
+                }
+                set_text_value(&self.tiers_ref, "");
+                set_text_value(&self.catalogue_ref, "");
+                if let Some(select) = self.kind_ref.cast::<HtmlSelectElement>() {
+                    select.set_value("stamps");
+                }
+                self.error_msg = None;
+                ctx.link().send_message(ProgrammeManagerMsg::Refresh);
+                true
+            },
+            ProgrammeManagerMsg::Failed(err) => {
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Programmes"}</h3>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            <table class="table table-light table-striped">
+                <thead>
+                    <tr>
+                        <th>{"Id"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Reward"}</th>
+                        <th>{"Earns"}</th>
+                        <th>{"Expiry"}</th>
+                        <th>{"Bonuses"}</th>
+                        <th>{"Tiers"}</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for self.programmes.iter().map(|programme| {
+                        let (edit, delete) = (programme.clone(), programme.programme_id.clone());
+                        html! {
+                            <tr>
+                                <td>{ &programme.programme_id }</td>
+                                <td>{ &programme.name }</td>
+                                <td>{ &programme.reward }</td>
+                                <td>{ earns_label(programme) }</td>
+                                <td>{ expiry_label(programme.expiry) }</td>
+                                <td>{ bonuses_label(&programme.bonuses) }</td>
+                                <td>{ tiers_label(&programme.tiers) }</td>
+                                <td class="text-end">
+                                    <button type="button" class="btn btn-sm btn-outline-secondary me-2"
+                                        onclick={ctx.link().callback(move |_| ProgrammeManagerMsg::Edit(edit.clone()))}>
+                                        {"Edit"}
+                                    </button>
+                                    <button type="button" class="btn btn-sm btn-outline-danger"
+                                        onclick={ctx.link().callback(move |_| ProgrammeManagerMsg::Delete(delete.clone()))}>
+                                        {"Delete"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </tbody>
+            </table>
+
+            <form novalidate=true class="row g-2">
+                <div class="col-sm-3">
+                    <label for="programme_id" class="form-label">{"Id"}</label>
+                    <input type="text" class="form-control" id="programme_id" ref={&self.id_ref} placeholder="coffee"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="programme_name" class="form-label">{"Name"}</label>
+                    <input type="text" class="form-control" id="programme_name" ref={&self.name_ref} placeholder="Coffee Card"/>
+                </div>
+                <div class="col-sm-5">
+                    <label for="programme_reward" class="form-label">{"Reward"}</label>
+                    <input type="text" class="form-control" id="programme_reward" ref={&self.reward_ref} placeholder="A free coffee"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="programme_expiry" class="form-label">{"Stamps expire"}</label>
+                    <select class="form-select" id="programme_expiry" ref={&self.expiry_ref}>
+                        <option value="">{"Never"}</option>
+                        <option value="stamp_age">{"Months after each stamp"}</option>
+                        <option value="inactivity">{"Months after the last visit"}</option>
+                    </select>
+                </div>
+                <div class="col-sm-2">
+                    <label for="programme_months" class="form-label">{"Months"}</label>
+                    <input type="number" min="1" class="form-control" id="programme_months" ref={&self.months_ref} placeholder="6"/>
+                </div>
+                <div class="col-sm-2">
+                    <label for="programme_birthday" class="form-label">{"Birthday stamps"}</label>
+                    <input type="number" min="1" class="form-control" id="programme_birthday" ref={&self.birthday_ref} placeholder="None"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="programme_milestones" class="form-label">{"Milestones (cards:stamps)"}</label>
+                    <input type="text" class="form-control" id="programme_milestones" ref={&self.milestones_ref} placeholder="5:2, 10:3"/>
+                </div>
+                <div class="col-sm-3">
+                    <label for="programme_kind" class="form-label">{"Earns"}</label>
+                    <select class="form-select" id="programme_kind" ref={&self.kind_ref}>
+                        <option value="stamps">{"Stamps"}</option>
+                        <option value="points">{"Points"}</option>
+                    </select>
+                </div>
+                <div class="col-sm-3">
+                    <label for="programme_rate" class="form-label">{"Points per unit spent"}</label>
+                    <input type="number" min="1" class="form-control" id="programme_rate" ref={&self.rate_ref} placeholder="10"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="programme_catalogue" class="form-label">{"Rewards, one per line as id | name | stamps or points"}</label>
+                    <textarea class="form-control" id="programme_catalogue" rows="2" ref={&self.catalogue_ref}
+                        placeholder={"pastry | A pastry | 5\ncoffee | A coffee | 10"}/>
+                </div>
+                <div class="col-sm-12">
+                    <label for="programme_tiers" class="form-label">{"Tiers, one per line as name | cards redeemed | stamps per card | perks"}</label>
+                    <textarea class="form-control" id="programme_tiers" rows="3" ref={&self.tiers_ref}
+                        placeholder={"Silver | 5 | | Free syrup\nGold | 10 | 8 | Free oat milk, Skip the queue"}/>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-primary"
+                        onclick={ctx.link().callback(|_| ProgrammeManagerMsg::Save)}>
+                        {"Save programme"}
+                    </button>
+                </div>
+            </form>
+            </>
+        }
+    }
+}
+
+impl ProgrammeManager {
+    /// The policy picked in the form; an unreadable month count is sent as zero for the server to reject.
+    fn expiry(&self) -> Option<ExpiryPolicy> {
+        let kind = self.expiry_ref.cast::<HtmlSelectElement>()
+            .map(|select| select.value())
+            .unwrap_or_default();
+        let months = input_value(&self.months_ref).parse().unwrap_or_default();
+        match kind.as_str() {
+            "stamp_age" => Some(ExpiryPolicy::StampAge { months }),
+            "inactivity" => Some(ExpiryPolicy::Inactivity { months }),
+            _ => None
+        }
+    }
+
+    /// The bonuses in the form; an unreadable milestone is sent as zero for the server to reject.
+    fn bonuses(&self) -> BonusRules {
+        let birthday = input_value(&self.birthday_ref);
+        BonusRules {
+            birthday: (!birthday.is_empty()).then(|| birthday.parse().unwrap_or_default()),
+            milestones: input_value(&self.milestones_ref).split(',')
+                .map(str::trim)
+                .filter(|milestone| !milestone.is_empty())
+                .map(|milestone| {
+                    let (cards, stamps) = milestone.split_once(':').unwrap_or((milestone, ""));
+                    MilestoneBonus {
+                        cards: cards.trim().parse().unwrap_or_default(),
+                        stamps: stamps.trim().parse().unwrap_or_default()
+                    }
+                })
+                .collect()
+        }
+    }
+
+    fn kind(&self) -> CardKind {
+        match self.kind_ref.cast::<HtmlSelectElement>().map(|select| select.value()).as_deref() {
+            Some("points") => CardKind::Points,
+            _ => CardKind::Stamps
+        }
+    }
+
+    /// The catalogue in the form; an unreadable cost is sent as zero for the server to reject.
+    fn catalogue(&self) -> Vec<CatalogueReward> {
+        text_value(&self.catalogue_ref).lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut fields = line.split('|').map(str::trim);
+                CatalogueReward {
+                    reward_id: fields.next().unwrap_or_default().to_string(),
+                    name: fields.next().unwrap_or_default().to_string(),
+                    cost: fields.next().unwrap_or_default().parse().unwrap_or_default()
+                }
+            })
+            .collect()
+    }
+
+    /// The tiers in the form; an unreadable number of cards is sent as zero and an unreadable capacity
+    /// as an empty card, for the server to reject.
+    fn tiers(&self) -> Vec<Tier> {
+        text_value(&self.tiers_ref).lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut fields = line.split('|').map(str::trim);
+                let name = fields.next().unwrap_or_default().to_string();
+                let redemptions = fields.next().unwrap_or_default().parse().unwrap_or_default();
+                let capacity = fields.next()
+                    .filter(|capacity| !capacity.is_empty())
+                    .map(|capacity| capacity.parse().unwrap_or_default());
+                let perks = fields.next().unwrap_or_default().split(',')
+                    .map(|perk| perk.trim().to_string())
+                    .filter(|perk| !perk.is_empty())
+                    .collect();
+                Tier { name, redemptions, capacity, perks }
+            })
+            .collect()
+    }
+}
+
+fn expiry_label(expiry: Option<ExpiryPolicy>) -> String {
+    match expiry {
+        Some(ExpiryPolicy::StampAge { months }) => format!("{} months after each stamp", months),
+        Some(ExpiryPolicy::Inactivity { months }) => format!("After {} months without a visit", months),
+        None => String::from("Never")
+    }
+}
+
+fn milestones_text(milestones: &[MilestoneBonus]) -> String {
+    milestones.iter()
+        .map(|milestone| format!("{}:{}", milestone.cards, milestone.stamps))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn bonuses_label(bonuses: &BonusRules) -> String {
+    let mut labels: Vec<String> = bonuses.birthday.iter()
+        .map(|stamps| format!("{} on their birthday", stamps))
+        .collect();
+    labels.extend(bonuses.milestones.iter().map(|milestone| format!("{} after {} cards", milestone.stamps, milestone.cards)));
+    match labels.is_empty() {
+        true => String::from("None"),
+        false => labels.join(", ")
+    }
+}
+
+fn text_value(textarea_ref: &NodeRef) -> String {
+    textarea_ref.cast::<HtmlTextAreaElement>()
+        .map(|textarea| textarea.value())
+        .unwrap_or_default()
+}
+
+fn set_text_value(textarea_ref: &NodeRef, value: &str) {
+    if let Some(textarea) = textarea_ref.cast::<HtmlTextAreaElement>() {
+        textarea.set_value(value);
+    }
+}
+
+fn tiers_text(tiers: &[Tier]) -> String {
+    tiers.iter()
+        .map(|tier| format!(
+            "{} | {} | {} | {}",
+            tier.name,
+            tier.redemptions,
+            tier.capacity.map(|capacity| capacity.to_string()).unwrap_or_default(),
+            tier.perks.join(", ")
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn tiers_label(tiers: &[Tier]) -> String {
+    match tiers.is_empty() {
+        true => String::from("None"),
+        false => tiers.iter()
+            .map(|tier| format!("{} after {} cards", tier.name, tier.redemptions))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn catalogue_text(catalogue: &[CatalogueReward]) -> String {
+    catalogue.iter()
+        .map(|reward| format!("{} | {} | {}", reward.reward_id, reward.name, reward.cost))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn earns_label(programme: &ProgrammeDetails) -> String {
+    match (programme.kind, programme.points_rate) {
+        (CardKind::Points, Some(rate)) => format!("{} points per unit", rate),
+        (CardKind::Points, None) => String::from("Points"),
+        (CardKind::Stamps, _) => String::from("Stamps")
+    }
+}
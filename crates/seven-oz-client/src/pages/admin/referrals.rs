@@ -0,0 +1,95 @@
+use yew::prelude::*;
+
+use loyalty_core::api::v1::ReferralResponse;
+
+use crate::api_client::ApiClientError;
+use super::AdminSectionProps;
+
+pub struct ReferralList {
+    referrals: Vec<ReferralResponse>,
+    error_msg: Option<AttrValue>
+}
+
+pub enum ReferralListMsg {
+    Refresh,
+    Loaded(Vec<ReferralResponse>),
+    Failed(ApiClientError)
+}
+
+impl Component for ReferralList {
+    type Message = ReferralListMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ReferralListMsg::Refresh);
+        Self {
+            referrals: Vec::new(),
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ReferralListMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.list_referrals().await {
+                        Ok(referrals) => ReferralListMsg::Loaded(referrals),
+                        Err(err) => ReferralListMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ReferralListMsg::Loaded(referrals) => {
+                self.referrals = referrals;
+                self.error_msg = None;
+                true
+            },
+            ReferralListMsg::Failed(err) => {
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <div class="d-flex justify-content-between align-items-center">
+                <h3>{"Referrals"}</h3>
+                <button type="button" class="btn btn-outline-light"
+                    onclick={ctx.link().callback(|_| ReferralListMsg::Refresh)}>
+                    {"Refresh"}
+                </button>
+            </div>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            if self.referrals.is_empty() {
+                <p class="text-white">{"No referrals yet"}</p>
+            } else {
+                <table class="table table-light table-striped">
+                    <thead>
+                        <tr>
+                            <th>{"When"}</th>
+                            <th>{"Referred by"}</th>
+                            <th>{"New customer"}</th>
+                            <th>{"Bonus each"}</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for self.referrals.iter().map(|referral| html! {
+                            <tr>
+                                <td>{ referral.timestamp.replace('T', " ").trim_end_matches('Z').to_string() }</td>
+                                <td>{ &referral.referrer }</td>
+                                <td>{ &referral.referred }</td>
+                                <td>{ referral.bonus }</td>
+                            </tr>
+                        }) }
+                    </tbody>
+                </table>
+            }
+            </>
+        }
+    }
+}
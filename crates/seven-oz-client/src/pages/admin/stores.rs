@@ -0,0 +1,192 @@
+use web_sys::HtmlSelectElement;
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{ProgrammeDetails, StoreDetails};
+use loyalty_core::StoreId;
+
+use crate::api_client::ApiClientError;
+use super::{input_value, set_input_value, AdminSectionProps};
+
+pub struct StoreManager {
+    stores: Vec<StoreDetails>,
+    programmes: Vec<ProgrammeDetails>,
+    id_ref: NodeRef,
+    name_ref: NodeRef,
+    programme_ref: NodeRef,
+    error_msg: Option<AttrValue>
+}
+
+pub enum StoreManagerMsg {
+    Refresh,
+    Loaded(Vec<StoreDetails>, Vec<ProgrammeDetails>),
+    Edit(StoreDetails),
+    Save,
+    Delete(StoreId),
+    Saved,
+    Failed(ApiClientError)
+}
+
+impl Component for StoreManager {
+    type Message = StoreManagerMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(StoreManagerMsg::Refresh);
+        Self {
+            stores: Vec::new(),
+            programmes: Vec::new(),
+            id_ref: NodeRef::default(),
+            name_ref: NodeRef::default(),
+            programme_ref: NodeRef::default(),
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            StoreManagerMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    // the programmes are needed to fill in the store form
+                    let loaded = async { Ok((api.list_stores().await?, api.list_programmes().await?)) };
+                    match loaded.await {
+                        Ok((stores, programmes)) => StoreManagerMsg::Loaded(stores, programmes),
+                        Err(err) => StoreManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            StoreManagerMsg::Loaded(stores, programmes) => {
+                self.stores = stores;
+                self.programmes = programmes;
+                true
+            },
+            StoreManagerMsg::Edit(store) => {
+                set_input_value(&self.id_ref, &store.store_id.0);
+                set_input_value(&self.name_ref, &store.name);
+                if let Some(select) = self.programme_ref.cast::<HtmlSelectElement>() {
+                    select.set_value(store.programme_id.as_deref().unwrap_or_default());
+                }
+                false
+            },
+            StoreManagerMsg::Save => {
+                let programme_id = self.programme_ref.cast::<HtmlSelectElement>()
+                    .map(|select| select.value())
+                    .filter(|value| !value.is_empty());
+                let store = StoreDetails {
+                    store_id: StoreId(input_value(&self.id_ref)),
+                    name: input_value(&self.name_ref),
+                    programme_id
+                };
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.save_store(&store).await {
+                        Ok(_) => StoreManagerMsg::Saved,
+                        Err(err) => StoreManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            StoreManagerMsg::Delete(store_id) => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.delete_store(&store_id.0).await {
+                        Ok(()) => StoreManagerMsg::Saved,
+                        Err(err) => StoreManagerMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            StoreManagerMsg::Saved => {
+                set_input_value(&self.id_ref, "");
+                set_input_value(&self.name_ref, "");
+                self.error_msg = None;
+                ctx.link().send_message(StoreManagerMsg::Refresh);
+                true
+            },
+            StoreManagerMsg::Failed(err) => {
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Stores"}</h3>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            <table class="table table-light table-striped">
+                <thead>
+                    <tr>
+                        <th>{"Id"}</th>
+                        <th>{"Name"}</th>
+                        <th>{"Programme"}</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    { for self.stores.iter().map(|store| {
+                        let (edit, delete) = (store.clone(), store.store_id.clone());
+                        html! {
+                            <tr>
+                                <td>{ store.store_id.to_string() }</td>
+                                <td>{ &store.name }</td>
+                                <td>{ self.programme_name(store.programme_id.as_deref()) }</td>
+                                <td class="text-end">
+                                    <button type="button" class="btn btn-sm btn-outline-secondary me-2"
+                                        onclick={ctx.link().callback(move |_| StoreManagerMsg::Edit(edit.clone()))}>
+                                        {"Edit"}
+                                    </button>
+                                    <button type="button" class="btn btn-sm btn-outline-danger"
+                                        onclick={ctx.link().callback(move |_| StoreManagerMsg::Delete(delete.clone()))}>
+                                        {"Delete"}
+                                    </button>
+                                </td>
+                            </tr>
+                        }
+                    }) }
+                </tbody>
+            </table>
+
+            <form novalidate=true class="row g-2">
+                <div class="col-sm-3">
+                    <label for="store_id" class="form-label">{"Id"}</label>
+                    <input type="text" class="form-control" id="store_id" ref={&self.id_ref} placeholder="high-street"/>
+                </div>
+                <div class="col-sm-5">
+                    <label for="store_name" class="form-label">{"Name"}</label>
+                    <input type="text" class="form-control" id="store_name" ref={&self.name_ref} placeholder="High Street"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="store_programme" class="form-label">{"Programme"}</label>
+                    <select class="form-select" id="store_programme" ref={&self.programme_ref}>
+                        <option value="">{"None"}</option>
+                        { for self.programmes.iter().map(|programme| html! {
+                            <option value={programme.programme_id.clone()}>{ &programme.name }</option>
+                        }) }
+                    </select>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-primary"
+                        onclick={ctx.link().callback(|_| StoreManagerMsg::Save)}>
+                        {"Save store"}
+                    </button>
+                </div>
+            </form>
+            </>
+        }
+    }
+}
+
+impl StoreManager {
+    fn programme_name(&self, programme_id: Option<&str>) -> String {
+        let Some(programme_id) = programme_id else { return String::new() };
+        self.programmes.iter()
+            .find(|programme| programme.programme_id == programme_id)
+            .map(|programme| programme.name.clone())
+            .unwrap_or_else(|| programme_id.to_string())
+    }
+}
@@ -0,0 +1,228 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use loyalty_core::api::v1::{StampSlot, ThemeDetails};
+
+use crate::api_client::ApiClientError;
+use super::{input_value, set_input_value, AdminSectionProps};
+
+/// Edits how the customer pages look; pages pick up a saved theme the next time they load.
+pub struct ThemeEditor {
+    logo_ref: NodeRef,
+    background_ref: NodeRef,
+    text_ref: NodeRef,
+    stamp_ref: NodeRef,
+    stamp_text_ref: NodeRef,
+    stamp_icon_ref: NodeRef,
+    empty_slot_ref: NodeRef,
+    stamp_file_ref: NodeRef,
+    empty_file_ref: NodeRef,
+    title_ref: NodeRef,
+    reward_ref: NodeRef,
+    error_msg: Option<AttrValue>,
+    notice: Option<AttrValue>
+}
+
+pub enum ThemeEditorMsg {
+    Refresh,
+    Loaded(ThemeDetails),
+    Save,
+    Upload(StampSlot),
+    Saved(ThemeDetails),
+    Failed(ApiClientError)
+}
+
+impl Component for ThemeEditor {
+    type Message = ThemeEditorMsg;
+    type Properties = AdminSectionProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        ctx.link().send_message(ThemeEditorMsg::Refresh);
+        Self {
+            logo_ref: NodeRef::default(),
+            background_ref: NodeRef::default(),
+            text_ref: NodeRef::default(),
+            stamp_ref: NodeRef::default(),
+            stamp_text_ref: NodeRef::default(),
+            stamp_icon_ref: NodeRef::default(),
+            empty_slot_ref: NodeRef::default(),
+            stamp_file_ref: NodeRef::default(),
+            empty_file_ref: NodeRef::default(),
+            title_ref: NodeRef::default(),
+            reward_ref: NodeRef::default(),
+            error_msg: None,
+            notice: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            ThemeEditorMsg::Refresh => {
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.get_theme().await {
+                        Ok(theme) => ThemeEditorMsg::Loaded(theme),
+                        Err(err) => ThemeEditorMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ThemeEditorMsg::Loaded(theme) => {
+                self.fill(&theme);
+                false
+            },
+            ThemeEditorMsg::Save => {
+                let optional = |value: String| (!value.is_empty()).then_some(value);
+                let theme = ThemeDetails {
+                    logo_url: optional(input_value(&self.logo_ref)),
+                    background_colour: input_value(&self.background_ref),
+                    text_colour: input_value(&self.text_ref),
+                    stamp_colour: input_value(&self.stamp_ref),
+                    stamp_text: input_value(&self.stamp_text_ref),
+                    stamp_icon_url: optional(input_value(&self.stamp_icon_ref)),
+                    empty_slot_url: optional(input_value(&self.empty_slot_ref)),
+                    card_title: input_value(&self.title_ref),
+                    reward_text: input_value(&self.reward_ref)
+                };
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.save_theme(&theme).await {
+                        Ok(theme) => ThemeEditorMsg::Saved(theme),
+                        Err(err) => ThemeEditorMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ThemeEditorMsg::Upload(slot) => {
+                let file_ref = match slot {
+                    StampSlot::Stamped => &self.stamp_file_ref,
+                    StampSlot::Empty => &self.empty_file_ref
+                };
+                let Some(file) = file_ref.cast::<HtmlInputElement>().and_then(|input| input.files()?.get(0)) else {
+                    self.error_msg = Some(AttrValue::from("Choose an SVG or PNG image to upload"));
+                    return true;
+                };
+
+                let api = ctx.props().api.clone();
+                ctx.link().send_future(async move {
+                    match api.upload_stamp_image(slot, &file).await {
+                        Ok(theme) => ThemeEditorMsg::Saved(theme),
+                        Err(err) => ThemeEditorMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            ThemeEditorMsg::Saved(theme) => {
+                self.fill(&theme);
+                self.error_msg = None;
+                self.notice = Some(AttrValue::from("Theme saved, customers will see it next time they open a page"));
+                true
+            },
+            ThemeEditorMsg::Failed(err) => {
+                self.notice = None;
+                self.error_msg = Some(ctx.props().handle_error(err));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <>
+            <h3>{"Theme"}</h3>
+            if let Some(error_msg) = self.error_msg.clone() {
+                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+            }
+            if let Some(notice) = self.notice.clone() {
+                <div class="alert alert-success" role="status">{ notice }</div>
+            }
+            <form novalidate=true class="row g-2">
+                <div class="col-sm-4">
+                    <label for="theme_background" class="form-label">{"Background colour"}</label>
+                    <input type="color" class="form-control form-control-color w-100" id="theme_background" ref={&self.background_ref}/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="theme_text" class="form-label">{"Text colour"}</label>
+                    <input type="color" class="form-control form-control-color w-100" id="theme_text" ref={&self.text_ref}/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="theme_stamp" class="form-label">{"Stamp colour"}</label>
+                    <input type="color" class="form-control form-control-color w-100" id="theme_stamp" ref={&self.stamp_ref}/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="theme_title" class="form-label">{"Card title"}</label>
+                    <input type="text" class="form-control" id="theme_title" ref={&self.title_ref} placeholder="Your Loyalty Card"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="theme_reward" class="form-label">{"Reward text"}</label>
+                    <input type="text" class="form-control" id="theme_reward" ref={&self.reward_ref} placeholder="Coffee on the way!"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="theme_stamp_text" class="form-label">{"Stamp text"}</label>
+                    <input type="text" class="form-control" id="theme_stamp_text" ref={&self.stamp_text_ref} placeholder="7oz"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="theme_stamp_icon" class="form-label">{"Stamp icon address"}</label>
+                    <input type="text" class="form-control" id="theme_stamp_icon" ref={&self.stamp_icon_ref} placeholder="None"/>
+                </div>
+                <div class="col-sm-4">
+                    <label for="theme_empty_slot" class="form-label">{"Empty slot image address"}</label>
+                    <input type="text" class="form-control" id="theme_empty_slot" ref={&self.empty_slot_ref} placeholder="None"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="theme_logo" class="form-label">{"Logo address"}</label>
+                    <input type="text" class="form-control" id="theme_logo" ref={&self.logo_ref} placeholder="None"/>
+                </div>
+                <div class="col-sm-6">
+                    <label for="theme_stamp_file" class="form-label">{"Upload a stamp image"}</label>
+                    <div class="input-group">
+                        <input type="file" accept="image/svg+xml,image/png" class="form-control" id="theme_stamp_file" ref={&self.stamp_file_ref}/>
+                        <button type="button" class="btn btn-outline-light"
+                            onclick={ctx.link().callback(|_| ThemeEditorMsg::Upload(StampSlot::Stamped))}>
+                            {"Upload"}
+                        </button>
+                    </div>
+                </div>
+                <div class="col-sm-6">
+                    <label for="theme_empty_file" class="form-label">{"Upload an empty slot image"}</label>
+                    <div class="input-group">
+                        <input type="file" accept="image/svg+xml,image/png" class="form-control" id="theme_empty_file" ref={&self.empty_file_ref}/>
+                        <button type="button" class="btn btn-outline-light"
+                            onclick={ctx.link().callback(|_| ThemeEditorMsg::Upload(StampSlot::Empty))}>
+                            {"Upload"}
+                        </button>
+                    </div>
+                </div>
+                <div class="col-auto">
+                    <button type="button" class="btn btn-primary"
+                        onclick={ctx.link().callback(|_| ThemeEditorMsg::Save)}>
+                        {"Save theme"}
+                    </button>
+                </div>
+            </form>
+            </>
+        }
+    }
+}
+
+impl ThemeEditor {
+    fn fill(&self, theme: &ThemeDetails) {
+        set_input_value(&self.logo_ref, theme.logo_url.as_deref().unwrap_or_default());
+        set_input_value(&self.background_ref, &full_hex(&theme.background_colour));
+        set_input_value(&self.text_ref, &full_hex(&theme.text_colour));
+        set_input_value(&self.stamp_ref, &full_hex(&theme.stamp_colour));
+        set_input_value(&self.stamp_text_ref, &theme.stamp_text);
+        set_input_value(&self.stamp_icon_ref, theme.stamp_icon_url.as_deref().unwrap_or_default());
+        set_input_value(&self.empty_slot_ref, theme.empty_slot_url.as_deref().unwrap_or_default());
+        set_input_value(&self.title_ref, &theme.card_title);
+        set_input_value(&self.reward_ref, &theme.reward_text);
+    }
+}
+
+/// Colour inputs only take `#rrggbb`, so `#abc` is spelled out as `#aabbcc`.
+fn full_hex(colour: &str) -> String {
+    match colour.strip_prefix('#') {
+        Some(hex) if hex.len() == 3 => hex.chars().fold(String::from("#"), |full, c| format!("{}{}{}", full, c, c)),
+        _ => colour.to_string()
+    }
+}
@@ -1,12 +1,16 @@
-use reqwasm::http::Request;
-use serde::{Deserialize, Serialize};
 use wasm_bindgen_futures::wasm_bindgen::JsValue;
-use web_sys::{console, HtmlInputElement};
+use web_sys::{console, window, HtmlInputElement};
 use yew::prelude::*;
 use yew_router::prelude::*;
-use loyalty_core::PhoneNumber;
+use loyalty_core::api::v1::ClaimRequest;
+use loyalty_core::{PhoneNumber, UserId};
 
-use crate::{get_api_base, Route};
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::config;
+use crate::pages::refer::{save_referral, saved_referral};
+use crate::Route;
+
+const CARD_KEY_STORAGE: &str = "loyalty-card-key-";
 
 #[derive(Properties, PartialEq)]
 pub struct CollectProps {
@@ -16,19 +20,15 @@ pub struct CollectProps {
 pub enum CollectMsg {
     Submit,
     Claiming,
-    ClaimOk(String),
-    ClaimFail(u16)
-}
-
-#[derive(Deserialize, Serialize)]
-struct Claim {
-    id: String,
-    code: String
+    ClaimOk(String, String),
+    ClaimFail(ApiClientError)
 }
 
 pub struct Collect {
+    api: LoyaltyApiClient,
     input_ref: NodeRef,
-    validation_msg: AttrValue
+    validation_msg: AttrValue,
+    error_msg: Option<AttrValue>
 }
 
 impl Component for Collect {
@@ -37,8 +37,10 @@ impl Component for Collect {
 
     fn create(_ctx: &Context<Self>) -> Self {
         Self {
+            api: LoyaltyApiClient::new(),
             input_ref: NodeRef::default(),
-            validation_msg: AttrValue::from("foo")
+            validation_msg: AttrValue::from("foo"),
+            error_msg: None
         }
     }
 
@@ -52,21 +54,28 @@ impl Component for Collect {
                     console::log_1(&input_value.clone().into());
 
                     // check validity
-                    if let Err(message) = PhoneNumber::try_from(input_value.as_ref()) {
-                        console::log_1(&JsValue::from(message));
-                        self.validation_msg = AttrValue::from(message);
-                        input.class_list().add_1("is-invalid").unwrap();
-                        return true;
-                    }
-                    
-                    let claim = Claim {
+                    let phone = match PhoneNumber::try_from(input_value.as_ref()) {
+                        Ok(phone) => phone,
+                        Err(message) => {
+                            console::log_1(&JsValue::from(message));
+                            self.validation_msg = AttrValue::from(message);
+                            input.class_list().add_1("is-invalid").unwrap();
+                            return true;
+                        }
+                    };
+                    // the server keeps the card under the bare digits, so open it by those
+                    let UserId(card_id) = UserId::from(phone);
+
+                    let claim = ClaimRequest {
                         id:  input_value.clone(),
-                        code: ctx.props().code.clone()
+                        code: ctx.props().code.clone(),
+                        referral: saved_referral()
                     };
                     
-                    ctx.link().send_future(async {
-                        match post_claim(claim).await {
-                            Ok(()) => CollectMsg::ClaimOk(input_value),
+                    let api = self.api.clone();
+                    ctx.link().send_future(async move {
+                        match api.claim_code(&claim).await {
+                            Ok(resp) => CollectMsg::ClaimOk(card_id, resp.card_key),
                             Err(err) => CollectMsg::ClaimFail(err),
                         }
                     });
@@ -78,15 +87,19 @@ impl Component for Collect {
                 console::log_1(&JsValue::from("Claiming"));
                 false
             },
-            CollectMsg::ClaimOk(id) => {
+            CollectMsg::ClaimOk(id, card_key) => {
                 console::log_1(&JsValue::from("ClaimOk"));
+                save_card_key(&id, &card_key);
+                // the server only counts a referral on the first claim so there is no use keeping it
+                save_referral(None);
                 let navigator = ctx.link().navigator().unwrap();
                 navigator.push(&Route::StampCard{id});
                 false
             },
             CollectMsg::ClaimFail(err) => {
-                console::log_1(&JsValue::from(format!("ClaimFail. Status code: {}", err)));
-                false
+                console::log_1(&JsValue::from(format!("ClaimFail. {}", err)));
+                self.error_msg = Some(AttrValue::from(err.message()));
+                true
             }
         }
     }
@@ -97,7 +110,11 @@ impl Component for Collect {
                 <div class="row">
                     <div class="col">
                         <h1 class="display-1 py-3">{"Collect a Stamp"}</h1>
-    
+
+                        if let Some(error_msg) = self.error_msg.clone() {
+                            <div class="alert alert-danger" role="alert">{ error_msg }</div>
+                        }
+
                         <form novalidate=true>
                             <div class="mb-3">
                                 <label for="phone_number" class="form-label">{"Phone Number"}</label>
@@ -127,17 +144,14 @@ impl Component for Collect {
     }
 }
 
-async fn post_claim(claim: Claim) -> Result<(), u16> {
-    let json = serde_json::to_string(&claim).unwrap();
-    let api_base = get_api_base();
-    let endpoint = format!("{}/api/customercode/claim", api_base);
-    let resp = Request::post(&endpoint)
-        .body(json)
-        .header("Content-Type", "application/json")
-        .send().await.unwrap();
-    
-    match resp.status() {
-        200 => Ok(()),
-        _ => Err(resp.status())
-    }
+/// The key for following a card live, kept from the last stamp claimed for it in this browser.
+pub fn saved_card_key(id: &str) -> Option<String> {
+    window()?.local_storage().ok()??.get_item(&config::storage_key(&format!("{}{}", CARD_KEY_STORAGE, id))).ok()?
+}
+
+fn save_card_key(id: &str, card_key: &str) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten())
+        else { return };
+
+    _ = storage.set_item(&config::storage_key(&format!("{}{}", CARD_KEY_STORAGE, id)), card_key);
 }
@@ -1,27 +1,44 @@
 use std::time::Duration;
 
-use reqwasm::http::Request;
-use serde::{Deserialize, Serialize};
 use wasm_bindgen_futures::js_sys::JsString;
-use web_sys::{console, window};
-use yew::{AttrValue, Callback, Component, Context, Html, html};
+use web_sys::{console, window, HtmlInputElement};
+use yew::{AttrValue, Callback, Component, Context, Html, html, NodeRef};
 use yew::platform::time::sleep;
 
+use loyalty_core::api::v1::{CampaignDetails, CodeResponse};
+
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::config;
+use crate::components::campaign_banner::CampaignBanner;
 use crate::components::qrcode_image::QrCodeImage;
-use crate::get_api_base;
+use crate::event_stream::EventStream;
 
-#[derive(Serialize, Deserialize, PartialEq)]
-struct QrResponse {
-    code: String
-}
+const DEVICE_KEY_STORAGE: &str = "loyalty-device-key";
+// how many times to poll after the stream drops before trying to stream again
+const FALLBACK_POLLS: u32 = 15;
+const CAMPAIGN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 pub struct Display {
     location: String,
-    code: Option<AttrValue>
+    code: Option<AttrValue>,
+    campaigns: Vec<CampaignDetails>,
+    /// Set once the display is paired, carrying its device key.
+    api: Option<LoyaltyApiClient>,
+    stream: Option<EventStream>,
+    pairing_ref: NodeRef,
+    pairing_error: Option<AttrValue>
 }
 
 pub enum DisplayMsg {
-    CodeReceived(AttrValue)
+    CodeReceived(AttrValue),
+    PairSubmit,
+    Paired(String),
+    PairFail(ApiClientError),
+    Unpaired,
+    StreamDropped,
+    Reconnect,
+    CheckCampaigns,
+    CampaignsLoaded(Result<Vec<CampaignDetails>, ApiClientError>)
 }
 
 impl Component for Display {
@@ -29,15 +46,24 @@ impl Component for Display {
     type Properties = ();
 
     fn create(ctx: &Context<Self>) -> Self {
-        let code_cb = ctx.link().callback(DisplayMsg::CodeReceived);
-        poll_code_service(code_cb);
+        let api = load_device_key().map(|key| LoyaltyApiClient::new().with_device_key(&key));
+        let stream = api.as_ref().and_then(|api| subscribe(ctx, api));
+        if api.is_some() {
+            ctx.link().send_message(DisplayMsg::CheckCampaigns);
+        }
+
         Self {
-            location: window().unwrap().location().origin().unwrap(),
-            code: None
+            location: format!("{}{}", window().unwrap().location().origin().unwrap(), config::tenant_prefix()),
+            code: None,
+            campaigns: Vec::new(),
+            api,
+            stream,
+            pairing_ref: NodeRef::default(),
+            pairing_error: None
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             DisplayMsg::CodeReceived(new_code) => {
                 if self.code.as_deref().unwrap_or("") == new_code {
@@ -47,35 +73,99 @@ impl Component for Display {
                 console::log_1(&JsString::from("Received new code!"));
                 self.code = Some(new_code);
                 true
+            },
+            DisplayMsg::PairSubmit => {
+                if let Some(input) = self.pairing_ref.cast::<HtmlInputElement>() {
+                    let code = input.value();
+                    ctx.link().send_future(async move {
+                        match LoyaltyApiClient::new().pair_device(&code).await {
+                            Ok(resp) => DisplayMsg::Paired(resp.device_key),
+                            Err(err) => DisplayMsg::PairFail(err),
+                        }
+                    });
+                }
+                false
+            },
+            DisplayMsg::Paired(key) => {
+                console::log_1(&JsString::from("Display paired"));
+                save_device_key(Some(&key));
+                let api = LoyaltyApiClient::new().with_device_key(&key);
+                self.stream = subscribe(ctx, &api);
+                self.api = Some(api);
+                self.pairing_error = None;
+                ctx.link().send_message(DisplayMsg::CheckCampaigns);
+                true
+            },
+            DisplayMsg::PairFail(err) => {
+                console::log_1(&JsString::from(format!("Pairing failed. {}", err)));
+                self.pairing_error = Some(AttrValue::from(err.message()));
+                true
+            },
+            DisplayMsg::Unpaired => {
+                // the key has been revoked so go back to asking for a pairing code
+                console::log_1(&JsString::from("Device key rejected"));
+                save_device_key(None);
+                self.api = None;
+                self.stream = None;
+                self.code = None;
+                self.campaigns.clear();
+                true
+            },
+            DisplayMsg::StreamDropped => {
+                console::log_1(&JsString::from("Code stream dropped, falling back to polling"));
+                self.stream = None;
+                if let Some(api) = self.api.clone() {
+                    start_polling(ctx, api);
+                }
+                false
+            },
+            DisplayMsg::Reconnect => {
+                if let Some(api) = self.api.as_ref() {
+                    self.stream = subscribe(ctx, api);
+                }
+                false
+            },
+            DisplayMsg::CheckCampaigns => {
+                // stops checking once the display is unpaired
+                if let Some(api) = self.api.clone() {
+                    ctx.link().send_future(async move { DisplayMsg::CampaignsLoaded(api.live_campaigns().await) });
+                }
+                false
+            },
+            DisplayMsg::CampaignsLoaded(result) => {
+                ctx.link().send_future(async {
+                    sleep(CAMPAIGN_CHECK_INTERVAL).await;
+                    DisplayMsg::CheckCampaigns
+                });
+                match result {
+                    Ok(campaigns) if campaigns != self.campaigns => {
+                        self.campaigns = campaigns;
+                        true
+                    },
+                    Ok(_) => false,
+                    Err(err) => {
+                        console::log_1(&JsString::from(format!("Campaign check failed. {}", err)));
+                        false
+                    }
+                }
             }
         }
     }
 
-    fn view(&self, _ctx: &Context<Self>) -> Html {
+    fn view(&self, ctx: &Context<Self>) -> Html {
         html! {
             <>
             <div class="container text-center">
                 <div class="row">
                     <div class="col">
-                        <h1 class="display-1 py-3">{"Scan Me"}</h1>
-                            {
-                                match self.code.clone() {
-                                    Some(code) => html!{
-                                        <div>
-                                            <div>
-                                                <QrCodeImage link={ format!("{}/collect/{}", self.location, code) } dim=250 module_dim=7  />
-                                            </div>
-                                            <div>
-                                                //<a href={ format!("{}/collect/{}", self.location, code) }>{ format!("{}/collect/{}", self.location, code) }</a>
-                                            </div>
-                                        </div>
-
-                                    },
-                                    None => html!{
-                                        <div>{ "Loading..." }</div>
-                                    }
-                                }
+                        {
+                            if self.api.is_none() {
+                                self.view_pairing(ctx)
                             }
+                            else {
+                                self.view_code()
+                            }
+                        }
                     </div>
                 </div>
             </div>
@@ -84,27 +174,113 @@ impl Component for Display {
     }
 }
 
-fn poll_code_service(code_cb: Callback<AttrValue>) {
-    wasm_bindgen_futures::spawn_local(async move {
+impl Display {
+    fn view_code(&self) -> Html {
+        html! {
+            <>
+            <h1 class="display-1 py-3">{"Scan Me"}</h1>
+            { for self.campaigns.iter().map(|campaign| html! { <CampaignBanner campaign={campaign.clone()} /> }) }
+                {
+                    match self.code.clone() {
+                        Some(code) => html!{
+                            <div>
+                                <div>
+                                    <QrCodeImage link={ format!("{}/collect/{}", self.location, code) } dim=250 module_dim=7  />
+                                </div>
+                                <div>
+                                    //<a href={ format!("{}/collect/{}", self.location, code) }>{ format!("{}/collect/{}", self.location, code) }</a>
+                                </div>
+                            </div>
 
-        let api_base = get_api_base();
-        let endpoint = format!("{}/api/customercode", api_base);
+                        },
+                        None => html!{
+                            <div>{ "Loading..." }</div>
+                        }
+                    }
+                }
+            </>
+        }
+    }
 
-        loop {
-            let resp = Request::get(&endpoint)
-                //.header("Access-Control-Allow-Origin", "http://localhost:8000/")
-                .send()
-                .await.unwrap()
-                .json::<QrResponse>()
-                .await.unwrap();
+    fn view_pairing(&self, ctx: &Context<Self>) -> Html {
+        let input_class = if self.pairing_error.is_some() { "form-control is-invalid" } else { "form-control" };
 
-            console::log_1(&JsString::from(
-                serde_json::to_string(&resp).unwrap()
-            ));
+        html! {
+            <>
+            <h1 class="display-1 py-3">{"Pair Display"}</h1>
+            <form novalidate=true>
+                <div class="mb-3">
+                    <label for="pairing_code" class="form-label">{"Pairing Code"}</label>
+                    <input type="text"
+                        class={input_class}
+                        id="pairing_code"
+                        name="pairing_code"
+                        ref={&self.pairing_ref}
+                        placeholder="ABC123"/>
+                    <div class="invalid-feedback">
+                        { self.pairing_error.clone() }
+                    </div>
+                </div>
+
+                <button type="button"
+                    class="btn btn-primary"
+                    onclick={ctx.link().callback(|_| DisplayMsg::PairSubmit)}>
+                    {"Pair"}
+                </button>
+            </form>
+            </>
+        }
+    }
+}
+
+fn load_device_key() -> Option<String> {
+    window()?.local_storage().ok()??.get_item(&config::storage_key(DEVICE_KEY_STORAGE)).ok()?
+}
+
+fn save_device_key(key: Option<&str>) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten())
+        else { return };
+
+    _ = match key {
+        Some(key) => storage.set_item(&config::storage_key(DEVICE_KEY_STORAGE), key),
+        None => storage.remove_item(&config::storage_key(DEVICE_KEY_STORAGE))
+    };
+}
+
+/// Opens the server-sent event stream of new codes for this display's store.
+fn subscribe(ctx: &Context<Display>, api: &LoyaltyApiClient) -> Option<EventStream> {
+    let code_cb = ctx.link().batch_callback(|data: String| {
+        serde_json::from_str::<CodeResponse>(&data).ok()
+            .map(|resp| DisplayMsg::CodeReceived(resp.code.into()))
+    });
+    let dropped_cb = ctx.link().callback(|_| DisplayMsg::StreamDropped);
+
+    EventStream::open(&api.code_stream_url(), code_cb, dropped_cb)
+}
+
+fn start_polling(ctx: &Context<Display>, api: LoyaltyApiClient) {
+    let code_cb = ctx.link().callback(DisplayMsg::CodeReceived);
+    let unpaired_cb = ctx.link().callback(|_| DisplayMsg::Unpaired);
+    let reconnect_cb = ctx.link().callback(|_| DisplayMsg::Reconnect);
+    poll_code_service(code_cb, unpaired_cb, reconnect_cb, api);
+}
+
+fn poll_code_service(code_cb: Callback<AttrValue>, unpaired_cb: Callback<()>, reconnect_cb: Callback<()>, api: LoyaltyApiClient) {
+    wasm_bindgen_futures::spawn_local(async move {
+
+        for _ in 0..FALLBACK_POLLS {
+            match api.get_code().await {
+                Ok(resp) => code_cb.emit(resp.code.into()),
+                Err(err) if err.status() == Some(401) => {
+                    unpaired_cb.emit(());
+                    return;
+                },
+                Err(err) => console::log_1(&JsString::from(format!("Polling failed. {}", err)))
+            }
 
-            code_cb.emit(resp.code.into());
             sleep(Duration::from_secs(2)).await
         }
 
+        reconnect_cb.emit(());
     });
-}
\ No newline at end of file
+}
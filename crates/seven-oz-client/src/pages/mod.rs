@@ -1,4 +1,8 @@
 
 pub mod display;
 pub mod collect;
-pub mod stamp_card;
\ No newline at end of file
+pub mod stamp_card;
+pub mod points_card;
+pub mod admin;
+pub mod privacy;
+pub mod refer;
\ No newline at end of file
@@ -0,0 +1,118 @@
+use wasm_bindgen_futures::wasm_bindgen::JsValue;
+use web_sys::console;
+use yew::{AttrValue, Callback, Component, Context, Html, html, Properties};
+
+use loyalty_core::api::v1::{CardKind, CardResponse, CardUpdate};
+
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::event_stream::EventStream;
+use crate::pages::collect::saved_card_key;
+
+/// The customer's points balance and the rewards they can spend it on.
+pub struct PointsCard {
+    api: LoyaltyApiClient,
+    card: Option<CardResponse>,
+    error_msg: Option<AttrValue>,
+    _stream: Option<EventStream>
+}
+
+pub enum PointsCardMsg {
+    Load,
+    Received(CardResponse),
+    LoadErr(ApiClientError)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct PointsCardProps {
+    pub id: String
+}
+
+impl Component for PointsCard {
+    type Message = PointsCardMsg;
+    type Properties = PointsCardProps;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let api = LoyaltyApiClient::new();
+        ctx.link().send_message(PointsCardMsg::Load);
+
+        Self {
+            _stream: subscribe(ctx, &api),
+            api,
+            card: None,
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            PointsCardMsg::Load => {
+                let (api, card_id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.get_points(&card_id).await {
+                        Ok(card) => PointsCardMsg::Received(card),
+                        Err(err) => PointsCardMsg::LoadErr(err),
+                    }
+                });
+                false
+            },
+            PointsCardMsg::Received(card) => {
+                self.card = Some(card);
+                self.error_msg = None;
+                true
+            },
+            PointsCardMsg::LoadErr(err) => {
+                console::log_1(&JsValue::from(format!("Load Error: {}", err)));
+                self.error_msg = Some(AttrValue::from(err.message()));
+                true
+            }
+        }
+    }
+
+    fn view(&self, _ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="container text-center">
+                <h1 class="display-1 py-3">{ "Your Points" }</h1>
+                if let Some(error_msg) = self.error_msg.clone() {
+                    <div class="alert alert-danger" role="alert">{ error_msg }</div>
+                }
+                if let Some(card) = &self.card {
+                    <div class="card text-bg-light">
+                        <div class="card-body">
+                            <p class="display-3 mb-0">{ card.stamps }</p>
+                            <p class="text-muted">{ format!("points, {} earned in total", card.total_stamps) }</p>
+                            <ul class="list-group">
+                                { for card.rewards.iter().map(|reward| {
+                                    let affordable = reward.cost <= card.stamps;
+                                    html! {
+                                        <li class={if affordable { "list-group-item list-group-item-success" } else { "list-group-item" }}>
+                                            { format!("{} for {} points", reward.name, reward.cost) }
+                                            if !affordable {
+                                                <small class="d-block text-muted">{ format!("{} more to go", reward.cost - card.stamps) }</small>
+                                            }
+                                        </li>
+                                    }
+                                }) }
+                            </ul>
+                        </div>
+                    </div>
+                }
+            </div>
+        }
+    }
+}
+
+/// Reloads the card whenever points are earned or spent, ignoring the customer's stamp card.
+fn subscribe(ctx: &Context<PointsCard>, api: &LoyaltyApiClient) -> Option<EventStream> {
+    // only a browser that has claimed a stamp for the card holds its key
+    let card_key = saved_card_key(&ctx.props().id)?;
+    let endpoint = api.card_stream_url(&ctx.props().id, &card_key);
+
+    let update_cb = ctx.link().batch_callback(|data: String| {
+        serde_json::from_str::<CardUpdate>(&data).ok()
+            .filter(|update| update.kind == CardKind::Points)
+            .map(|_| PointsCardMsg::Load)
+    });
+
+    // the browser reconnects a dropped stream by itself so there is nothing to do on error
+    EventStream::open(&endpoint, update_cb, Callback::noop())
+}
@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use wasm_bindgen_futures::js_sys::encode_uri_component;
+use wasm_bindgen_futures::wasm_bindgen::JsValue;
+use web_sys::{console, window};
+use yew::{AttrValue, Component, Context, Html, html, Properties};
+use yew::platform::time::sleep;
+use yew_router::prelude::Link;
+
+use loyalty_core::api::v1::{DataRequestResponse, PersonalDataResponse};
+
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::Route;
+
+const APPROVAL_POLL: Duration = Duration::from_secs(3);
+
+pub struct Privacy {
+    api: LoyaltyApiClient,
+    request: Option<DataRequestResponse>,
+    data: Option<PersonalDataResponse>,
+    erased: bool,
+    error_msg: Option<AttrValue>
+}
+
+pub enum PrivacyMsg {
+    Request,
+    Requested(DataRequestResponse),
+    /// Asks whether staff have approved the request yet.
+    Check,
+    Loaded(Box<PersonalDataResponse>),
+    Erase,
+    Erased,
+    Failed(ApiClientError)
+}
+
+#[derive(Properties, PartialEq)]
+pub struct PrivacyProps {
+    pub id: String
+}
+
+impl Component for Privacy {
+    type Message = PrivacyMsg;
+    type Properties = PrivacyProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            api: LoyaltyApiClient::new(),
+            request: None,
+            data: None,
+            erased: false,
+            error_msg: None
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            PrivacyMsg::Request => {
+                let (api, id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.request_data(&id).await {
+                        Ok(request) => PrivacyMsg::Requested(request),
+                        Err(err) => PrivacyMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            PrivacyMsg::Requested(request) => {
+                self.request = Some(request);
+                self.error_msg = None;
+                ctx.link().send_message(PrivacyMsg::Check);
+                true
+            },
+            PrivacyMsg::Check => {
+                let Some(request) = &self.request else { return false };
+
+                let (api, token) = (self.api.clone(), request.token.clone());
+                ctx.link().send_future(async move {
+                    loop {
+                        match api.personal_data(&token).await {
+                            Ok(data) => return PrivacyMsg::Loaded(Box::new(data)),
+                            Err(err) if err.status() == Some(403) => sleep(APPROVAL_POLL).await,
+                            Err(err) => return PrivacyMsg::Failed(err)
+                        }
+                    }
+                });
+                false
+            },
+            PrivacyMsg::Loaded(data) => {
+                self.data = Some(*data);
+                true
+            },
+            PrivacyMsg::Erase => {
+                let Some(request) = &self.request else { return false };
+
+                let confirmed = window()
+                    .and_then(|w| w.confirm_with_message("This deletes your card and every stamp on it. Are you sure?").ok())
+                    .unwrap_or(false);
+                if !confirmed {
+                    return false;
+                }
+
+                let (api, token) = (self.api.clone(), request.token.clone());
+                ctx.link().send_future(async move {
+                    match api.erase_data(&token).await {
+                        Ok(_) => PrivacyMsg::Erased,
+                        Err(err) => PrivacyMsg::Failed(err),
+                    }
+                });
+                false
+            },
+            PrivacyMsg::Erased => {
+                self.erased = true;
+                self.request = None;
+                self.data = None;
+                true
+            },
+            PrivacyMsg::Failed(err) => {
+                console::log_1(&JsValue::from(format!("Privacy request failed. {}", err)));
+                // an expired request has to be started again
+                if err.status() == Some(401) {
+                    self.request = None;
+                    self.data = None;
+                }
+                self.error_msg = Some(AttrValue::from(err.message()));
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <div class="container text-center">
+                <div class="row">
+                    <div class="col">
+                        <h1 class="display-1 py-3">{"Your Data"}</h1>
+
+                        if let Some(error_msg) = self.error_msg.clone() {
+                            <div class="alert alert-danger" role="alert">{ error_msg }</div>
+                        }
+
+                        { self.view_step(ctx) }
+
+                        <div class="py-3">
+                            <Link<Route> to={Route::StampCard { id: ctx.props().id.clone() }} classes="link-light">
+                                {"Back to your card"}
+                            </Link<Route>>
+                        </div>
+                    </div>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl Privacy {
+    fn view_step(&self, ctx: &Context<Self>) -> Html {
+        if self.erased {
+            return html! {
+                <div class="alert alert-success" role="status">{"Your card and phone number have been deleted"}</div>
+            };
+        }
+
+        match (&self.request, &self.data) {
+            (None, _) => html! {
+                <>
+                <p class="text-white">
+                    {"You can see everything we hold about your card, or ask for it to be deleted. "}
+                    {"A member of staff will need to check this is your phone before we show you anything."}
+                </p>
+                <button type="button" class="btn btn-primary"
+                    onclick={ctx.link().callback(|_| PrivacyMsg::Request)}>
+                    {"Request my data"}
+                </button>
+                </>
+            },
+            (Some(request), None) => html! {
+                <>
+                <p class="text-white">{"Show this code and your phone to a member of staff"}</p>
+                <p class="display-3 text-white font-monospace">{ &request.code }</p>
+                <p class="text-white">{"Waiting for approval..."}</p>
+                </>
+            },
+            (Some(_), Some(data)) => {
+                let json = serde_json::to_string_pretty(data).unwrap_or_default();
+                let download = format!("data:application/json;charset=utf-8,{}", String::from(encode_uri_component(&json)));
+
+                html! {
+                    <>
+                    <pre class="text-start bg-light p-3 rounded">{ json }</pre>
+                    <div class="d-flex justify-content-center gap-2">
+                        <a class="btn btn-primary" href={download} download="my-loyalty-data.json">{"Download"}</a>
+                        <button type="button" class="btn btn-danger"
+                            onclick={ctx.link().callback(|_| PrivacyMsg::Erase)}>
+                            {"Delete my data"}
+                        </button>
+                    </div>
+                    </>
+                }
+            }
+        }
+    }
+}
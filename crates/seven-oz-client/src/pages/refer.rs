@@ -0,0 +1,44 @@
+use web_sys::window;
+use yew::{function_component, html, use_effect_with, Html, Properties};
+
+use crate::config;
+
+const REFERRAL_STORAGE: &str = "loyalty-referral";
+
+#[derive(Properties, PartialEq)]
+pub struct ReferProps {
+    pub code: String
+}
+
+/// Where a shared referral link lands; the code is kept until the customer's first claim.
+#[function_component]
+pub fn Refer(props: &ReferProps) -> Html {
+    use_effect_with(props.code.clone(), |code| save_referral(Some(code)));
+
+    html! {
+        <div class="container text-center">
+            <div class="row">
+                <div class="col">
+                    <h1 class="display-1 py-3">{"You've been invited"}</h1>
+                    <p class="lead">
+                        {"Scan the QR code in store to collect your first stamp and you and your friend will both get a bonus stamp."}
+                    </p>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+pub fn saved_referral() -> Option<String> {
+    window()?.local_storage().ok()??.get_item(&config::storage_key(REFERRAL_STORAGE)).ok()?
+}
+
+pub fn save_referral(code: Option<&str>) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok().flatten())
+        else { return };
+
+    _ = match code {
+        Some(code) => storage.set_item(&config::storage_key(REFERRAL_STORAGE), code),
+        None => storage.remove_item(&config::storage_key(REFERRAL_STORAGE))
+    };
+}
@@ -1,28 +1,71 @@
-use reqwasm::http::Request;
-use serde::{Deserialize, Serialize};
-use wasm_bindgen_futures::js_sys::JsString;
+use std::time::Duration;
+
 use wasm_bindgen_futures::wasm_bindgen::JsValue;
-use web_sys::console;
-use yew::{Callback, Component, Context, Html, html, Properties};
-use yew_router::prelude::RouterScopeExt;
+use web_sys::{console, window, HtmlSelectElement};
+use yew::{AttrValue, Callback, Component, Context, Event, Html, html, Properties, TargetCast};
+use yew::platform::time::sleep;
+use yew_router::prelude::{Link, RouterScopeExt};
+
+use loyalty_core::api::v1::{CardEvent, CardKind, CardResponse, CardUpdate, CatalogueReward, StampExpiry, TenantResponse, TierProgress};
 
+use crate::api_client::{ApiClientError, LoyaltyApiClient};
+use crate::config;
+use crate::theme;
+use crate::components::celebration::Celebration;
 use crate::components::qrcode_image::QrCodeImage;
 use crate::components::stamp_area::StampArea;
-use crate::get_api_base;
+use crate::event_stream::EventStream;
+use crate::pages::collect::saved_card_key;
+use crate::pages::admin::load_admin_key;
+use crate::Route;
 
 const REDEEM_PARAM: &str = "?redeem=1";
+const CELEBRATION_LENGTH: Duration = Duration::from_secs(3);
+/// Servers from before cards said how many stamps they hold always gave out ten.
+const DEFAULT_CAPACITY: u32 = 10;
+const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
 
 pub struct StampCard {
+    api: LoyaltyApiClient,
+    business: Option<AttrValue>,
     stamp_count: u32,
+    capacity: u32,
+    expiring: Vec<StampExpiry>,
+    birth_month: Option<u32>,
+    redemptions: u32,
+    tier: Option<TierProgress>,
+    rewards: Vec<CatalogueReward>,
+    referral_link: Option<String>,
     query: String,
-    location: String
+    /// Whether this browser is signed in to the dashboard, and so can redeem from the redeem QR code.
+    staff: bool,
+    location: String,
+    celebration: Option<CardCelebration>,
+    error_msg: Option<AttrValue>,
+    _stream: Option<EventStream>
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CardCelebration {
+    Full,
+    Redeemed,
+    Promoted
 }
 
 pub enum StampCardMsg {
-    StampsReceived(u32),
+    Load,
+    StampsReceived(CardResponse),
+    ReferralReceived(String),
+    TenantReceived(TenantResponse),
+    BirthMonthChosen(Option<u32>),
+    LoadErr(ApiClientError),
+    Updated(CardUpdate),
+    CelebrationDone,
     StampsResetRequested,
     StampsResetOk,
-    StampsResetErr(u16)
+    RewardRequested(String),
+    RewardOk(CardResponse),
+    StampsResetErr(ApiClientError)
 }
 
 #[derive(Properties, PartialEq)]
@@ -35,29 +78,127 @@ impl Component for StampCard {
     type Properties = StampCardProps;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let card_callback = ctx.link().callback(StampCardMsg::StampsReceived);
-        get_stamp_card(card_callback, ctx.props().id.clone());
+        // redeeming changes the card, so the staff member scanning the redeem code needs the dashboard's key
+        let admin_key = load_admin_key();
+        let api = match &admin_key {
+            Some(key) => LoyaltyApiClient::new().with_admin_key(key),
+            None => LoyaltyApiClient::new()
+        };
+        ctx.link().send_message(StampCardMsg::Load);
+        let (referrals, card_id) = (api.clone(), ctx.props().id.clone());
+        ctx.link().send_future_batch(async move {
+            match referrals.referral_link(&card_id).await {
+                Ok(link) => vec![StampCardMsg::ReferralReceived(link.code)],
+                Err(err) => {
+                    // the card still works without a referral link so just leave it off
+                    console::log_1(&JsValue::from(format!("Referral link error: {}", err)));
+                    vec![]
+                }
+            }
+        });
+        let tenant = api.clone();
+        ctx.link().send_future_batch(async move {
+            match tenant.get_tenant().await {
+                Ok(tenant) => vec![StampCardMsg::TenantReceived(tenant)],
+                Err(err) => {
+                    console::log_1(&JsValue::from(format!("Tenant error: {}", err)));
+                    vec![]
+                }
+            }
+        });
         let location = ctx.link().location().unwrap();
         let query = ctx.link().location().unwrap().query_str().to_string();
-        
+
         Self {
+            _stream: subscribe(ctx, &api),
+            api,
+            business: None,
             stamp_count: 0,
+            capacity: DEFAULT_CAPACITY,
+            expiring: Vec::new(),
+            birth_month: None,
+            redemptions: 0,
+            tier: None,
+            rewards: Vec::new(),
+            referral_link: None,
             query,
-            location: location.path().to_string()
+            staff: admin_key.is_some(),
+            location: location.path().to_string(),
+            celebration: None,
+            error_msg: None
         }
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
-            StampCardMsg::StampsReceived(count) => {
-                self.stamp_count = count;
+            StampCardMsg::Load => {
+                let (api, card_id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.get_card(&card_id).await {
+                        Ok(card) => StampCardMsg::StampsReceived(card),
+                        Err(err) => StampCardMsg::LoadErr(err),
+                    }
+                });
+                false
+            },
+            StampCardMsg::StampsReceived(card) => {
+                self.stamp_count = card.stamps;
+                self.capacity = capacity_or_default(card.capacity);
+                self.expiring = card.expiring;
+                self.birth_month = card.birth_month;
+                self.redemptions = card.redemptions;
+                self.tier = card.tier;
+                self.rewards = card.rewards;
+                true
+            },
+            StampCardMsg::BirthMonthChosen(month) => {
+                let (api, card_id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.set_birthday(&card_id, month).await {
+                        Ok(card) => StampCardMsg::StampsReceived(card),
+                        Err(err) => StampCardMsg::LoadErr(err),
+                    }
+                });
+                false
+            },
+            StampCardMsg::ReferralReceived(code) => {
+                let origin = window().and_then(|window| window.location().origin().ok()).unwrap_or_default();
+                self.referral_link = Some(format!("{}{}/refer/{}", origin, config::tenant_prefix(), code));
+                true
+            },
+            StampCardMsg::TenantReceived(tenant) => {
+                self.business = Some(AttrValue::from(tenant.name));
+                true
+            },
+            StampCardMsg::LoadErr(err) => {
+                console::log_1(&JsValue::from(format!("Load Error: {}", err)));
+                self.error_msg = Some(AttrValue::from(err.message()));
+                true
+            },
+            StampCardMsg::Updated(update) => {
+                self.stamp_count = update.stamps;
+                self.capacity = capacity_or_default(update.capacity);
+                match update.event {
+                    CardEvent::Stamped if update.stamps >= self.capacity => self.celebrate(ctx, CardCelebration::Full),
+                    CardEvent::Redeemed => self.celebrate(ctx, CardCelebration::Redeemed),
+                    CardEvent::Promoted => self.celebrate(ctx, CardCelebration::Promoted),
+                    _ => {}
+                }
+                // the stream only carries the count so fetch the card again for its expiry dates
+                if update.event != CardEvent::Snapshot {
+                    ctx.link().send_message(StampCardMsg::Load);
+                }
+                true
+            },
+            StampCardMsg::CelebrationDone => {
+                self.celebration = None;
                 true
             },
             StampCardMsg::StampsResetRequested => {
                 console::log_1(&JsValue::from("Reset requested"));
-                let card_id = ctx.props().id.clone();
-                ctx.link().send_future(async {
-                    match reset_stamp_card(card_id).await {
+                let (api, card_id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.redeem_card(&card_id).await {
                         Ok(()) => StampCardMsg::StampsResetOk,
                         Err(err) => StampCardMsg::StampsResetErr(err),
                     }
@@ -67,15 +208,32 @@ impl Component for StampCard {
             },
             StampCardMsg::StampsResetOk => {
                 console::log_1(&JsValue::from("Reset OK"));
-                
-                // TODO replace this redirect with some live polling and refresh screen with "coffee on the way" animation
-                web_sys::window().unwrap().location().set_href(self.location.as_ref()).unwrap();
+                self.stamp_count = 0;
+                self.expiring.clear();
+                self.error_msg = None;
+                self.celebrate(ctx, CardCelebration::Redeemed);
+                true
+            },
+            StampCardMsg::RewardRequested(reward_id) => {
+                let (api, card_id) = (self.api.clone(), ctx.props().id.clone());
+                ctx.link().send_future(async move {
+                    match api.redeem_reward(&card_id, &reward_id).await {
+                        Ok(card) => StampCardMsg::RewardOk(card),
+                        Err(err) => StampCardMsg::StampsResetErr(err),
+                    }
+                });
                 false
             },
-            StampCardMsg::StampsResetErr(response_code) => {
-                // TODO some sort of visual feedback for error
-                console::log_1(&JsValue::from(format!("Reset Error: {}", response_code)));
+            StampCardMsg::RewardOk(card) => {
+                self.error_msg = None;
+                self.celebrate(ctx, CardCelebration::Redeemed);
+                ctx.link().send_message(StampCardMsg::StampsReceived(card));
                 false
+            },
+            StampCardMsg::StampsResetErr(err) => {
+                console::log_1(&JsValue::from(format!("Reset Error: {}", err)));
+                self.error_msg = Some(AttrValue::from(err.message()));
+                true
             }
         }
     }
@@ -84,8 +242,22 @@ impl Component for StampCard {
 
         html! {
             <div class="container-fluid text-center" style="height:100vh">
+                {
+                    match self.celebration {
+                        Some(CardCelebration::Full) => html! {
+                            <Celebration icon="🎉" message="Your card is full!" />
+                        },
+                        Some(CardCelebration::Redeemed) => html! {
+                            <Celebration icon="☕" message={theme::current().reward_text.clone()} />
+                        },
+                        Some(CardCelebration::Promoted) => html! {
+                            <Celebration icon="🏅" message="You've moved up a tier!" />
+                        },
+                        None => html! {}
+                    }
+                }
                 <div class="row col">
-                    <h1 class="display-1 py-3">{ "Your Loyalty Card" }</h1>
+                    <h1 class="display-1 py-3">{ &theme::current().card_title }</h1>
                 </div>
 
                 <div class="row" style="height:100vh">
@@ -93,49 +265,32 @@ impl Component for StampCard {
                     <div class="col-10 d-flex flex-column">
                         <div class="card text-bg-light">
                             <div class="card-header">
-                                { "7oz" }
+                                if let Some(logo) = theme::current().logo_url.clone() {
+                                    <img src={config::asset_url(&logo)} alt="" class="me-2" style="height:2rem" />
+                                }
+                                { self.business.clone().unwrap_or_default() }
                             </div>
                             <div class="card-body">
-                                <div class="row col">
-                                    <div class="d-flex justify-content-between">
-                                        <StampArea is_stamped={self.stamp_count>=1} />
-                                        <StampArea is_stamped={self.stamp_count>=2} />
-                                        <StampArea is_stamped={self.stamp_count>=3} />
-                                    </div>
-                                </div>
-                                <div class="row col py-2"></div>
-                                <div class="row col">
-                                    <div class="d-flex justify-content-between">
-                                        <StampArea is_stamped={self.stamp_count>=4} />
-                                        <StampArea is_stamped={self.stamp_count>=5} />
-                                        <StampArea is_stamped={self.stamp_count>=6} />
-            
-                                    </div>
-                                </div>
-                                <div class="row col py-2"></div>
-                                <div class="row col">
-                                    <div class="d-flex justify-content-between">
-                                        <StampArea is_stamped={self.stamp_count>=7} />
-                                        <StampArea is_stamped={self.stamp_count>=8} />
-                                        <StampArea is_stamped={self.stamp_count>=9} />
-                                    </div>
-                                </div>
-                                <div class="row col py-2"></div>
-                                <div class="row col">
-                                    <div class="d-flex justify-content-between">
-                                        <div style="visibility:hidden">
-                                            <StampArea is_stamped={false} />
-                                        </div>
-                                        <StampArea is_stamped={self.stamp_count>=10} />
-                                        <div style="visibility:hidden">
-                                            <StampArea is_stamped={false} />
-                                        </div>
-                                    </div>
-                                </div>
+                                { view_stamps(self.stamp_count, self.capacity) }
                             </div>
                         </div>
+                        if let Some(tier) = &self.tier {
+                            { view_tier(tier, self.redemptions) }
+                        }
+                        if !self.rewards.is_empty() {
+                            { self.view_rewards(ctx) }
+                        }
                         <div class="mt-auto" style="height:300px">
-                            if self.query.clone() == REDEEM_PARAM {
+                            if let Some(error_msg) = self.error_msg.clone() {
+                                <div class="alert alert-danger" role="alert">{ error_msg }</div>
+                            }
+                            { for self.expiring.iter().map(|expiry| html! {
+                                <div class="alert alert-warning" role="alert">{ expiry_warning(expiry) }</div>
+                            }) }
+                            if self.query.clone() == REDEEM_PARAM && !self.staff {
+                                <div class="alert alert-info" role="status">{"Sign in to the dashboard on this device to redeem"}</div>
+                            }
+                            else if self.query.clone() == REDEEM_PARAM {
                                 <button type="button"
                                     onclick={ctx.link().callback(|_| StampCardMsg::StampsResetRequested)}
                                     class="btn btn-danger btn-lg">{ "Redeem" }</button>
@@ -143,6 +298,30 @@ impl Component for StampCard {
                             else {
                                 <QrCodeImage link={ format!("{}{}", self.location, REDEEM_PARAM)} dim={150} module_dim={4} />
                             }
+                            if let Some(referral_link) = self.referral_link.clone() {
+                                <div class="py-2">
+                                    <label for="referral_link" class="form-label">{"Invite a friend and you both get a bonus stamp"}</label>
+                                    <input type="text" class="form-control text-center" id="referral_link" readonly=true value={referral_link}/>
+                                </div>
+                            }
+                            <div class="py-2">
+                                <label for="birth_month" class="form-label">{"Tell us your birthday month for a treat"}</label>
+                                <select class="form-select text-center" id="birth_month"
+                                    onchange={ctx.link().callback(|event: Event| {
+                                        let month = event.target_unchecked_into::<HtmlSelectElement>().value().parse().ok();
+                                        StampCardMsg::BirthMonthChosen(month)
+                                    })}>
+                                    <option value="" selected={self.birth_month.is_none()}>{"Rather not say"}</option>
+                                    { for MONTHS.iter().zip(1..).map(|(name, month)| html! {
+                                        <option value={month.to_string()} selected={self.birth_month == Some(month)}>{ *name }</option>
+                                    }) }
+                                </select>
+                            </div>
+                            <div class="py-2">
+                                <Link<Route> to={Route::Privacy { id: ctx.props().id.clone() }} classes="link-light">
+                                    {"Your data"}
+                                </Link<Route>>
+                            </div>
                         </div>
                     </div>
                     <div class="col"></div>
@@ -152,41 +331,123 @@ impl Component for StampCard {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
-struct CardResponse {
-    stamps: u32
+impl StampCard {
+    /// The programme's rewards, those the card can afford highlighted and redeemable by signed in staff.
+    fn view_rewards(&self, ctx: &Context<Self>) -> Html {
+        let redeeming = self.query == REDEEM_PARAM && self.staff;
+        html! {
+            <ul class="list-group mt-3">
+                { for self.rewards.iter().map(|reward| {
+                    let affordable = reward.cost <= self.stamp_count;
+                    let reward_id = reward.reward_id.clone();
+                    html! {
+                        <li class={if affordable { "list-group-item list-group-item-success d-flex justify-content-between align-items-center" } else { "list-group-item d-flex justify-content-between align-items-center" }}>
+                            <span>
+                                { format!("{} for {} stamps", reward.name, reward.cost) }
+                                if !affordable {
+                                    <small class="d-block text-muted">{ format!("{} more to go", reward.cost - self.stamp_count) }</small>
+                                }
+                            </span>
+                            if redeeming && affordable {
+                                <button type="button" class="btn btn-danger btn-sm"
+                                    onclick={ctx.link().callback(move |_| StampCardMsg::RewardRequested(reward_id.clone()))}>
+                                    { "Redeem" }
+                                </button>
+                            }
+                        </li>
+                    }
+                }) }
+            </ul>
+        }
+    }
+
+    fn celebrate(&mut self, ctx: &Context<Self>, celebration: CardCelebration) {
+        self.celebration = Some(celebration);
+        ctx.link().send_future(async {
+            sleep(CELEBRATION_LENGTH).await;
+            StampCardMsg::CelebrationDone
+        });
+    }
 }
 
-fn get_stamp_card(code_cb: Callback<u32>, id: String) {
-    wasm_bindgen_futures::spawn_local(async move {
-        let api_base = get_api_base();
-        let endpoint = format!("{}/api/stampcard/{}", api_base, id);
+/// A slot for every stamp the card holds, wrapping onto as many rows as the screen needs,
+/// read out as a count since the slots themselves say nothing.
+fn view_stamps(stamps: u32, capacity: u32) -> Html {
+    let label = format!("{} of {} stamps", stamps.min(capacity), capacity);
+    html! {
+        <div role="img" aria-label={label} class="mx-auto"
+            style="display:grid; grid-template-columns:repeat(auto-fit, minmax(56px, 1fr)); justify-items:center; gap:1rem; max-width:22rem">
+            { for (1..=capacity).map(|slot| html! {
+                <StampArea is_stamped={stamps >= slot} />
+            }) }
+        </div>
+    }
+}
 
-        let resp = Request::get(&endpoint)
-            //.header("Access-Control-Allow-Origin", "http://localhost:8000/")
-            .send()
-            .await.unwrap()
-            .json::<CardResponse>()
-            .await.unwrap();
+fn capacity_or_default(capacity: u32) -> u32 {
+    match capacity {
+        0 => DEFAULT_CAPACITY,
+        capacity => capacity
+    }
+}
 
-        console::log_1(&JsString::from(
-            serde_json::to_string(&resp).unwrap()
-        ));
+/// The customer's tier with its perks and a bar showing how close they are to the next one.
+fn view_tier(tier: &TierProgress, redemptions: u32) -> Html {
+    let from = tier.current.as_ref().map_or(0, |current| current.redemptions);
+    html! {
+        <div class="card text-bg-light mt-3">
+            <div class="card-body">
+                <h5 class="card-title">{ tier.current.as_ref().map_or("No tier yet", |current| current.name.as_str()) }</h5>
+                if let Some(current) = &tier.current {
+                    { for current.perks.iter().map(|perk| html! {
+                        <span class="badge text-bg-secondary me-1">{ perk }</span>
+                    }) }
+                }
+                {
+                    match &tier.next {
+                        Some(next) => {
+                            let percent = (redemptions - from) * 100 / (next.redemptions - from).max(1);
+                            html! {
+                                <>
+                                <div class="progress my-2" role="progressbar" aria-valuemin="0" aria-valuemax="100" aria-valuenow={percent.to_string()}>
+                                    <div class="progress-bar" style={format!("width: {}%", percent)}></div>
+                                </div>
+                                <small>{ match next.redemptions - redemptions {
+                                    1 => format!("1 more card to {}", next.name),
+                                    cards => format!("{} more cards to {}", cards, next.name)
+                                } }</small>
+                                </>
+                            }
+                        },
+                        None => html! { <p class="mb-0 mt-2">{"You're in our top tier"}</p> }
+                    }
+                }
+            </div>
+        </div>
+    }
+}
 
-        code_cb.emit(resp.stamps.into());
-    });
+fn expiry_warning(expiry: &StampExpiry) -> String {
+    let date = expiry.expires.get(..10).unwrap_or(&expiry.expires);
+    match expiry.stamps {
+        1 => format!("1 stamp expires on {}", date),
+        stamps => format!("{} stamps expire on {}", stamps, date)
+    }
 }
 
-async fn reset_stamp_card(id: String) -> Result<(), u16> {
-    let api_base = get_api_base();
-    let endpoint = format!("{}/api/stampcard/{}/reset", api_base, id);
+/// Listens for stamps and redemptions on this card so the page can update in place.
+fn subscribe(ctx: &Context<StampCard>, api: &LoyaltyApiClient) -> Option<EventStream> {
+    // only a browser that has claimed a stamp for the card holds its key
+    let card_key = saved_card_key(&ctx.props().id)?;
+    let endpoint = api.card_stream_url(&ctx.props().id, &card_key);
 
-    let resp = Request::post(&endpoint)
-        .send()
-        .await.unwrap();
+    let update_cb = ctx.link().batch_callback(|data: String| {
+        // the stream carries every card the customer has so leave out their points card
+        serde_json::from_str::<CardUpdate>(&data).ok()
+            .filter(|update| update.kind == CardKind::Stamps)
+            .map(StampCardMsg::Updated)
+    });
 
-    match resp.status() {
-        200 => Ok(()),
-        _ => Err(resp.status())
-    }
-}
\ No newline at end of file
+    // the browser reconnects a dropped stream by itself so there is nothing to do on error
+    EventStream::open(&endpoint, update_cb, Callback::noop())
+}
@@ -0,0 +1,28 @@
+use std::sync::OnceLock;
+
+use wasm_bindgen_futures::wasm_bindgen::JsValue;
+use web_sys::console;
+
+use loyalty_core::api::v1::ThemeDetails;
+
+use crate::api_client::LoyaltyApiClient;
+
+static THEME: OnceLock<ThemeDetails> = OnceLock::new();
+
+/// Fetches the business's theme, keeping the default look when it cannot be loaded.
+/// Must finish after `config::load` and before the first page renders.
+pub async fn load() {
+    let theme = match LoyaltyApiClient::new().get_theme().await {
+        Ok(theme) => theme,
+        Err(err) => {
+            console::log_1(&JsValue::from(format!("Theme error: {}", err)));
+            ThemeDetails::default()
+        }
+    };
+
+    _ = THEME.set(theme);
+}
+
+pub fn current() -> &'static ThemeDetails {
+    THEME.get_or_init(ThemeDetails::default)
+}
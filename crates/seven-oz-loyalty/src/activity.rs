@@ -0,0 +1,137 @@
+use actix_web::{HttpResponse, web};
+use log::warn;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ActivityKind, ActivityResponse, ErrorResponse};
+use loyalty_core::card::LoyaltyCard;
+use loyalty_core::{StoreId, UserId};
+
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+/// A change to a card, kept so staff can see how a card got to where it is.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CardActivity {
+    pub timestamp: DateTime,
+    pub user_id: UserId,
+    pub kind: ActivityKind,
+    pub delta: i32,
+    pub stamps: u32, // the card's stamps or points once the change was made
+    pub store_id: Option<StoreId>,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub staff: Option<String>,
+    #[serde(default)]
+    pub campaign: Option<String>,
+}
+
+impl CardActivity {
+    pub fn new(kind: ActivityKind, card: &impl LoyaltyCard, delta: i32) -> Self {
+        CardActivity {
+            timestamp: DateTime::now(),
+            user_id: card.user_id().clone(),
+            kind,
+            delta,
+            stamps: card.balance(),
+            store_id: None,
+            reason: None,
+            staff: None,
+            campaign: None
+        }
+    }
+
+    pub fn store(mut self, store_id: &StoreId) -> Self {
+        self.store_id = Some(store_id.clone());
+        self
+    }
+
+    pub fn reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    pub fn campaign(mut self, campaign_id: &str) -> Self {
+        self.campaign = Some(campaign_id.to_string());
+        self
+    }
+
+    /// Marks the change as made by hand from the dashboard.
+    pub fn staff(mut self, _: &Admin) -> Self {
+        self.staff = Some(String::from("admin"));
+        self
+    }
+}
+
+impl From<CardActivity> for ActivityResponse {
+    fn from(activity: CardActivity) -> Self {
+        ActivityResponse {
+            user_id: activity.user_id.to_string(),
+            kind: activity.kind,
+            delta: activity.delta,
+            stamps: activity.stamps,
+            store_id: activity.store_id,
+            reason: activity.reason,
+            staff: activity.staff,
+            campaign: activity.campaign,
+            timestamp: activity.timestamp.try_to_rfc3339_string().unwrap_or_default()
+        }
+    }
+}
+
+impl TryFrom<ActivityResponse> for CardActivity {
+    type Error = String;
+
+    fn try_from(activity: ActivityResponse) -> Result<Self, Self::Error> {
+        let timestamp = DateTime::parse_rfc3339_str(&activity.timestamp)
+            .map_err(|_| format!("'{}' is not a valid timestamp", activity.timestamp))?;
+
+        Ok(CardActivity {
+            timestamp,
+            user_id: UserId(activity.user_id),
+            kind: activity.kind,
+            delta: activity.delta,
+            stamps: activity.stamps,
+            store_id: activity.store_id,
+            reason: activity.reason,
+            staff: activity.staff,
+            campaign: activity.campaign
+        })
+    }
+}
+
+/// Writes the activity without failing the request, by now the card itself has already changed.
+pub async fn record(data: &AppData, activity: CardActivity) {
+    if let Err(err) = data.activity.lock().await.record(&activity).await {
+        warn!("Failed to record {:?} for card {}: {}", activity.kind, activity.user_id, err);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ActivityQuery {
+    limit: Option<i64>
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/admin/activity",
+    tag = "activity",
+    security(("admin_key" = [])),
+    params(("limit" = Option<i64>, Query, description = "How many entries to return, 50 by default")),
+    responses(
+        (status = 200, description = "The most recent changes across every card", body = Vec<ActivityResponse>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_activity(_: Admin, query: web::Query<ActivityQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let activity = data.activity.lock().await.recent(limit).await?;
+
+    let response: Vec<ActivityResponse> = activity.into_iter().map(ActivityResponse::from).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use actix_web::{HttpResponse, web};
+use actix_web::http::header;
+use chrono::{Days, NaiveDate};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ActivityKind, DailyCustomers, DailyRedemptions, DailyStamps, ErrorResponse, StampCount};
+use loyalty_core::{StoreId, UserId};
+
+use crate::activity::CardActivity;
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv
+}
+
+/// Both dates are inclusive and either can be left out.
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    #[serde(default)]
+    format: ReportFormat
+}
+
+impl ReportQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) if from > to => Err(ApiError::Validation(String::from("The from date must not be after the to date"))),
+            _ => Ok(())
+        }
+    }
+
+    fn start(&self) -> Option<DateTime> {
+        self.from.map(start_of)
+    }
+
+    fn end(&self) -> Option<DateTime> {
+        self.to.and_then(|to| to.checked_add_days(Days::new(1))).map(start_of)
+    }
+
+    fn includes(&self, date: NaiveDate) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+fn start_of(date: NaiveDate) -> DateTime {
+    DateTime::from_millis(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp_millis())
+}
+
+fn day(timestamp: &DateTime) -> NaiveDate {
+    chrono::DateTime::from_timestamp_millis(timestamp.timestamp_millis())
+        .unwrap_or_default()
+        .date_naive()
+}
+
+pub fn respond<T: Serialize>(rows: Vec<T>, format: ReportFormat, name: &str) -> Result<HttpResponse, ApiError> {
+    if format == ReportFormat::Json {
+        return Ok(HttpResponse::Ok().json(rows));
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row).map_err(|_| ApiError::Internal("failed to write report row"))?;
+    }
+    let body = writer.into_inner().map_err(|_| ApiError::Internal("failed to finish report"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}.csv\"", name)))
+        .body(body))
+}
+
+pub fn stamps_per_day(activity: &[CardActivity]) -> Vec<DailyStamps> {
+    let mut days: BTreeMap<(NaiveDate, Option<String>), i64> = BTreeMap::new();
+    for entry in activity.iter().filter(|entry| matches!(entry.kind, ActivityKind::Stamped | ActivityKind::Adjusted)) {
+        let store = entry.store_id.as_ref().map(|store_id| store_id.0.clone());
+        *days.entry((day(&entry.timestamp), store)).or_default() += entry.delta as i64;
+    }
+
+    days.into_iter()
+        .map(|((date, store_id), stamps)| DailyStamps {
+            date: date.to_string(),
+            store_id: store_id.map(StoreId),
+            stamps
+        })
+        .collect()
+}
+
+pub fn redemptions_per_day(activity: &[CardActivity]) -> Vec<DailyRedemptions> {
+    let mut days: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+    for entry in activity.iter().filter(|entry| entry.kind == ActivityKind::Redeemed) {
+        *days.entry(day(&entry.timestamp)).or_default() += 1;
+    }
+
+    days.into_iter()
+        .map(|(date, redemptions)| DailyRedemptions { date: date.to_string(), redemptions })
+        .collect()
+}
+
+/// Expects every activity up to the end of the report, oldest first, so each customer's first visit is known.
+pub fn customers_per_day(activity: &[CardActivity], query: &ReportQuery) -> Vec<DailyCustomers> {
+    let mut first_seen: HashMap<&UserId, NaiveDate> = HashMap::new();
+    let mut days: BTreeMap<NaiveDate, HashSet<&UserId>> = BTreeMap::new();
+
+    for entry in activity {
+        let date = day(&entry.timestamp);
+        first_seen.entry(&entry.user_id).or_insert(date);
+        // imports and expiries are not visits, but an import does make the customer's next visit a returning one
+        if query.includes(date) && !matches!(entry.kind, ActivityKind::Imported | ActivityKind::Expired) {
+            days.entry(date).or_default().insert(&entry.user_id);
+        }
+    }
+
+    days.into_iter()
+        .map(|(date, customers)| {
+            let new = customers.iter().filter(|user_id| first_seen.get(*user_id) == Some(&date)).count() as u32;
+            DailyCustomers {
+                date: date.to_string(),
+                active: customers.len() as u32,
+                new,
+                returning: customers.len() as u32 - new
+            }
+        })
+        .collect()
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/admin/analytics/stamps",
+    tag = "analytics",
+    security(("admin_key" = [])),
+    params(
+        ("from" = Option<String>, Query, description = "First day to include, as YYYY-MM-DD"),
+        ("to" = Option<String>, Query, description = "Last day to include, as YYYY-MM-DD"),
+        ("format" = Option<String>, Query, description = "json (the default) or csv")
+    ),
+    responses(
+        (status = 200, description = "Stamps given per day and store", body = Vec<DailyStamps>),
+        (status = 400, description = "Invalid date range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn stamps_report(_: Admin, query: web::Query<ReportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    let activity = data.activity.lock().await.between(query.start(), query.end()).await?;
+
+    respond(stamps_per_day(&activity), query.format, "stamps")
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/analytics/redemptions",
+    tag = "analytics",
+    security(("admin_key" = [])),
+    params(
+        ("from" = Option<String>, Query, description = "First day to include, as YYYY-MM-DD"),
+        ("to" = Option<String>, Query, description = "Last day to include, as YYYY-MM-DD"),
+        ("format" = Option<String>, Query, description = "json (the default) or csv")
+    ),
+    responses(
+        (status = 200, description = "Rewards redeemed per day", body = Vec<DailyRedemptions>),
+        (status = 400, description = "Invalid date range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn redemptions_report(_: Admin, query: web::Query<ReportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    let activity = data.activity.lock().await.between(query.start(), query.end()).await?;
+
+    respond(redemptions_per_day(&activity), query.format, "redemptions")
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/analytics/customers",
+    tag = "analytics",
+    security(("admin_key" = [])),
+    params(
+        ("from" = Option<String>, Query, description = "First day to include, as YYYY-MM-DD"),
+        ("to" = Option<String>, Query, description = "Last day to include, as YYYY-MM-DD"),
+        ("format" = Option<String>, Query, description = "json (the default) or csv")
+    ),
+    responses(
+        (status = 200, description = "Active, new and returning customers per day", body = Vec<DailyCustomers>),
+        (status = 400, description = "Invalid date range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn customers_report(_: Admin, query: web::Query<ReportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    // read from the very beginning so a customer's first visit before the range still makes them returning
+    let activity = data.activity.lock().await.between(None, query.end()).await?;
+
+    respond(customers_per_day(&activity, &query), query.format, "customers")
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/analytics/stamp-counts",
+    tag = "analytics",
+    security(("admin_key" = [])),
+    params(
+        ("from" = Option<String>, Query, description = "Only count cards used on or after this day, as YYYY-MM-DD"),
+        ("to" = Option<String>, Query, description = "Only count cards used on or before this day, as YYYY-MM-DD"),
+        ("format" = Option<String>, Query, description = "json (the default) or csv")
+    ),
+    responses(
+        (status = 200, description = "How many cards hold each number of stamps right now", body = Vec<StampCount>),
+        (status = 400, description = "Invalid date range", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn stamp_counts_report(_: Admin, query: web::Query<ReportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    query.validate()?;
+    let cards = data.cards.lock().await.list_cards().await?;
+
+    let used: Option<HashSet<UserId>> = match query.from.is_some() || query.to.is_some() {
+        true => {
+            let activity = data.activity.lock().await.between(query.start(), query.end()).await?;
+            Some(activity.into_iter().map(|entry| entry.user_id).collect())
+        },
+        false => None
+    };
+
+    let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+    for card in cards.iter().filter(|card| used.as_ref().is_none_or(|used| used.contains(card.user_id()))) {
+        *counts.entry(card.stamps).or_default() += 1;
+    }
+
+    let rows: Vec<StampCount> = counts.into_iter().map(|(stamps, cards)| StampCount { stamps, cards }).collect();
+    respond(rows, query.format, "stamp-counts")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn activity(date: &str, user: &str, kind: ActivityKind, delta: i32, store: Option<&str>) -> CardActivity {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        CardActivity {
+            timestamp: DateTime::from_millis(start_of(date).timestamp_millis() + 60_000),
+            user_id: UserId(user.into()),
+            kind,
+            delta,
+            stamps: 0,
+            store_id: store.map(|store| StoreId(store.into())),
+            reason: None,
+            staff: None,
+            campaign: None
+        }
+    }
+
+    #[test]
+    fn stamps_are_totalled_per_day_and_store() {
+        let activity = vec![
+            activity("2024-03-01", "a", ActivityKind::Stamped, 1, Some("high-street")),
+            activity("2024-03-01", "b", ActivityKind::Stamped, 1, Some("high-street")),
+            activity("2024-03-01", "b", ActivityKind::Adjusted, -1, None),
+            activity("2024-03-02", "a", ActivityKind::Stamped, 1, Some("station")),
+            activity("2024-03-02", "a", ActivityKind::Redeemed, -10, None),
+        ];
+
+        let rows: Vec<(String, Option<String>, i64)> = stamps_per_day(&activity).into_iter()
+            .map(|row| (row.date, row.store_id.map(|store_id| store_id.0), row.stamps))
+            .collect();
+        assert_eq!(rows, vec![
+            ("2024-03-01".into(), None, -1),
+            ("2024-03-01".into(), Some("high-street".into()), 2),
+            ("2024-03-02".into(), Some("station".into()), 1),
+        ]);
+        assert_eq!(redemptions_per_day(&activity), vec![DailyRedemptions { date: "2024-03-02".into(), redemptions: 1 }]);
+    }
+
+    #[test]
+    fn customers_seen_before_the_range_are_returning() {
+        let activity = vec![
+            activity("2024-02-20", "a", ActivityKind::Stamped, 1, None),
+            activity("2024-03-01", "a", ActivityKind::Stamped, 1, None),
+            activity("2024-03-01", "b", ActivityKind::Stamped, 1, None),
+            activity("2024-03-01", "b", ActivityKind::Stamped, 1, None),
+        ];
+        let query = ReportQuery {
+            from: NaiveDate::from_ymd_opt(2024, 3, 1),
+            to: None,
+            format: ReportFormat::Json
+        };
+
+        assert_eq!(customers_per_day(&activity, &query), vec![
+            DailyCustomers { date: "2024-03-01".into(), active: 2, new: 1, returning: 1 }
+        ]);
+    }
+}
@@ -0,0 +1,69 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::AuditRecord;
+use loyalty_core::UserId;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ClaimRateLimited,
+    StampTooSoon,
+    DataAccessed,
+    /// Never carries the erased card's id.
+    DataErased,
+}
+
+impl AuditAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuditAction::ClaimRateLimited => "claim_rate_limited",
+            AuditAction::StampTooSoon => "stamp_too_soon",
+            AuditAction::DataAccessed => "data_accessed",
+            AuditAction::DataErased => "data_erased"
+        }
+    }
+}
+
+/// A security relevant event kept for later review.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub timestamp: DateTime,
+    pub action: AuditAction,
+    pub user_id: Option<UserId>,
+    pub ip: Option<String>,
+    pub detail: String,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction, detail: impl Into<String>) -> Self {
+        AuditEntry {
+            timestamp: DateTime::now(),
+            action,
+            user_id: None,
+            ip: None,
+            detail: detail.into()
+        }
+    }
+
+    pub fn user(mut self, user_id: &UserId) -> Self {
+        self.user_id = Some(user_id.clone());
+        self
+    }
+
+    pub fn ip(mut self, ip: &str) -> Self {
+        self.ip = Some(ip.to_string());
+        self
+    }
+}
+
+impl From<AuditEntry> for AuditRecord {
+    fn from(entry: AuditEntry) -> Self {
+        AuditRecord {
+            timestamp: entry.timestamp.try_to_rfc3339_string().unwrap_or_default(),
+            action: entry.action.name().to_string(),
+            ip: entry.ip,
+            detail: entry.detail
+        }
+    }
+}
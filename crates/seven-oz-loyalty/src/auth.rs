@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+
+use actix_web::{FromRequest, HttpRequest, web};
+use actix_web::dev::Payload;
+use actix_web::http::header;
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use loyalty_core::UserId;
+
+use crate::AppData;
+use crate::devices::{hash_key, Device};
+use crate::error::ApiError;
+
+/// Proves the request carries the admin key of the business the api belongs to.
+pub struct Admin;
+
+/// The paired display device that sent the request.
+pub struct PairedDevice(pub Device);
+
+/// A customer whose request for their own data has been approved by staff.
+pub struct VerifiedCustomer(pub UserId);
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str().ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+// browsers cannot set headers on an EventSource so displays may also pass their key in the query string
+fn device_token(req: &HttpRequest) -> Option<String> {
+    bearer_token(req).or_else(|| {
+        web::Query::<HashMap<String, String>>::from_query(req.query_string()).ok()?
+            .get("access_token")
+            .cloned()
+    })
+}
+
+fn is_admin(req: &HttpRequest, data: &AppData) -> bool {
+    // with no admin key configured the admin api is disabled entirely
+    match (&data.tenant.admin_key, bearer_token(req)) {
+        (Some(admin_key), Some(token)) => keys_match(admin_key, &token),
+        _ => false
+    }
+}
+
+/// Compares the digests of both keys byte by byte without stopping early, so the time taken
+/// says nothing about how much of a guessed key was right.
+pub fn keys_match(expected: &str, given: &str) -> bool {
+    let (expected, given) = (Sha256::digest(expected.as_bytes()), Sha256::digest(given.as_bytes()));
+    expected.iter().zip(given.iter()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn find_device(data: &AppData, token: &str) -> Result<Device, ApiError> {
+    let mut devices = data.devices.lock().await;
+    match devices.find_active_device(&hash_key(token)).await? {
+        Some(device) => Ok(device),
+        None => Err(ApiError::Unauthorized("Unknown or revoked device key"))
+    }
+}
+
+impl FromRequest for Admin {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(data) = req.app_data::<AppData>()
+            else { return ready(Err(ApiError::Internal("missing app data"))) };
+
+        if !is_admin(req, data) {
+            warn!("Rejected admin request to {}", req.path());
+            return ready(Err(ApiError::Unauthorized("Invalid admin key")));
+        }
+
+        ready(Ok(Admin))
+    }
+}
+
+impl FromRequest for PairedDevice {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let data = req.app_data::<AppData>().cloned();
+        let token = device_token(req);
+
+        Box::pin(async move {
+            let data = data.ok_or(ApiError::Internal("missing app data"))?;
+            let token = token.ok_or(ApiError::Unauthorized("Missing device key"))?;
+
+            Ok(PairedDevice(find_device(&data, &token).await?))
+        })
+    }
+}
+
+impl FromRequest for VerifiedCustomer {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let data = req.app_data::<AppData>().cloned();
+        let token = bearer_token(req);
+
+        Box::pin(async move {
+            let data = data.ok_or(ApiError::Internal("missing app data"))?;
+            let token = token.ok_or(ApiError::Unauthorized("Missing data request token"))?;
+
+            let requests = data.data_requests.lock().await;
+            match requests.get(&hash_key(&token)) {
+                Some(request) if request.is_expired() => Err(ApiError::Unauthorized("Your request has expired, please start again")),
+                Some(request) if !request.approved => Err(ApiError::NotApproved),
+                Some(request) => Ok(VerifiedCustomer(request.user_id.clone())),
+                None => Err(ApiError::Unauthorized("Unknown data request"))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::http::StatusCode;
+
+    use super::*;
+
+    #[test]
+    fn keys_must_match_exactly() {
+        assert!(keys_match("secret", "secret"));
+        assert!(!keys_match("secret", "secreT"));
+        assert!(!keys_match("secret", "secret "));
+        assert!(!keys_match("secret", ""));
+    }
+
+    #[actix_web::test]
+    async fn admin_routes_need_the_tenants_key() {
+        let data = crate::test_state(Some("letmein"));
+        let app = init_service(App::new().app_data(data).service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        let request = |key: Option<&str>| {
+            let request = TestRequest::get().uri("/api/admin/devices");
+            match key {
+                Some(key) => request.insert_header((header::AUTHORIZATION, format!("Bearer {}", key))),
+                None => request
+            }.to_request()
+        };
+        assert_eq!(call_service(&app, request(None)).await.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(call_service(&app, request(Some("letmeout"))).await.status(), StatusCode::UNAUTHORIZED);
+        // the right key gets as far as the database, which is not there in tests
+        assert_eq!(call_service(&app, request(Some("letmein"))).await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[actix_web::test]
+    async fn no_admin_key_disables_the_admin_api() {
+        let app = init_service(App::new().app_data(crate::test_state(None)).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::get().uri("/api/admin/devices")
+            .insert_header((header::AUTHORIZATION, "Bearer "))
+            .to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
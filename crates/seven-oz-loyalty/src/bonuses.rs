@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::Datelike;
+use log::{error, info, warn};
+use mongodb::bson::DateTime;
+use tokio::time::interval;
+
+use loyalty_core::api::v1::{ActivityKind, BonusRules, CardEvent, MilestoneBonus};
+
+use crate::activity::{self, CardActivity};
+use crate::AppData;
+use crate::db::StampCardRepositoryError;
+use crate::stampcard::{BasicStampCard, CardNotification};
+
+const BIRTHDAY_SWEEP_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// The milestone reached by redeeming the `redemptions`th card, if the programme has one.
+pub fn milestone(rules: &BonusRules, redemptions: u32) -> Option<MilestoneBonus> {
+    rules.milestones.iter().copied().find(|milestone| milestone.cards == redemptions)
+}
+
+/// Gives the milestone bonus for a card that has just been redeemed, without failing the redemption.
+pub async fn reward_milestone(data: &AppData, card: &BasicStampCard) {
+    if let Err(err) = try_reward_milestone(data, card).await {
+        warn!("Failed to give milestone bonus to card {}: {}", card.user_id(), err);
+    }
+}
+
+async fn try_reward_milestone(data: &AppData, card: &BasicStampCard) -> Result<(), StampCardRepositoryError> {
+    let Some(programme_id) = &card.programme_id else { return Ok(()) };
+    let Some(programme) = data.programmes.lock().await.find_programme(programme_id).await? else { return Ok(()) };
+    let Some(milestone) = milestone(&programme.bonuses, card.redemptions()) else { return Ok(()) };
+
+    let mut tracker = data.cards.lock().await;
+    let previous = tracker.get_or_create_card(card.user_id()).await?;
+    let bonus = tracker.adjust_card(card.user_id(), i32::try_from(milestone.stamps).unwrap_or(i32::MAX)).await?;
+    drop(tracker);
+    let reason = format!("Completed {} cards", milestone.cards);
+    announce(data, &previous, &bonus, &reason).await;
+    Ok(())
+}
+
+/// Background job that gives birthday bonuses to customers in their birthday month.
+pub async fn award_birthdays(data: AppData) {
+    let mut ticker = interval(BIRTHDAY_SWEEP_INTERVAL);
+    loop {
+        ticker.tick().await;
+
+        if let Err(err) = sweep(&data).await {
+            error!("Birthday bonus sweep failed: {:?}", err);
+        }
+    }
+}
+
+async fn sweep(data: &AppData) -> Result<(), StampCardRepositoryError> {
+    let bonuses: HashMap<String, u32> = data.programmes.lock().await.list_programmes().await?
+        .into_iter()
+        .filter_map(|programme| Some((programme.programme_id, programme.bonuses.birthday?)))
+        .collect();
+    if bonuses.is_empty() {
+        return Ok(())
+    }
+
+    let Some(today) = chrono::DateTime::from_timestamp_millis(DateTime::now().timestamp_millis())
+        else { return Ok(()) };
+    // the cards are only locked one at a time so claims are not held up for the whole sweep
+    let cards = data.cards.lock().await.list_cards().await?;
+    for listed in cards {
+        if !listed.birthday_due(today.month(), today.year()) {
+            continue
+        }
+        let mut tracker = data.cards.lock().await;
+        // read again so a stamp given since it was listed is not lost
+        let Some(card) = tracker.find_card(listed.user_id()).await? else { continue };
+        let stamps = card.programme_id.as_ref().and_then(|programme_id| bonuses.get(programme_id));
+        let Some(&stamps) = stamps.filter(|_| card.birthday_due(today.month(), today.year()))
+            else { continue };
+
+        let bonus = card.with_birthday_bonus(stamps, today.year());
+        tracker.save_card(&bonus).await?;
+        drop(tracker);
+        announce(data, &card, &bonus, "Happy birthday").await;
+    }
+
+    Ok(())
+}
+
+/// Records the stamps the bonus actually added, which a nearly full card may have cut short.
+async fn announce(data: &AppData, previous: &BasicStampCard, card: &BasicStampCard, reason: &str) {
+    let added = card.stamps as i32 - previous.stamps as i32;
+    info!("Card {} got {} bonus stamps: {}", card.user_id(), added, reason);
+    activity::record(data, CardActivity::new(ActivityKind::Bonus, card, added).reason(reason)).await;
+    _ = data.card_updates.send(CardNotification::new(card.user_id().clone(), CardEvent::Bonus, card));
+}
+
+#[cfg(test)]
+mod tests {
+    use loyalty_core::UserId;
+
+    use super::*;
+
+    #[test]
+    fn milestones_match_the_number_of_cards_redeemed() {
+        let rules = BonusRules {
+            birthday: None,
+            milestones: vec![MilestoneBonus { cards: 5, stamps: 2 }, MilestoneBonus { cards: 10, stamps: 5 }]
+        };
+
+        assert_eq!(milestone(&rules, 5), Some(MilestoneBonus { cards: 5, stamps: 2 }));
+        assert_eq!(milestone(&rules, 6), None);
+        assert_eq!(milestone(&rules, 10).map(|milestone| milestone.stamps), Some(5));
+    }
+
+    #[test]
+    fn birthday_bonus_is_given_once_a_year() {
+        let card = BasicStampCard::new(UserId("07715559999".into())).with_birth_month(Some(7));
+        assert!(card.birthday_due(7, 2024));
+        assert!(!card.birthday_due(8, 2024));
+
+        let card = card.with_birthday_bonus(2, 2024);
+        assert_eq!(card.stamps, 2);
+        assert!(!card.birthday_due(7, 2024));
+        assert!(card.birthday_due(7, 2025));
+    }
+}
@@ -0,0 +1,251 @@
+use actix_web::{HttpResponse, web};
+use chrono::Datelike;
+use log::info;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{CampaignDetails, CampaignReward, ErrorResponse, Weekday};
+use loyalty_core::StoreId;
+
+use crate::AppData;
+use crate::auth::{Admin, PairedDevice};
+use crate::error::ApiError;
+use crate::programmes::required;
+use crate::stampcard::MAX_CAPACITY;
+
+/// A promotion that gives extra stamps on claims made within its window.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Campaign {
+    pub campaign_id: String,
+    name: String,
+    starts: DateTime,
+    ends: DateTime,
+    weekdays: Vec<Weekday>,
+    stores: Vec<StoreId>,
+    programmes: Vec<String>,
+    reward: CampaignReward,
+}
+
+impl Campaign {
+    /// Whether a claim at `store_id` in `programme_id` made at `now` is covered.
+    pub fn applies(&self, now: DateTime, store_id: &StoreId, programme_id: Option<&str>) -> bool {
+        self.starts <= now && now < self.ends
+            && (self.weekdays.is_empty() || self.weekdays.contains(&weekday(now)))
+            && (self.stores.is_empty() || self.stores.contains(store_id))
+            && (self.programmes.is_empty() || programme_id.is_some_and(|id| self.programmes.iter().any(|p| p == id)))
+    }
+
+    /// How many stamps a single claim is worth during the campaign, never more than fill a card.
+    pub fn stamps(&self) -> u32 {
+        self.reward_stamps().min(MAX_CAPACITY)
+    }
+
+    fn reward_stamps(&self) -> u32 {
+        match self.reward {
+            CampaignReward::Multiplier { factor } => factor,
+            CampaignReward::Bonus { stamps } => stamps.saturating_add(1)
+        }
+    }
+}
+
+impl From<Campaign> for CampaignDetails {
+    fn from(campaign: Campaign) -> Self {
+        CampaignDetails {
+            campaign_id: campaign.campaign_id,
+            name: campaign.name,
+            starts: campaign.starts.try_to_rfc3339_string().unwrap_or_default(),
+            ends: campaign.ends.try_to_rfc3339_string().unwrap_or_default(),
+            weekdays: campaign.weekdays,
+            stores: campaign.stores,
+            programmes: campaign.programmes,
+            reward: campaign.reward
+        }
+    }
+}
+
+/// The most generous campaign covering a claim, when campaigns overlap.
+pub fn best<'a>(campaigns: &'a [Campaign], now: DateTime, store_id: &StoreId, programme_id: Option<&str>) -> Option<&'a Campaign> {
+    campaigns.iter()
+        .filter(|campaign| campaign.applies(now, store_id, programme_id))
+        .max_by_key(|campaign| campaign.stamps())
+}
+
+fn weekday(date: DateTime) -> Weekday {
+    let weekday = chrono::DateTime::from_timestamp_millis(date.timestamp_millis())
+        .map(|date| date.weekday())
+        .unwrap_or(chrono::Weekday::Mon);
+    match weekday {
+        chrono::Weekday::Mon => Weekday::Monday,
+        chrono::Weekday::Tue => Weekday::Tuesday,
+        chrono::Weekday::Wed => Weekday::Wednesday,
+        chrono::Weekday::Thu => Weekday::Thursday,
+        chrono::Weekday::Fri => Weekday::Friday,
+        chrono::Weekday::Sat => Weekday::Saturday,
+        chrono::Weekday::Sun => Weekday::Sunday
+    }
+}
+
+fn timestamp(value: &str, field: &str) -> Result<DateTime, ApiError> {
+    DateTime::parse_rfc3339_str(value.trim())
+        .map_err(|_| ApiError::Validation(format!("The {} time must be an RFC 3339 timestamp", field)))
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/admin/campaigns",
+    tag = "campaigns",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Every campaign, past, live and upcoming", body = Vec<CampaignDetails>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_campaigns(_: Admin, data: AppData) -> Result<HttpResponse, ApiError> {
+    let campaigns = data.campaigns.lock().await.list_campaigns().await?;
+
+    let response: Vec<CampaignDetails> = campaigns.into_iter().map(CampaignDetails::from).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/campaigns",
+    tag = "campaigns",
+    security(("admin_key" = [])),
+    request_body = CampaignDetails,
+    responses(
+        (status = 200, description = "The campaign has been created or replaced", body = CampaignDetails),
+        (status = 400, description = "A required field is missing, the window is empty or the reward gives nothing extra", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn save_campaign(_: Admin, request: web::Json<CampaignDetails>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let request = request.into_inner();
+    let campaign = Campaign {
+        campaign_id: required(&request.campaign_id, "campaign id")?,
+        name: required(&request.name, "name")?,
+        starts: timestamp(&request.starts, "start")?,
+        ends: timestamp(&request.ends, "end")?,
+        weekdays: request.weekdays,
+        stores: request.stores,
+        programmes: request.programmes,
+        reward: request.reward
+    };
+    if campaign.ends <= campaign.starts {
+        return Err(ApiError::Validation(String::from("A campaign must end after it starts")))
+    }
+    if campaign.stamps() < 2 {
+        return Err(ApiError::Validation(String::from("A campaign must give at least one extra stamp")))
+    }
+    if campaign.reward_stamps() > MAX_CAPACITY {
+        return Err(ApiError::Validation(format!("A campaign can give at most {} stamps a claim", MAX_CAPACITY)))
+    }
+
+    data.campaigns.lock().await.save_campaign(&campaign).await?;
+
+    info!("Saved campaign {}", campaign.campaign_id);
+    Ok(HttpResponse::Ok().json(CampaignDetails::from(campaign)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/campaigns/{campaign_id}",
+    tag = "campaigns",
+    security(("admin_key" = [])),
+    params(("campaign_id" = String, Path, description = "The campaign to delete")),
+    responses(
+        (status = 200, description = "The campaign has been deleted"),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such campaign", body = ErrorResponse)
+    )
+)]
+pub async fn delete_campaign(_: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let campaign_id = path.into_inner();
+
+    match data.campaigns.lock().await.delete_campaign(&campaign_id).await? {
+        true => Ok(HttpResponse::Ok().finish()),
+        false => Err(ApiError::NotFound("Campaign"))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/campaigns/live",
+    tag = "campaigns",
+    security(("device_key" = [])),
+    responses(
+        (status = 200, description = "Campaigns covering claims at the display's store right now", body = Vec<CampaignDetails>),
+        (status = 401, description = "Missing, invalid or revoked device key", body = ErrorResponse)
+    )
+)]
+pub async fn live_campaigns(device: PairedDevice, data: AppData) -> Result<HttpResponse, ApiError> {
+    let store = data.stores.lock().await.find_store(&device.0.store_id).await?;
+    let programme_id = store.and_then(|store| store.programme_id);
+    let campaigns = data.campaigns.lock().await.list_campaigns().await?;
+
+    let now = DateTime::now();
+    let response: Vec<CampaignDetails> = campaigns.into_iter()
+        .filter(|campaign| campaign.applies(now, &device.0.store_id, programme_id.as_deref()))
+        .map(CampaignDetails::from)
+        .collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn campaign(weekdays: Vec<Weekday>, stores: Vec<&str>, programmes: Vec<&str>, reward: CampaignReward) -> Campaign {
+        Campaign {
+            campaign_id: "launch".into(),
+            name: "Launch week".into(),
+            starts: DateTime::parse_rfc3339_str("2024-03-01T00:00:00Z").unwrap(),
+            ends: DateTime::parse_rfc3339_str("2024-04-01T00:00:00Z").unwrap(),
+            weekdays,
+            stores: stores.into_iter().map(|store| StoreId(store.into())).collect(),
+            programmes: programmes.into_iter().map(String::from).collect(),
+            reward
+        }
+    }
+
+    fn at(time: &str) -> DateTime {
+        DateTime::parse_rfc3339_str(time).unwrap()
+    }
+
+    #[test]
+    fn campaigns_apply_within_their_window_days_and_stores() {
+        let mondays = campaign(vec![Weekday::Monday], vec!["high-street"], vec![], CampaignReward::Multiplier { factor: 2 });
+        let store = StoreId("high-street".into());
+
+        assert!(mondays.applies(at("2024-03-04T09:00:00Z"), &store, None));
+        assert!(!mondays.applies(at("2024-03-05T09:00:00Z"), &store, None));
+        assert!(!mondays.applies(at("2024-04-01T09:00:00Z"), &store, None));
+        assert!(!mondays.applies(at("2024-03-04T09:00:00Z"), &StoreId("station".into()), None));
+
+        let coffee = campaign(vec![], vec![], vec!["coffee"], CampaignReward::Bonus { stamps: 1 });
+        assert!(coffee.applies(at("2024-03-05T09:00:00Z"), &store, Some("coffee")));
+        assert!(!coffee.applies(at("2024-03-05T09:00:00Z"), &store, None));
+    }
+
+    #[test]
+    fn the_most_generous_campaign_wins() {
+        let campaigns = vec![
+            campaign(vec![], vec![], vec![], CampaignReward::Bonus { stamps: 1 }),
+            campaign(vec![], vec![], vec![], CampaignReward::Multiplier { factor: 3 }),
+        ];
+
+        let winner = best(&campaigns, at("2024-03-05T09:00:00Z"), &StoreId("high-street".into()), None);
+        assert_eq!(winner.map(Campaign::stamps), Some(3));
+        assert!(best(&campaigns, at("2024-05-01T09:00:00Z"), &StoreId("high-street".into()), None).is_none());
+    }
+
+    #[test]
+    fn huge_rewards_are_capped() {
+        let bonus = campaign(vec![], vec![], vec![], CampaignReward::Bonus { stamps: u32::MAX });
+        let multiplier = campaign(vec![], vec![], vec![], CampaignReward::Multiplier { factor: u32::MAX });
+
+        assert_eq!((bonus.reward_stamps(), bonus.stamps()), (u32::MAX, MAX_CAPACITY));
+        assert_eq!(multiplier.stamps(), MAX_CAPACITY);
+    }
+}
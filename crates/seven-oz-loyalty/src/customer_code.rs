@@ -1,74 +1,333 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, web};
 use actix_web::dev::ConnectionInfo;
-use actix_web::{get, HttpResponse, post, web};
 use log::{info, warn};
-use serde::{Deserialize, Serialize};
-use loyalty_core::qr_gen::CustomerQrCode;
-use loyalty_core::UserId;
+use mongodb::bson::DateTime;
+use tokio::sync::{broadcast, MutexGuard};
+use tokio::time::interval;
+use loyalty_core::api::v1::{ActivityKind, CardEvent, ClaimRequest, ClaimResponse, CodeResponse, ErrorResponse};
+use loyalty_core::qr_gen::{rand_string, CustomerQrCode};
+use loyalty_core::{PhoneNumber, StoreId, UserId};
+use crate::activity::{self, CardActivity};
 use crate::AppData;
+use crate::audit::{AuditAction, AuditEntry};
+use crate::campaigns::{self, Campaign};
+use crate::devices;
+use crate::auth::PairedDevice;
+use crate::error::ApiError;
+use crate::rate_limit::{ClaimLimiter, LimitExceeded};
+use crate::referrals;
+use crate::sse;
+use crate::stampcard::CardNotification;
 
-#[derive(Serialize)]
-struct CodeResponse {
-    code: String
+/// How long a code stays on a display before it is replaced even if nobody claims it.
+const CODE_TTL: Duration = Duration::from_secs(5 * 60);
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(15);
+const CARD_KEY_LENGTH: usize = 32;
+
+pub struct ActiveCode {
+    qr: CustomerQrCode,
+    issued: Instant
+}
+
+impl ActiveCode {
+    fn new() -> Self {
+        Self {
+            qr: CustomerQrCode::new(),
+            issued: Instant::now()
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        self.qr.is_used() || self.issued.elapsed() > CODE_TTL
+    }
 }
 
-#[derive(Deserialize, Serialize)]
-pub struct ClaimRequest {
-    id: String,
+/// Sent to every connected display whenever a store's code changes.
+#[derive(Clone)]
+pub struct CodeUpdate {
+    store_id: StoreId,
     code: String
 }
 
-pub async fn get_code(data: AppData, conn: ConnectionInfo) -> HttpResponse {
-    info!("getting QR");
-    
-    let Ok(mut current_qr) = data.qr.lock() 
-        else {return HttpResponse::InternalServerError().finish()};
-    
-    match &*current_qr {
-        Some(qrcode) => {
-            if qrcode.is_used() {
-                *current_qr = Some(CustomerQrCode::new())
-            }
+/// Replaces the store's code and lets any listening displays know about it.
+fn rotate_code(codes: &mut HashMap<StoreId, ActiveCode>, store_id: &StoreId, updates: &broadcast::Sender<CodeUpdate>) -> String {
+    let active = ActiveCode::new();
+    let code = active.qr.code.clone();
+    codes.insert(store_id.clone(), active);
+
+    // an error here only means no display is currently subscribed
+    _ = updates.send(CodeUpdate {
+        store_id: store_id.clone(),
+        code: code.clone()
+    });
+    code
+}
+
+fn current_code(codes: &mut HashMap<StoreId, ActiveCode>, store_id: &StoreId, updates: &broadcast::Sender<CodeUpdate>) -> String {
+    match codes.get(store_id) {
+        Some(active) if !active.is_stale() => active.qr.code.clone(),
+        _ => rotate_code(codes, store_id, updates)
+    }
+}
+
+/// Background job that rotates codes which have been on display for too long.
+pub async fn expire_codes(data: AppData) {
+    let mut ticker = interval(EXPIRY_SWEEP_INTERVAL);
+    loop {
+        ticker.tick().await;
+
+        let mut codes = data.qr.lock().await;
+        let stale: Vec<StoreId> = codes.iter()
+            .filter(|(_, active)| active.is_stale())
+            .map(|(store_id, _)| store_id.clone())
+            .collect();
+
+        for store_id in stale {
+            info!("Code for store {} has expired", store_id);
+            rotate_code(&mut codes, &store_id, &data.code_updates);
         }
-        None => *current_qr = Some(CustomerQrCode::new())
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/customercode",
+    tag = "customer codes",
+    security(("device_key" = [])),
+    responses(
+        (status = 200, description = "The code currently shown on the display's store", body = CodeResponse),
+        (status = 401, description = "Missing, unknown or revoked device key", body = ErrorResponse)
+    )
+)]
+pub async fn get_code(data: AppData, PairedDevice(device): PairedDevice) -> HttpResponse {
+    info!("getting QR for store {}", device.store_id);
+
+    let mut codes = data.qr.lock().await;
+    let response = CodeResponse {
+        code: current_code(&mut codes, &device.store_id, &data.code_updates),
     };
 
-    if let Some(qrcode) = current_qr.as_ref() {
+    HttpResponse::Ok().json(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/customercode/stream",
+    tag = "customer codes",
+    security(("device_key" = [])),
+    params(("access_token" = Option<String>, Query, description = "The device key, for clients that cannot send headers")),
+    responses(
+        (status = 200, description = "Server-sent events carrying each new code for the display's store", content_type = "text/event-stream", body = CodeResponse),
+        (status = 401, description = "Missing, unknown or revoked device key", body = ErrorResponse)
+    )
+)]
+pub async fn stream_codes(data: AppData, PairedDevice(device): PairedDevice) -> HttpResponse {
+    info!("Display for store {} subscribed to code updates", device.store_id);
 
-        let response = CodeResponse {
-            code: qrcode.code.clone(),
-        };
+    // subscribe before reading the current code so a rotation in between is not missed
+    let updates = data.code_updates.subscribe();
+    let initial = {
+        let mut codes = data.qr.lock().await;
+        current_code(&mut codes, &device.store_id, &data.code_updates)
+    };
+
+    let store_id = device.store_id.clone();
+    let select = move |update: CodeUpdate| {
+        (update.store_id == store_id).then(|| sse::event(&CodeResponse { code: update.code }))
+    };
+    // a revoked display must stop getting codes, not just fail to reconnect
+    let allowed = move || {
+        let (data, device) = (data.clone(), device.clone());
+        async move { devices::still_paired(&data, &device).await }
+    };
+    sse::guarded_event_stream(sse::event(&CodeResponse { code: initial }), updates, select, allowed)
+}
+
+async fn record_block(data: &AppData, limit: &LimitExceeded, user_id: &UserId, ip: &str) {
+    let action = match limit {
+        LimitExceeded::StampTooSoon(..) => AuditAction::StampTooSoon,
+        _ => AuditAction::ClaimRateLimited
+    };
 
-        return HttpResponse::Ok().json(response)
+    let entry = AuditEntry::new(action, limit.to_string()).user(user_id).ip(ip);
+    if let Err(err) = data.audit.lock().await.record(entry).await {
+        warn!("Failed to write audit entry: {}", err);
     }
+}
 
-    HttpResponse::InternalServerError().finish()
+/// Audits the block after the limiter is released, so a slow write holds up nobody else's claim.
+pub async fn rate_limited(data: &AppData, limiter: MutexGuard<'_, ClaimLimiter>, limit: LimitExceeded, user_id: &UserId, ip: &str) -> ApiError {
+    let retry_after = limit.retry_after(&limiter.config);
+    drop(limiter);
+
+    record_block(data, &limit, user_id, ip).await;
+    ApiError::RateLimited {
+        retry_after,
+        reason: limit
+    }
+}
+
+/// Stamps the card for a claimed code, with any campaign and referral bonus, and gives back the card's key.
+async fn give_stamp(data: &AppData, card_id: &UserId, store_id: &StoreId, referral: Option<&str>) -> Result<String, ApiError> {
+    let programme_id = data.stores.lock().await.find_store(store_id).await?.and_then(|store| store.programme_id);
+    let campaigns = data.campaigns.lock().await.list_campaigns().await?;
+    let campaign = campaigns::best(&campaigns, DateTime::now(), store_id, programme_id.as_deref());
+    let stamps = campaign.map_or(1, Campaign::stamps);
+    // a referral only counts for a customer who has never had a card before
+    let referral = match referral {
+        Some(code) if data.activity.lock().await.for_card(card_id, 1).await?.is_empty() => Some(code),
+        _ => None
+    };
+
+    let mut tracker = data.cards.lock().await;
+    let previous = tracker.get_or_create_card(card_id).await?;
+    let card = match tracker.stamp_card(card_id, stamps, programme_id).await? {
+        card if card.card_key().is_some() => card,
+        card => {
+            let card = card.with_card_key(rand_string(CARD_KEY_LENGTH));
+            tracker.save_card(&card).await?;
+            card
+        }
+    };
+    // a nearly full card may take fewer than the campaign offered
+    let added = card.stamps as i32 - previous.stamps as i32;
+    let mut stamped = CardActivity::new(ActivityKind::Stamped, &card, added).store(store_id);
+    if let Some(campaign) = campaign {
+        stamped = stamped.campaign(&campaign.campaign_id);
+    }
+    activity::record(data, stamped).await;
+    _ = data.card_updates.send(CardNotification::new(card_id.clone(), CardEvent::Stamped, &card));
+    drop(tracker);
+
+    if let Some(code) = referral {
+        referrals::reward(data, card_id, code).await;
+    }
+    Ok(card.card_key().unwrap_or_default().to_string())
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/customercode/claim",
+    tag = "customer codes",
+    request_body = ClaimRequest,
+    responses(
+        (status = 200, description = "The card has been stamped", body = ClaimResponse),
+        (status = 400, description = "Invalid phone number or code", body = ErrorResponse),
+        (status = 429, description = "Too many attempts or the card was stamped too recently", body = ErrorResponse)
+    )
+)]
+pub async fn claim_code(claim: web::Json<ClaimRequest>, data: AppData, conn: ConnectionInfo) -> Result<HttpResponse, ApiError> {
 
+    // spaces and dashes are dropped so each way of typing a number shares one card and one set of limits
+    let card_id = PhoneNumber::try_from(claim.id.as_str())
+        .map(UserId::from)
+        .map_err(|message| ApiError::Validation(message.to_string()))?;
 
-pub async fn claim_code(claim: web::Json<ClaimRequest>, data: AppData) -> HttpResponse {
+    // every attempt counts towards the limits so guessing codes is as slow as claiming them
+    let mut limiter = data.limiter.lock().await;
+    let ip = limiter.config.client_address(&conn);
+    if let Err(limit) = limiter.check_attempt(&ip, &card_id) {
+        return Err(rate_limited(&data, limiter, limit, &card_id, &ip).await)
+    }
+    drop(limiter);
 
-    let card_id = UserId(claim.id.clone());
-    let mut current_qr = data.qr.lock().unwrap();
+    // the code is always locked before the limiter, and both are let go before the database is touched
+    let mut codes = data.qr.lock().await;
 
-    match &*current_qr {
-        Some(qrcode) => {
+    let store_id = codes.iter()
+        .find(|(_, active)| active.qr.code == claim.code && !active.is_stale())
+        .map(|(store_id, _)| store_id.clone());
 
-            if qrcode.code != claim.code {
-                warn!("Card '{}' tried to claim code '{}' but it does not match the current active code!", claim.id, claim.code);
-                return HttpResponse::BadRequest().body("Invalid code!")
-            }
+    let Some(store_id) = store_id
+        else {
+            warn!("Card '{}' tried to claim code '{}' but it does not match any active code!", card_id, claim.code);
+            return Err(ApiError::InvalidCode)
+        };
 
-            let mut tracker = data.cards.lock().unwrap();
-            _ = tracker.stamp_card(&card_id).await;
-            *current_qr = None;
+    // checked after the code so a customer who stamps too soon keeps the code for someone else
+    let mut limiter = data.limiter.lock().await;
+    if let Err(limit) = limiter.check_stamp_gap(&card_id) {
+        drop(codes);
+        return Err(rate_limited(&data, limiter, limit, &card_id, &ip).await)
+    }
+    // recorded now so a second claim for the card is refused while this one talks to the database
+    limiter.record_stamp(&card_id);
+    drop(limiter);
+    rotate_code(&mut codes, &store_id, &data.code_updates);
+    drop(codes);
 
-            info!("Card '{}' has claimed code ''{}'", claim.id, claim.code);
-            return HttpResponse::Ok().finish()
+    let card_key = match give_stamp(&data, &card_id, &store_id, claim.referral.as_deref()).await {
+        Ok(card_key) => card_key,
+        Err(err) => {
+            data.limiter.lock().await.forget_stamp(&card_id);
+            return Err(err)
         }
-        None => {
-            warn!("Card '{}' tried to claim code '{}' but it there is no active qr code!", claim.id, claim.code);
-            HttpResponse::BadRequest().body("Invalid code!")
+    };
+
+    info!("Card '{}' has claimed code '{}' at store {}", card_id, claim.code, store_id);
+    Ok(HttpResponse::Ok().json(ClaimResponse { card_key }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::http::{header, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+
+    use super::*;
+
+    fn claim(id: &str) -> TestRequest {
+        TestRequest::post().uri("/api/customercode/claim")
+            .set_json(ClaimRequest { id: id.to_string(), code: String::from("nope"), referral: None })
+    }
+
+    #[actix_web::test]
+    async fn guessing_codes_is_rate_limited_per_card() {
+        let data = crate::test_state(None);
+        let per_user = data.limiter.lock().await.config.per_user;
+        let app = init_service(App::new().app_data(data.clone()).service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        // spacing the number out differently is still the same card
+        for attempt in 0..per_user {
+            let id = if attempt % 2 == 0 { "07700900001" } else { "07700 900 001" };
+            assert_eq!(call_service(&app, claim(id).to_request()).await.status(), StatusCode::BAD_REQUEST);
         }
+        let blocked = call_service(&app, claim("07700-900-001").to_request()).await;
+        assert_eq!(blocked.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(blocked.headers().contains_key(header::RETRY_AFTER));
+
+        // another card from a different address is unaffected, and the limiter was not left locked
+        let other = claim("07700900002").peer_addr("10.0.0.2:1234".parse().unwrap()).to_request();
+        assert_eq!(call_service(&app, other).await.status(), StatusCode::BAD_REQUEST);
+        assert!(data.limiter.try_lock().is_ok());
+    }
+
+    #[actix_web::test]
+    async fn forwarded_addresses_are_only_believed_from_the_trusted_proxy() {
+        let data = crate::test_state(None);
+        let per_ip = data.limiter.lock().await.config.per_ip;
+        let app = init_service(App::new().app_data(data.clone()).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let from = |card: usize, forwarded: &str| claim(&format!("077009{:05}", card))
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded))
+            .to_request();
+
+        // a different made up address on every attempt still counts against the connection's own
+        for card in 0..per_ip {
+            assert_eq!(call_service(&app, from(card, &format!("192.0.2.{}", card))).await.status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(call_service(&app, from(per_ip, "192.0.2.250")).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        data.limiter.lock().await.config.trusted_proxy = Some(String::from("10.0.0.1"));
+        assert_eq!(call_service(&app, from(per_ip + 1, "192.0.2.251")).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn claims_need_a_phone_number() {
+        let app = init_service(App::new().app_data(crate::test_state(None)).service(Scope::new("/api").configure(crate::api_routes))).await;
+        assert_eq!(call_service(&app, claim("not a number").to_request()).await.status(), StatusCode::BAD_REQUEST);
     }
 }
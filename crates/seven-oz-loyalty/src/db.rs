@@ -1,23 +1,37 @@
 use log::info;
-use mongodb::bson::doc;
+use mongodb::bson::{doc, DateTime, Document};
 use mongodb::Collection;
+use mongodb::options::{FindOptions, ReplaceOptions};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use thiserror::Error;
-use loyalty_core::{UserId};
+use loyalty_core::api::v1::{StampSlot, ThemeDetails};
+use loyalty_core::card::LoyaltyCard;
+use loyalty_core::{StoreId, UserId};
+use crate::activity::CardActivity;
+use crate::campaigns::Campaign;
+use crate::audit::AuditEntry;
+use crate::devices::Device;
+use crate::programmes::{Programme, Store};
+use crate::referrals::{referred_hash, Referral};
+use crate::stamp_images::StampImage;
 use crate::stampcard::BasicStampCard;
 
-pub struct MongoDbStampCardRepository {
-    pub collection: Collection<BasicStampCard> // TODO should this be public?
+/// Every kind of card is kept the same way, one per customer keyed by their user id.
+pub struct MongoDbCardRepository<C> {
+    pub collection: Collection<C> // TODO should this be public?
 }
 
+pub type MongoDbStampCardRepository = MongoDbCardRepository<BasicStampCard>;
+
 #[derive(Debug, Error)]
 pub enum StampCardRepositoryError {
     #[error("mongodb error")]
     MongoDbError(#[from] mongodb::error::Error)
 }
 
-
-impl MongoDbStampCardRepository {
-    pub async fn get_or_create_card(&mut self, user_id: &UserId) -> Result<BasicStampCard, StampCardRepositoryError> {
+impl<C> MongoDbCardRepository<C> where C: LoyaltyCard + Serialize + DeserializeOwned + Unpin + Send + Sync {
+    pub async fn get_or_create_card(&mut self, user_id: &UserId) -> Result<C, StampCardRepositoryError> {
         info!("Searching for card with user_id {}", user_id);
         let filter = doc! {
             "user_id": user_id.to_string()
@@ -33,17 +47,53 @@ impl MongoDbStampCardRepository {
             },
             None => {
                 info!("Creating new card for user_id {}", user_id);
-                let new_card = BasicStampCard::new(user_id.clone());
+                let new_card = C::new(user_id.clone());
                 self.collection.insert_one(&new_card, None).await?;
                 Ok(new_card)
             }
         }
     }
 
+    pub async fn find_card(&mut self, user_id: &UserId) -> Result<Option<C>, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn delete_card(&mut self, user_id: &UserId) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        let result = self.collection.delete_one(filter, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    /// Creates the card or replaces it outright, used when importing.
+    pub async fn save_card(&mut self, card: &C) -> Result<(), StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": card.user_id().to_string()
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(filter, card, options).await?;
+        Ok(())
+    }
+
+    pub async fn list_cards(&mut self) -> Result<Vec<C>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(None, None).await?;
+        let mut cards = Vec::new();
+        while cursor.advance().await? {
+            cards.push(cursor.deserialize_current()?);
+        }
+        Ok(cards)
+    }
+}
+
+impl MongoDbStampCardRepository {
     // TODO Command Query Separation
-    pub async fn stamp_card(&mut self, user_id: &UserId) -> Result<BasicStampCard, StampCardRepositoryError> {
+    pub async fn stamp_card(&mut self, user_id: &UserId, stamps: u32, programme_id: Option<String>) -> Result<BasicStampCard, StampCardRepositoryError> {
         let user_card = self.get_or_create_card(user_id).await?;
-        let stamped_card = user_card.with_stamp();
+        let stamped_card = user_card.with_stamps(stamps, programme_id);
         let filter = doc! {
             "user_id": user_id.to_string() // todo extract method
         };
@@ -54,8 +104,28 @@ impl MongoDbStampCardRepository {
         Ok(stamped_card)
     }
 
-    pub async fn reset_card(&mut self, user_id: &UserId) -> Result<(), StampCardRepositoryError> {
-        let new_card = BasicStampCard::new(user_id.clone());
+    pub async fn find_by_referral_code(&mut self, code: &str) -> Result<Option<BasicStampCard>, StampCardRepositoryError> {
+        let filter = doc! {
+            "referral_code": code
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn adjust_card(&mut self, user_id: &UserId, delta: i32) -> Result<BasicStampCard, StampCardRepositoryError> {
+        let user_card = self.get_or_create_card(user_id).await?;
+        let adjusted_card = user_card.with_adjustment(delta);
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+
+        self.collection.replace_one(filter, &adjusted_card, None).await?;
+
+        info!("Card for user_id {} adjusted by {} to {} stamps", user_id, delta, adjusted_card.stamps);
+        Ok(adjusted_card)
+    }
+
+    pub async fn reset_card(&mut self, user_id: &UserId) -> Result<BasicStampCard, StampCardRepositoryError> {
+        let new_card = self.get_or_create_card(user_id).await?.emptied();
         let filter = doc! {
             "user_id": user_id.to_string() // todo extract method
         };
@@ -63,6 +133,383 @@ impl MongoDbStampCardRepository {
         self.collection.replace_one(filter, &new_card, None).await?;
 
         info!("Card for user_id {} has been reset", user_id);
+        Ok(new_card)
+    }
+}
+
+pub struct MongoDbDeviceRepository {
+    pub collection: Collection<Device>
+}
+
+impl MongoDbDeviceRepository {
+    pub async fn add_device(&mut self, device: &Device) -> Result<(), StampCardRepositoryError> {
+        self.collection.insert_one(device, None).await?;
+        info!("Device {} has been paired with store {}", device.device_id, device.store_id);
+        Ok(())
+    }
+
+    pub async fn find_active_device(&mut self, key_hash: &str) -> Result<Option<Device>, StampCardRepositoryError> {
+        let filter = doc! {
+            "key_hash": key_hash,
+            "revoked": false
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn list_devices(&mut self) -> Result<Vec<Device>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(None, None).await?;
+        let mut devices = Vec::new();
+        while cursor.advance().await? {
+            devices.push(cursor.deserialize_current()?);
+        }
+        Ok(devices)
+    }
+
+    pub async fn revoke_device(&mut self, device_id: &str) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "device_id": device_id
+        };
+        let update = doc! {
+            "$set": { "revoked": true }
+        };
+        let result = self.collection.update_one(filter, update, None).await?;
+
+        info!("Device {} has been revoked", device_id);
+        Ok(result.matched_count > 0)
+    }
+}
+
+pub struct MongoDbAuditRepository {
+    pub collection: Collection<AuditEntry>
+}
+
+impl MongoDbAuditRepository {
+    pub async fn record(&mut self, entry: AuditEntry) -> Result<(), StampCardRepositoryError> {
+        info!("Audit {:?}: {}", entry.action, entry.detail);
+        self.collection.insert_one(&entry, None).await?;
+        Ok(())
+    }
+
+    pub async fn for_user(&mut self, user_id: &UserId) -> Result<Vec<AuditEntry>, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        let mut cursor = self.collection.find(filter, None).await?;
+        let mut entries = Vec::new();
+        while cursor.advance().await? {
+            entries.push(cursor.deserialize_current()?);
+        }
+        Ok(entries)
+    }
+
+    /// Keeps the entries for security review but strips the card id, including where the detail mentions it.
+    pub async fn anonymise(&mut self, user_id: &UserId) -> Result<u64, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        let update = vec![doc! {
+            "$set": {
+                "user_id": null,
+                "ip": null,
+                "detail": { "$replaceAll": { "input": "$detail", "find": user_id.to_string(), "replacement": "[erased]" } }
+            }
+        }];
+        let result = self.collection.update_many(filter, update, None).await?;
+        Ok(result.modified_count)
+    }
+}
+
+pub struct MongoDbActivityRepository {
+    pub collection: Collection<CardActivity>
+}
+
+impl MongoDbActivityRepository {
+    pub async fn record(&mut self, activity: &CardActivity) -> Result<(), StampCardRepositoryError> {
+        self.collection.insert_one(activity, None).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn record_many(&mut self, activity: &[CardActivity]) -> Result<(), StampCardRepositoryError> {
+        if !activity.is_empty() {
+            self.collection.insert_many(activity, None).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn recent(&mut self, limit: i64) -> Result<Vec<CardActivity>, StampCardRepositoryError> {
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .limit(limit)
+            .build();
+        self.find(None, options).await
+    }
+
+    pub async fn for_card(&mut self, user_id: &UserId, limit: i64) -> Result<Vec<CardActivity>, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .limit(limit)
+            .build();
+        self.find(Some(filter), options).await
+    }
+
+    /// Moves a card's activity onto a pseudonym so totals still add up but nobody can be identified.
+    pub async fn anonymise(&mut self, user_id: &UserId, pseudonym: &UserId) -> Result<u64, StampCardRepositoryError> {
+        let filter = doc! {
+            "user_id": user_id.to_string()
+        };
+        // staff write reasons by hand so they may name the customer
+        let update = doc! {
+            "$set": { "user_id": pseudonym.to_string(), "reason": null }
+        };
+        let result = self.collection.update_many(filter, update, None).await?;
+        Ok(result.modified_count)
+    }
+
+    /// Everything from `from` up to but not including `until`, oldest first.
+    pub async fn between(&mut self, from: Option<DateTime>, until: Option<DateTime>) -> Result<Vec<CardActivity>, StampCardRepositoryError> {
+        let mut timestamp = Document::new();
+        if let Some(from) = from {
+            timestamp.insert("$gte", from);
+        }
+        if let Some(until) = until {
+            timestamp.insert("$lt", until);
+        }
+        let filter = (!timestamp.is_empty()).then(|| doc! { "timestamp": timestamp });
+
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": 1 })
+            .build();
+        self.find(filter, options).await
+    }
+
+    async fn find(&mut self, filter: Option<Document>, options: FindOptions) -> Result<Vec<CardActivity>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(filter, options).await?;
+        let mut activity = Vec::new();
+        while cursor.advance().await? {
+            activity.push(cursor.deserialize_current()?);
+        }
+        Ok(activity)
+    }
+}
+
+pub struct MongoDbProgrammeRepository {
+    pub collection: Collection<Programme>
+}
+
+impl MongoDbProgrammeRepository {
+    pub async fn list_programmes(&mut self) -> Result<Vec<Programme>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(None, None).await?;
+        let mut programmes = Vec::new();
+        while cursor.advance().await? {
+            programmes.push(cursor.deserialize_current()?);
+        }
+        Ok(programmes)
+    }
+
+    pub async fn find_programme(&mut self, programme_id: &str) -> Result<Option<Programme>, StampCardRepositoryError> {
+        let filter = doc! {
+            "programme_id": programme_id
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn save_programme(&mut self, programme: &Programme) -> Result<(), StampCardRepositoryError> {
+        let filter = doc! {
+            "programme_id": &programme.programme_id
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(filter, programme, options).await?;
+        Ok(())
+    }
+
+    pub async fn delete_programme(&mut self, programme_id: &str) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "programme_id": programme_id
+        };
+        let result = self.collection.delete_one(filter, None).await?;
+
+        info!("Programme {} has been deleted", programme_id);
+        Ok(result.deleted_count > 0)
+    }
+}
+
+pub struct MongoDbStoreRepository {
+    pub collection: Collection<Store>
+}
+
+impl MongoDbStoreRepository {
+    pub async fn list_stores(&mut self) -> Result<Vec<Store>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(None, None).await?;
+        let mut stores = Vec::new();
+        while cursor.advance().await? {
+            stores.push(cursor.deserialize_current()?);
+        }
+        Ok(stores)
+    }
+
+    pub async fn find_store(&mut self, store_id: &StoreId) -> Result<Option<Store>, StampCardRepositoryError> {
+        let filter = doc! {
+            "store_id": store_id.to_string()
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn save_store(&mut self, store: &Store) -> Result<(), StampCardRepositoryError> {
+        let filter = doc! {
+            "store_id": store.store_id.to_string()
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(filter, store, options).await?;
+        Ok(())
+    }
+
+    pub async fn delete_store(&mut self, store_id: &StoreId) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "store_id": store_id.to_string()
+        };
+        let result = self.collection.delete_one(filter, None).await?;
+
+        info!("Store {} has been deleted", store_id);
+        Ok(result.deleted_count > 0)
+    }
+}
+
+pub struct MongoDbCampaignRepository {
+    pub collection: Collection<Campaign>
+}
+
+impl MongoDbCampaignRepository {
+    pub async fn list_campaigns(&mut self) -> Result<Vec<Campaign>, StampCardRepositoryError> {
+        let mut cursor = self.collection.find(None, None).await?;
+        let mut campaigns = Vec::new();
+        while cursor.advance().await? {
+            campaigns.push(cursor.deserialize_current()?);
+        }
+        Ok(campaigns)
+    }
+
+    pub async fn save_campaign(&mut self, campaign: &Campaign) -> Result<(), StampCardRepositoryError> {
+        let filter = doc! {
+            "campaign_id": &campaign.campaign_id
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(filter, campaign, options).await?;
+        Ok(())
+    }
+
+    pub async fn delete_campaign(&mut self, campaign_id: &str) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "campaign_id": campaign_id
+        };
+        let result = self.collection.delete_one(filter, None).await?;
+
+        info!("Campaign {} has been deleted", campaign_id);
+        Ok(result.deleted_count > 0)
+    }
+}
+
+pub struct MongoDbReferralRepository {
+    pub collection: Collection<Referral>
+}
+
+impl MongoDbReferralRepository {
+    pub async fn record(&mut self, referral: &Referral) -> Result<(), StampCardRepositoryError> {
+        self.collection.insert_one(referral, None).await?;
+        Ok(())
+    }
+
+    /// Whether the number has ever been referred, even if that customer has since been erased.
+    pub async fn was_referred(&mut self, user_id: &UserId) -> Result<bool, StampCardRepositoryError> {
+        let filter = doc! {
+            "$or": [{ "referred": user_id.to_string() }, { "referred_hash": referred_hash(user_id) }]
+        };
+        Ok(self.collection.count_documents(filter, None).await? > 0)
+    }
+
+    pub async fn recent(&mut self, limit: i64) -> Result<Vec<Referral>, StampCardRepositoryError> {
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .limit(limit)
+            .build();
+        let mut cursor = self.collection.find(None, options).await?;
+        let mut referrals = Vec::new();
+        while cursor.advance().await? {
+            referrals.push(cursor.deserialize_current()?);
+        }
+        Ok(referrals)
+    }
+
+    /// Every referral the customer made or was brought in by, newest first.
+    pub async fn for_user(&mut self, user_id: &UserId) -> Result<Vec<Referral>, StampCardRepositoryError> {
+        let filter = doc! {
+            "$or": [{ "referrer": user_id.to_string() }, { "referred": user_id.to_string() }]
+        };
+        let options = FindOptions::builder()
+            .sort(doc! { "timestamp": -1 })
+            .build();
+        let mut cursor = self.collection.find(filter, options).await?;
+        let mut referrals = Vec::new();
+        while cursor.advance().await? {
+            referrals.push(cursor.deserialize_current()?);
+        }
+        Ok(referrals)
+    }
+
+    /// Swaps the customer for a pseudonym on either side of a referral, keeping the hash of a referred
+    /// number, set here for referrals from before it was kept, so they cannot be referred again.
+    pub async fn anonymise(&mut self, user_id: &UserId, pseudonym: &UserId) -> Result<u64, StampCardRepositoryError> {
+        let referrer = self.collection.update_many(
+            doc! { "referrer": user_id.to_string() },
+            doc! { "$set": { "referrer": pseudonym.to_string() } },
+            None
+        ).await?;
+        let referred = self.collection.update_many(
+            doc! { "referred": user_id.to_string() },
+            doc! { "$set": { "referred": pseudonym.to_string(), "referred_hash": referred_hash(user_id) } },
+            None
+        ).await?;
+        Ok(referrer.modified_count + referred.modified_count)
+    }
+}
+
+/// The tenant's theme, the only document in its collection.
+pub struct MongoDbThemeRepository {
+    pub collection: Collection<ThemeDetails>
+}
+
+impl MongoDbThemeRepository {
+    pub async fn find_theme(&mut self) -> Result<Option<ThemeDetails>, StampCardRepositoryError> {
+        Ok(self.collection.find_one(None, None).await?)
+    }
+
+    pub async fn save_theme(&mut self, theme: &ThemeDetails) -> Result<(), StampCardRepositoryError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(doc! {}, theme, options).await?;
+        Ok(())
+    }
+}
+
+pub struct MongoDbStampImageRepository {
+    pub collection: Collection<StampImage>
+}
+
+impl MongoDbStampImageRepository {
+    pub async fn find_image(&mut self, slot: StampSlot) -> Result<Option<StampImage>, StampCardRepositoryError> {
+        let filter = doc! {
+            "slot": slot.as_str()
+        };
+        Ok(self.collection.find_one(filter, None).await?)
+    }
+
+    pub async fn save_image(&mut self, image: &StampImage) -> Result<(), StampCardRepositoryError> {
+        let filter = doc! {
+            "slot": image.slot.as_str()
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.collection.replace_one(filter, image, options).await?;
+        Ok(())
+    }
+}
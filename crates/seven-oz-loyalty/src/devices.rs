@@ -0,0 +1,241 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, web};
+use log::{info, warn};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use loyalty_core::api::v1::{DeviceResponse, ErrorResponse, PairingCodeRequest, PairingCodeResponse, PairRequest, PairResponse};
+use loyalty_core::qr_gen::rand_string;
+use loyalty_core::StoreId;
+
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+
+const PAIRING_CODE_LENGTH: usize = 6;
+const PAIRING_CODE_TTL: Duration = Duration::from_secs(10 * 60);
+const DEVICE_KEY_LENGTH: usize = 40;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Device {
+    pub device_id: String,
+    pub store_id: StoreId,
+    key_hash: String, // only the hash is stored so a leaked database cannot be used to impersonate a display
+    created: DateTime,
+    revoked: bool,
+}
+
+/// A pairing code that has been issued by the owner but not yet entered on a display.
+pub struct PendingPairing {
+    store_id: StoreId,
+    expires: Instant,
+}
+
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// Whether the device is still paired, for requests that outlive the check made when they started.
+/// A failed lookup counts as paired so a database blip does not blank every display.
+pub async fn still_paired(data: &AppData, device: &Device) -> bool {
+    match data.devices.lock().await.find_active_device(&device.key_hash).await {
+        Ok(found) => found.is_some(),
+        Err(err) => {
+            warn!("Failed to check device {} is still paired: {}", device.device_id, err);
+            true
+        }
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    post,
+    path = "/api/admin/pairing",
+    tag = "devices",
+    security(("admin_key" = [])),
+    request_body = PairingCodeRequest,
+    responses(
+        (status = 200, description = "A single use code to enter on the display", body = PairingCodeResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn create_pairing_code(_: Admin, request: web::Json<PairingCodeRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    if request.store_id.trim().is_empty() {
+        return Err(ApiError::Validation(String::from("A store id is required")))
+    }
+
+    let store_id = StoreId(request.store_id.clone());
+    let code = rand_string(PAIRING_CODE_LENGTH).to_uppercase();
+
+    let mut pairing = data.pairing.lock().await;
+    pairing.retain(|_, pending| pending.expires > Instant::now());
+    pairing.insert(code.clone(), PendingPairing {
+        store_id: store_id.clone(),
+        expires: Instant::now() + PAIRING_CODE_TTL
+    });
+
+    info!("Issued pairing code for store {}", store_id);
+    Ok(HttpResponse::Ok().json(PairingCodeResponse {
+        code,
+        expires_in: PAIRING_CODE_TTL.as_secs()
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/devices/pair",
+    tag = "devices",
+    request_body = PairRequest,
+    responses(
+        (status = 200, description = "The display's long lived device key", body = PairResponse),
+        (status = 400, description = "Unknown or expired pairing code", body = ErrorResponse)
+    )
+)]
+pub async fn pair_device(request: web::Json<PairRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let code = request.code.trim().to_uppercase();
+
+    // pairing codes are single use so take it out of the pending list straight away
+    let pending = data.pairing.lock().await.remove(&code);
+    let Some(pending) = pending.filter(|p| p.expires > Instant::now())
+        else {
+            warn!("Attempt to pair with unknown or expired code '{}'", code);
+            return Err(ApiError::InvalidPairingCode)
+        };
+
+    let device_key = rand_string(DEVICE_KEY_LENGTH);
+    let device = Device {
+        device_id: rand_string(12),
+        store_id: pending.store_id,
+        key_hash: hash_key(&device_key),
+        created: DateTime::now(),
+        revoked: false
+    };
+
+    let mut devices = data.devices.lock().await;
+    devices.add_device(&device).await?;
+
+    Ok(HttpResponse::Ok().json(PairResponse {
+        device_key,
+        store_id: device.store_id
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/devices",
+    tag = "devices",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Every paired display", body = Vec<DeviceResponse>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_devices(_: Admin, data: AppData) -> Result<HttpResponse, ApiError> {
+    let devices = data.devices.lock().await.list_devices().await?;
+
+    let response: Vec<DeviceResponse> = devices.into_iter()
+        .map(|device| DeviceResponse {
+            device_id: device.device_id,
+            store_id: device.store_id,
+            created: device.created.try_to_rfc3339_string().unwrap_or_default(),
+            revoked: device.revoked
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/devices/{device_id}/revoke",
+    tag = "devices",
+    security(("admin_key" = [])),
+    params(("device_id" = String, Path, description = "The display to revoke")),
+    responses(
+        (status = 200, description = "The display's key no longer works"),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such display", body = ErrorResponse)
+    )
+)]
+pub async fn revoke_device(_: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let device_id = path.into_inner();
+
+    let mut devices = data.devices.lock().await;
+    match devices.revoke_device(&device_id).await? {
+        true => Ok(HttpResponse::Ok().finish()),
+        false => Err(ApiError::NotFound("Device"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::http::{header, StatusCode};
+
+    use super::*;
+
+    fn pair_request(code: &str) -> TestRequest {
+        TestRequest::post().uri("/api/devices/pair").set_json(PairRequest { code: code.to_string() })
+    }
+
+    #[test]
+    fn only_the_hash_of_a_device_key_is_kept() {
+        let key = rand_string(DEVICE_KEY_LENGTH);
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_ne!(hash_key(&key), key);
+        assert_ne!(hash_key(&key), hash_key(&rand_string(DEVICE_KEY_LENGTH)));
+
+        let device = Device {
+            device_id: String::from("d1"),
+            store_id: StoreId(String::from("high-street")),
+            key_hash: hash_key(&key),
+            created: DateTime::now(),
+            revoked: false
+        };
+        assert!(!serde_json::to_string(&device).unwrap().contains(&key));
+    }
+
+    #[actix_web::test]
+    async fn pairing_codes_are_single_use() {
+        let data = crate::test_state(Some("admin"));
+        let app = init_service(App::new().app_data(data.clone()).service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        let request = TestRequest::post().uri("/api/admin/pairing")
+            .insert_header((header::AUTHORIZATION, "Bearer admin"))
+            .set_json(PairingCodeRequest { store_id: String::from("high-street") })
+            .to_request();
+        let issued: PairingCodeResponse = call_and_read_body_json(&app, request).await;
+        assert_eq!((issued.code.len(), issued.expires_in), (PAIRING_CODE_LENGTH, PAIRING_CODE_TTL.as_secs()));
+
+        // the first attempt takes the code, even though saving the device fails without a database
+        let first = call_service(&app, pair_request(&issued.code.to_lowercase()).to_request()).await;
+        assert_eq!(first.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let second = call_service(&app, pair_request(&issued.code).to_request()).await;
+        assert_eq!(second.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn expired_pairing_codes_are_refused() {
+        let data = crate::test_state(Some("admin"));
+        data.pairing.lock().await.insert(String::from("ABC123"), PendingPairing {
+            store_id: StoreId(String::from("high-street")),
+            expires: Instant::now() - Duration::from_secs(1)
+        });
+        let app = init_service(App::new().app_data(data.clone()).service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        assert_eq!(call_service(&app, pair_request("ABC123").to_request()).await.status(), StatusCode::BAD_REQUEST);
+        assert!(data.pairing.lock().await.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn only_the_owner_can_issue_pairing_codes() {
+        let app = init_service(App::new().app_data(crate::test_state(Some("admin"))).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::post().uri("/api/admin/pairing")
+            .set_json(PairingCodeRequest { store_id: String::from("high-street") })
+            .to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::{header, StatusCode};
+use log::error;
+use thiserror::Error;
+
+use loyalty_core::api::v1::ErrorResponse;
+
+use crate::db::StampCardRepositoryError;
+use crate::rate_limit::LimitExceeded;
+
+/// Every failure an api handler can return, rendered as a JSON body with a stable `code`.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("That code is not valid, please scan the QR code again")]
+    InvalidCode,
+    #[error("That pairing code is invalid or has expired")]
+    InvalidPairingCode,
+    #[error("{0}")]
+    Unauthorized(&'static str),
+    #[error("Your request has not been approved yet")]
+    NotApproved,
+    #[error("{0} not found")]
+    NotFound(&'static str),
+    #[error("{reason}")]
+    RateLimited { reason: LimitExceeded, retry_after: Duration },
+    #[error("Something went wrong, please try again")]
+    Repository(#[from] StampCardRepositoryError),
+    #[error("Something went wrong, please try again")]
+    Internal(&'static str),
+    #[error("{0}")]
+    ImportIncomplete(String),
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::InvalidCode => "invalid_code",
+            ApiError::InvalidPairingCode => "invalid_pairing_code",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::NotApproved => "not_approved",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::RateLimited { reason: LimitExceeded::StampTooSoon(..), .. } => "stamp_too_soon",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::Repository(_) | ApiError::Internal(_) => "internal_error",
+            ApiError::ImportIncomplete(_) => "import_incomplete",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            // the limiter's reason names the caller so give them something friendlier
+            ApiError::RateLimited { reason: LimitExceeded::StampTooSoon(..), retry_after } =>
+                format!("You have just collected a stamp, please wait {} minutes", retry_after.as_secs().div_ceil(60)),
+            ApiError::RateLimited { .. } => String::from("Too many attempts, please wait a moment and try again"),
+            _ => self.to_string()
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::Validation(_) | ApiError::InvalidCode | ApiError::InvalidPairingCode => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::NotApproved => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Repository(_) | ApiError::Internal(_) | ApiError::ImportIncomplete(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            ApiError::Repository(err) => error!("Repository error: {:?}", err),
+            ApiError::Internal(detail) => error!("Internal error: {}", detail),
+            _ => {}
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+        if let ApiError::RateLimited { retry_after, .. } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after.as_secs().max(1)));
+        }
+
+        response.json(ErrorResponse {
+            code: self.code().to_string(),
+            message: self.message()
+        })
+    }
+}
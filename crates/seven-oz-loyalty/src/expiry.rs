@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::Months;
+use log::{error, info};
+use mongodb::bson::DateTime;
+use tokio::time::interval;
+
+use loyalty_core::api::v1::{ActivityKind, CardEvent, ExpiryPolicy, StampExpiry};
+
+use crate::activity::{self, CardActivity};
+use crate::AppData;
+use crate::db::StampCardRepositoryError;
+use crate::stampcard::CardNotification;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How far ahead the card api warns about stamps that are going to expire.
+const WARNING_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The same time `months` calendar months later, falling back to the end of shorter months.
+pub fn add_months(date: DateTime, months: u32) -> DateTime {
+    chrono::DateTime::from_timestamp_millis(date.timestamp_millis())
+        .and_then(|date| date.checked_add_months(Months::new(months)))
+        .map(|later| DateTime::from_millis(later.timestamp_millis()))
+        .unwrap_or(DateTime::MAX)
+}
+
+/// Groups the expiry dates falling within the warning period, soonest first.
+pub fn upcoming(expiries: &[DateTime], now: DateTime) -> Vec<StampExpiry> {
+    let cutoff = DateTime::from_millis(now.timestamp_millis() + WARNING_PERIOD.as_millis() as i64);
+
+    let mut upcoming: Vec<(DateTime, u32)> = Vec::new();
+    for expires in expiries.iter().filter(|expires| **expires <= cutoff) {
+        match upcoming.last_mut() {
+            Some((last, stamps)) if last == expires => *stamps += 1,
+            _ => upcoming.push((*expires, 1))
+        }
+    }
+
+    upcoming.into_iter()
+        .map(|(expires, stamps)| StampExpiry { stamps, expires: expires.try_to_rfc3339_string().unwrap_or_default() })
+        .collect()
+}
+
+/// Background job that takes expired stamps off cards under their programme's policy.
+pub async fn expire_stamps(data: AppData) {
+    let mut ticker = interval(SWEEP_INTERVAL);
+    loop {
+        ticker.tick().await;
+
+        if let Err(err) = sweep(&data).await {
+            error!("Stamp expiry sweep failed: {:?}", err);
+        }
+    }
+}
+
+async fn sweep(data: &AppData) -> Result<(), StampCardRepositoryError> {
+    let policies: HashMap<String, ExpiryPolicy> = data.programmes.lock().await.list_programmes().await?
+        .into_iter()
+        .filter_map(|programme| Some((programme.programme_id, programme.expiry?)))
+        .collect();
+
+    let now = DateTime::now();
+    // the cards are only locked one at a time so claims are not held up for the whole sweep
+    let cards = data.cards.lock().await.list_cards().await?;
+    for listed in cards {
+        let mut tracker = data.cards.lock().await;
+        // read again as it may have been stamped, redeemed or erased since it was listed
+        let Some(card) = tracker.find_card(listed.user_id()).await? else { continue };
+        let dated = card.dated(now);
+        let policy = card.programme_id.as_ref().and_then(|programme_id| policies.get(programme_id));
+        let kept = match policy {
+            Some(policy) => dated.without_expired(policy, now),
+            None => dated
+        };
+
+        let expired = card.stamps - kept.stamps;
+        if expired == 0 && card.is_dated() {
+            continue
+        }
+
+        tracker.save_card(&kept).await?;
+        drop(tracker);
+        if expired > 0 {
+            info!("{} stamps expired on card {}", expired, kept.user_id());
+            activity::record(data, CardActivity::new(ActivityKind::Expired, &kept, -(expired as i32))).await;
+            _ = data.card_updates.send(CardNotification::new(kept.user_id().clone(), CardEvent::Expired, &kept));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use loyalty_core::UserId;
+
+    use crate::stampcard::BasicStampCard;
+
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60 * 1000;
+
+    fn later(date: DateTime, days: i64) -> DateTime {
+        DateTime::from_millis(date.timestamp_millis() + days * DAY)
+    }
+
+    #[test]
+    fn months_are_calendar_months() {
+        let date = DateTime::parse_rfc3339_str("2024-01-31T09:00:00Z").unwrap();
+        assert_eq!(add_months(date, 1).try_to_rfc3339_string().unwrap(), "2024-02-29T09:00:00Z");
+        assert_eq!(add_months(date, 12).try_to_rfc3339_string().unwrap(), "2025-01-31T09:00:00Z");
+    }
+
+    #[test]
+    fn stamps_expire_oldest_first() {
+        let card = BasicStampCard::new(UserId("07715559999".into())).with_adjustment(2);
+        let policy = ExpiryPolicy::StampAge { months: 6 };
+        let now = DateTime::now();
+
+        assert_eq!(card.without_expired(&policy, later(now, 30)).stamps, 2);
+        let expired = card.without_expired(&policy, later(now, 190));
+        assert_eq!(expired.stamps, 0);
+        assert!(expired.is_dated());
+    }
+
+    #[test]
+    fn inactivity_empties_the_whole_card() {
+        let card = BasicStampCard::new(UserId("07715559999".into())).with_adjustment(3);
+        let policy = ExpiryPolicy::Inactivity { months: 1 };
+        let now = DateTime::now();
+
+        assert_eq!(card.without_expired(&policy, later(now, 20)).stamps, 3);
+        assert_eq!(card.without_expired(&policy, later(now, 40)).stamps, 0);
+    }
+
+    #[test]
+    fn only_expiries_inside_the_warning_period_are_reported() {
+        let now = DateTime::parse_rfc3339_str("2024-03-01T09:00:00Z").unwrap();
+        let soon = later(now, 10);
+        let expiries = vec![soon, soon, later(now, 12), later(now, 60)];
+
+        assert_eq!(upcoming(&expiries, now), vec![
+            StampExpiry { stamps: 2, expires: "2024-03-11T09:00:00Z".into() },
+            StampExpiry { stamps: 1, expires: "2024-03-13T09:00:00Z".into() },
+        ]);
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use log::{error, info};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ActivityKind, ActivityResponse, CardKind, CardRecord, ErrorResponse, ImportSummary};
+use loyalty_core::card::LoyaltyCard;
+use loyalty_core::{PhoneNumber, UserId};
+
+use crate::activity::CardActivity;
+use crate::analytics::{respond, ReportFormat};
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use crate::points::PointsCard;
+use crate::referrals;
+use crate::stampcard::{BasicStampCard, DEFAULT_CAPACITY, MAX_CAPACITY};
+
+/// Imports are read in one go so allow far more than a normal request body.
+pub const IMPORT_LIMIT: usize = 16 * 1024 * 1024;
+// only the first few problems are reported so the error stays readable
+const MAX_REPORTED_ERRORS: usize = 10;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ReportFormat
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStrategy {
+    /// Cards take the imported stamp count and counters, which suits restoring a backup.
+    /// Anything the file has nowhere to carry, such as when each stamp was given in a CSV row, is kept.
+    #[default]
+    Replace,
+    /// Imported stamps are added to any existing card, which suits paper cards.
+    Add
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    strategy: ImportStrategy
+}
+
+/// A card as a CSV row, which has nowhere to put the history.
+#[derive(Debug, Serialize, Deserialize)]
+struct CardRow {
+    user_id: String,
+    stamps: u32,
+    capacity: Option<u32>,
+    #[serde(default)]
+    kind: CardKind,
+    #[serde(default)]
+    referral_code: Option<String>
+}
+
+/// A checked card ready to be saved over any the customer already has.
+#[derive(Debug)]
+enum ImportedCard {
+    Stamps(BasicStampCard),
+    Points(PointsCard)
+}
+
+fn parse_date(value: &str) -> Result<DateTime, String> {
+    DateTime::parse_rfc3339_str(value.trim()).map_err(|_| format!("{} is not an RFC 3339 date", value))
+}
+
+fn read_csv(body: &[u8]) -> Result<Vec<CardRecord>, ApiError> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body)
+        .deserialize::<CardRow>()
+        .enumerate()
+        .map(|(index, row)| {
+            // the header is line 1
+            let row = row.map_err(|err| ApiError::Validation(format!("Row {}: {}", index + 2, err)))?;
+            Ok(CardRecord {
+                user_id: row.user_id,
+                stamps: row.stamps,
+                capacity: row.capacity,
+                kind: row.kind,
+                referral_code: row.referral_code.filter(|code| !code.is_empty()),
+                programme_id: None,
+                stamped: Vec::new(),
+                last_activity: None,
+                birth_month: None,
+                birthday_bonus_year: None,
+                total_stamps: 0,
+                redemptions: 0,
+                history: Vec::new()
+            })
+        })
+        .collect()
+}
+
+/// Checks every record up front so a bad file changes nothing, keeping each card's row label for later problems.
+fn check(records: Vec<CardRecord>, is_csv: bool) -> Result<Vec<(String, ImportedCard, Vec<CardActivity>)>, ApiError> {
+    let mut seen = HashSet::new();
+    let mut codes = HashSet::new();
+    let mut errors = Vec::new();
+    let mut cards = Vec::new();
+
+    for (index, record) in records.into_iter().enumerate() {
+        let label = if is_csv { format!("Row {}", index + 2) } else { format!("Card {}", index + 1) };
+        let capacity = record.capacity.unwrap_or(DEFAULT_CAPACITY);
+        let referral_code = record.referral_code.as_deref().map(referrals::normalised_code);
+
+        // spreadsheets tend to space numbers out, but cards are kept under the bare digits as claims are
+        let user_id = match PhoneNumber::try_from(record.user_id.as_str()) {
+            Ok(phone) => UserId::from(phone).0,
+            Err(message) => {
+                errors.push(format!("{}: {}", label, message));
+                continue;
+            }
+        };
+        // a customer may have both a stamp card and a points card
+        if !seen.insert((user_id.clone(), record.kind == CardKind::Points)) {
+            errors.push(format!("{}: {} appears more than once", label, user_id));
+            continue;
+        }
+        if record.kind == CardKind::Stamps && !(1..=MAX_CAPACITY).contains(&capacity) {
+            errors.push(format!("{}: a card must hold between 1 and {} stamps", label, MAX_CAPACITY));
+            continue;
+        }
+        if record.kind == CardKind::Stamps && record.stamps > capacity {
+            errors.push(format!("{}: {} stamps will not fit on a card of {}", label, record.stamps, capacity));
+            continue;
+        }
+        if record.birth_month.is_some_and(|month| !(1..=12).contains(&month)) {
+            errors.push(format!("{}: the birth month must be between 1 and 12", label));
+            continue;
+        }
+        match (record.kind, &referral_code) {
+            (_, Some(None)) => {
+                errors.push(format!("{}: a referral code is up to 16 letters and digits", label));
+                continue;
+            },
+            (CardKind::Points, Some(_)) => {
+                errors.push(format!("{}: only stamp cards have a referral code", label));
+                continue;
+            },
+            (_, Some(Some(code))) if !codes.insert(code.clone()) => {
+                errors.push(format!("{}: the referral code {} appears more than once", label, code));
+                continue;
+            },
+            _ => {}
+        }
+
+        if record.kind == CardKind::Stamps && !record.stamped.is_empty() && record.stamped.len() != record.stamps as usize {
+            errors.push(format!("{}: {} stamp dates were given for {} stamps", label, record.stamped.len(), record.stamps));
+            continue;
+        }
+        let dates: Result<(Vec<DateTime>, Option<DateTime>), String> = record.stamped.iter()
+            .map(|date| parse_date(date))
+            .collect::<Result<Vec<DateTime>, String>>()
+            .and_then(|stamped| Ok((stamped, record.last_activity.as_deref().map(parse_date).transpose()?)));
+        let (stamped, last_activity) = match dates {
+            Ok(dates) => dates,
+            Err(message) => {
+                errors.push(format!("{}: {}", label, message));
+                continue;
+            }
+        };
+
+        let history: Result<Vec<CardActivity>, String> = record.history.into_iter()
+            .map(|entry| CardActivity::try_from(ActivityResponse { user_id: user_id.clone(), ..entry }))
+            .collect();
+        let card = match record.kind {
+            CardKind::Stamps => {
+                let card = BasicStampCard::restored(UserId(user_id), record.stamps, capacity)
+                    .with_stamp_dates(stamped, last_activity)
+                    .in_programme(record.programme_id)
+                    .with_birth_month(record.birth_month)
+                    .with_birthday_bonus_year(record.birthday_bonus_year)
+                    .with_lifetime(record.total_stamps, record.redemptions);
+                ImportedCard::Stamps(match referral_code.flatten() {
+                    Some(code) => card.with_referral_code(code),
+                    None => card
+                })
+            },
+            CardKind::Points => ImportedCard::Points(PointsCard::restored(UserId(user_id), record.stamps, record.total_stamps, record.redemptions)
+                .with_history(record.programme_id, last_activity))
+        };
+        match history {
+            Ok(history) => cards.push((label, card, history)),
+            Err(message) => errors.push(format!("{}: {}", label, message))
+        }
+    }
+
+    if errors.is_empty() {
+        return Ok(cards);
+    }
+    Err(problems(errors))
+}
+
+fn problems(mut errors: Vec<String>) -> ApiError {
+    let count = errors.len();
+    errors.truncate(MAX_REPORTED_ERRORS);
+    ApiError::Validation(format!("Nothing was imported, {} problems were found. {}", count, errors.join("; ")))
+}
+
+/// What an imported card becomes on top of the customer's existing one, or why it cannot be.
+fn merged(existing: Option<&ImportedCard>, imported: ImportedCard, strategy: ImportStrategy) -> Result<ImportedCard, String> {
+    Ok(match (existing, imported, strategy) {
+        // adding clamps to the card, so stamps that do not fit would be lost without a word
+        (Some(ImportedCard::Stamps(card)), ImportedCard::Stamps(imported), ImportStrategy::Add) if card.stamps + imported.stamps > card.capacity() =>
+            return Err(format!("{} stamps will not fit on top of the {} already on a card of {}", imported.stamps, card.stamps, card.capacity())),
+        (Some(ImportedCard::Stamps(card)), ImportedCard::Stamps(imported), ImportStrategy::Add) => {
+            let card = card.with_adjustment(imported.stamps as i32)
+                .with_birth_month(imported.birth_month().or(card.birth_month()));
+            ImportedCard::Stamps(match (card.referral_code(), imported.referral_code()) {
+                (None, Some(code)) => card.with_referral_code(code.to_string()),
+                _ => card
+            })
+        },
+        (Some(ImportedCard::Stamps(card)), ImportedCard::Stamps(imported), ImportStrategy::Replace) =>
+            ImportedCard::Stamps(card.restored_over(&imported)),
+        (Some(ImportedCard::Points(card)), ImportedCard::Points(imported), ImportStrategy::Add) =>
+            ImportedCard::Points(card.with_earned(imported.points)),
+        (Some(ImportedCard::Points(card)), ImportedCard::Points(imported), ImportStrategy::Replace) =>
+            ImportedCard::Points(card.restored_over(&imported)),
+        (_, imported, _) => imported
+    })
+}
+
+/// A stamp card with everything needed to restore it.
+pub fn stamp_record(card: &BasicStampCard, history: Vec<ActivityResponse>) -> CardRecord {
+    CardRecord {
+        user_id: card.user_id().to_string(),
+        stamps: card.stamps,
+        capacity: Some(card.capacity()),
+        kind: CardKind::Stamps,
+        referral_code: card.referral_code().map(String::from),
+        programme_id: card.programme_id.clone(),
+        stamped: card.stamped().iter().map(|date| date.try_to_rfc3339_string().unwrap_or_default()).collect(),
+        last_activity: card.last_activity().and_then(|date| date.try_to_rfc3339_string().ok()),
+        birth_month: card.birth_month(),
+        birthday_bonus_year: card.birthday_bonus_year(),
+        total_stamps: card.total_stamps(),
+        redemptions: card.redemptions(),
+        history
+    }
+}
+
+/// A points card with everything needed to restore it, its points in the stamps column.
+pub fn points_record(card: &PointsCard, history: Vec<ActivityResponse>) -> CardRecord {
+    CardRecord {
+        user_id: card.user_id().to_string(),
+        stamps: card.points,
+        capacity: None,
+        kind: CardKind::Points,
+        referral_code: None,
+        programme_id: card.programme_id.clone(),
+        stamped: Vec::new(),
+        last_activity: card.last_activity().and_then(|date| date.try_to_rfc3339_string().ok()),
+        birth_month: None,
+        birthday_bonus_year: None,
+        total_stamps: card.total_points(),
+        redemptions: card.redemptions(),
+        history
+    }
+}
+
+impl ImportedCard {
+    fn balance(&self) -> u32 {
+        match self {
+            ImportedCard::Stamps(card) => card.balance(),
+            ImportedCard::Points(card) => card.balance()
+        }
+    }
+
+    fn activity(&self, delta: i32) -> CardActivity {
+        match self {
+            ImportedCard::Stamps(card) => CardActivity::new(ActivityKind::Imported, card, delta),
+            ImportedCard::Points(card) => CardActivity::new(ActivityKind::Imported, card, delta)
+        }
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/admin/export/cards",
+    tag = "import and export",
+    security(("admin_key" = [])),
+    params(("format" = Option<String>, Query, description = "json (the default, with history) or csv (without)")),
+    responses(
+        (status = 200, description = "Every card", body = Vec<CardRecord>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn export_cards(_: Admin, query: web::Query<ExportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let cards = data.cards.lock().await.list_cards().await?;
+    let points = data.points.lock().await.list_cards().await?;
+
+    if query.format == ReportFormat::Csv {
+        let rows: Vec<CardRow> = cards.into_iter()
+            .map(|card| CardRow {
+                user_id: card.user_id().to_string(),
+                stamps: card.stamps,
+                capacity: Some(card.capacity()),
+                kind: CardKind::Stamps,
+                referral_code: card.referral_code().map(String::from)
+            })
+            .chain(points.into_iter().map(|card| CardRow {
+                user_id: card.user_id().to_string(),
+                stamps: card.points,
+                capacity: None,
+                kind: CardKind::Points,
+                referral_code: None
+            }))
+            .collect();
+        return respond(rows, query.format, "cards");
+    }
+
+    // a customer with both cards has one history, which goes with their stamp card
+    let mut history: HashMap<UserId, Vec<ActivityResponse>> = HashMap::new();
+    for entry in data.activity.lock().await.between(None, None).await? {
+        history.entry(entry.user_id.clone()).or_default().push(entry.into());
+    }
+
+    let stamp_records: Vec<CardRecord> = cards.into_iter()
+        .map(|card| {
+            let history = history.remove(card.user_id()).unwrap_or_default();
+            stamp_record(&card, history)
+        })
+        .collect();
+    let points_records: Vec<CardRecord> = points.into_iter()
+        .map(|card| {
+            let history = history.remove(card.user_id()).unwrap_or_default();
+            points_record(&card, history)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_DISPOSITION, "attachment; filename=\"cards.json\""))
+        .json([stamp_records, points_records].concat()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/export/activity",
+    tag = "import and export",
+    security(("admin_key" = [])),
+    params(("format" = Option<String>, Query, description = "json (the default) or csv")),
+    responses(
+        (status = 200, description = "Every change to every card, oldest first", body = Vec<ActivityResponse>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn export_activity(_: Admin, query: web::Query<ExportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let activity = data.activity.lock().await.between(None, None).await?;
+
+    let rows: Vec<ActivityResponse> = activity.into_iter().map(ActivityResponse::from).collect();
+    respond(rows, query.format, "activity")
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/import/cards",
+    tag = "import and export",
+    security(("admin_key" = [])),
+    params(("strategy" = Option<String>, Query, description = "replace (the default) sets each card to the imported count, add puts the stamps or points on top of an existing card")),
+    request_body(
+        description = "A JSON array as produced by the export, or CSV with user_id and stamps columns and optional capacity, kind and referral_code columns",
+        content(
+            (Vec<CardRecord> = "application/json"),
+            (String = "text/csv")
+        )
+    ),
+    responses(
+        (status = 200, description = "Every card has been imported", body = ImportSummary),
+        (status = 400, description = "The file could not be read or a row is invalid, nothing was imported", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 500, description = "The database failed partway, the message says which row and how many were imported", body = ErrorResponse)
+    )
+)]
+pub async fn import_cards(_: Admin, req: HttpRequest, body: web::Bytes, query: web::Query<ImportQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let is_csv = req.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/csv"));
+
+    let records = match is_csv {
+        true => read_csv(&body)?,
+        false => serde_json::from_slice::<Vec<CardRecord>>(&body)
+            .map_err(|err| ApiError::Validation(format!("The file could not be read: {}", err)))?
+    };
+    let cards = check(records, is_csv)?;
+
+    let mut summary = ImportSummary { created: 0, updated: 0, history: 0 };
+    let mut tracker = data.cards.lock().await;
+    let mut points = data.points.lock().await;
+    let mut activity = data.activity.lock().await;
+
+    // every card is merged before any is saved, so a row that cannot be changes nothing
+    let mut errors = Vec::new();
+    let mut merges = Vec::new();
+    for (label, imported, history) in cards {
+        let existing = match &imported {
+            ImportedCard::Stamps(card) => tracker.find_card(card.user_id()).await?.map(ImportedCard::Stamps),
+            ImportedCard::Points(card) => points.find_card(card.user_id()).await?.map(ImportedCard::Points)
+        };
+        // a referral code already in another customer's link would send their friends to the wrong card
+        if let ImportedCard::Stamps(card) = &imported {
+            if let Some(code) = card.referral_code() {
+                if tracker.find_by_referral_code(code).await?.is_some_and(|owner| owner.user_id() != card.user_id()) {
+                    errors.push(format!("{}: the referral code {} already belongs to another card", label, code));
+                    continue;
+                }
+            }
+        }
+        match merged(existing.as_ref(), imported, query.strategy) {
+            Ok(card) => merges.push((label, card, existing, history)),
+            Err(message) => errors.push(format!("{}: {}", label, message))
+        }
+    }
+    if !errors.is_empty() {
+        return Err(problems(errors));
+    }
+
+    // the driver has no bulk write across collections, so a failure partway says how far the import got
+    let total = merges.len();
+    for (written, (label, card, existing, history)) in merges.into_iter().enumerate() {
+        let previous = existing.as_ref().map_or(0, ImportedCard::balance);
+        let saved = match &card {
+            ImportedCard::Stamps(card) => tracker.save_card(card).await,
+            ImportedCard::Points(card) => points.save_card(card).await
+        };
+        // history only makes sense on a card that starts here, otherwise it would be counted twice
+        let recorded = match saved {
+            Ok(()) if existing.is_none() && !history.is_empty() => activity.record_many(&history).await.map(|_| history.len() as u32),
+            Ok(()) => {
+                let mut entry = card.activity(card.balance() as i32 - previous as i32).reason("Imported");
+                entry.staff = Some(String::from("import"));
+                activity.record(&entry).await.map(|_| 0)
+            },
+            Err(err) => Err(err)
+        };
+        match recorded {
+            Ok(history) => summary.history += history,
+            Err(err) => {
+                error!("Import stopped at {} after {} of {} cards: {:?}", label, written, total, err);
+                return Err(ApiError::ImportIncomplete(format!(
+                    "The database failed at {}. The {} of {} cards before it were imported, check that card before importing the rest again",
+                    label, written, total
+                )))
+            }
+        }
+
+        match existing {
+            Some(_) => summary.updated += 1,
+            None => summary.created += 1
+        }
+    }
+
+    info!("Imported {} new and {} existing cards", summary.created, summary.updated);
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use loyalty_core::api::v1::ExpiryPolicy;
+
+    use super::*;
+
+    fn record(user_id: &str, stamps: u32, capacity: Option<u32>) -> CardRecord {
+        CardRecord {
+            user_id: user_id.into(),
+            stamps,
+            capacity,
+            kind: CardKind::Stamps,
+            referral_code: None,
+            programme_id: None,
+            stamped: Vec::new(),
+            last_activity: None,
+            birth_month: None,
+            birthday_bonus_year: None,
+            total_stamps: 0,
+            redemptions: 0,
+            history: Vec::new()
+        }
+    }
+
+    fn problem_count(records: Vec<CardRecord>) -> usize {
+        match check(records, false) {
+            Err(ApiError::Validation(message)) => message.split(';').count(),
+            other => panic!("expected the file to be refused, got {:?}", other.map(|cards| cards.len()))
+        }
+    }
+
+    #[test]
+    fn every_row_is_checked_before_anything_is_imported() {
+        let cards = check(vec![record("07700 900 001", 4, None), record("07700900002", 12, Some(12))], false).unwrap();
+        assert_eq!(cards.len(), 2);
+        let ImportedCard::Stamps(first) = &cards[0].1 else { panic!("expected a stamp card") };
+        assert_eq!((first.user_id().to_string(), first.stamps, first.capacity()), ("07700900001".to_string(), 4, DEFAULT_CAPACITY));
+
+        assert_eq!(problem_count(vec![record("not a number", 1, None)]), 1);
+        assert_eq!(problem_count(vec![record("07700900001", 1, None), record("07700900001", 2, None)]), 1);
+        assert_eq!(problem_count(vec![record("07700900001", 11, None)]), 1);
+        assert_eq!(problem_count(vec![record("07700900001", 0, Some(0))]), 1);
+        // a huge card would otherwise allocate a date for every stamp
+        assert_eq!(problem_count(vec![record("07700900001", 4_000_000_000, Some(4_000_000_000))]), 1);
+        assert_eq!(problem_count(vec![record("07700900001", MAX_CAPACITY + 1, Some(MAX_CAPACITY + 1))]), 1);
+        assert!(check(vec![record("07700900001", MAX_CAPACITY, Some(MAX_CAPACITY))], false).is_ok());
+    }
+
+    #[test]
+    fn customers_can_have_a_card_of_each_kind() {
+        let points = CardRecord { kind: CardKind::Points, stamps: 5000, capacity: None, total_stamps: 9000, ..record("07700900001", 0, None) };
+        let cards = check(vec![record("07700900001", 3, None), points.clone()], false).unwrap();
+        let ImportedCard::Points(card) = &cards[1].1 else { panic!("expected a points card") };
+        assert_eq!((card.points, card.total_points()), (5000, 9000));
+
+        assert_eq!(problem_count(vec![points.clone(), points.clone()]), 1);
+        assert_eq!(problem_count(vec![CardRecord { referral_code: Some("FRIEND12".into()), ..points }]), 1);
+    }
+
+    #[test]
+    fn referral_codes_must_be_usable_and_unique() {
+        let with_code = |user_id: &str, code: &str| CardRecord { referral_code: Some(code.into()), ..record(user_id, 1, None) };
+
+        let cards = check(vec![with_code("07700900001", " friend12 ")], false).unwrap();
+        let ImportedCard::Stamps(card) = &cards[0].1 else { panic!("expected a stamp card") };
+        assert_eq!(card.referral_code(), Some("FRIEND12"));
+
+        assert_eq!(problem_count(vec![with_code("07700900001", "not a code!")]), 1);
+        assert_eq!(problem_count(vec![with_code("07700900001", "FRIEND12"), with_code("07700900002", "friend12")]), 1);
+    }
+
+    #[test]
+    fn csv_files_may_leave_out_the_newer_columns() {
+        let old = read_csv(b"user_id,stamps,capacity\n07700900001,3,\n").unwrap();
+        assert_eq!((old[0].kind, old[0].capacity, old[0].referral_code.clone()), (CardKind::Stamps, None, None));
+
+        let new = read_csv(b"user_id,stamps,capacity,kind,referral_code\n07700900001,3,8,stamps,FRIEND12\n07700900001,250,,points,\n").unwrap();
+        assert_eq!((new[0].capacity, new[0].referral_code.as_deref()), (Some(8), Some("FRIEND12")));
+        assert_eq!((new[1].kind, new[1].stamps, new[1].referral_code.clone()), (CardKind::Points, 250, None));
+    }
+
+    #[test]
+    fn replacing_keeps_what_the_file_cannot_carry() {
+        let user_id = UserId("07700900001".into());
+        let existing = BasicStampCard::new(user_id.clone())
+            .with_stamps(3, Some("coffee".into()))
+            .with_referral_code(String::from("FRIEND12"))
+            .with_birth_month(Some(7))
+            .with_birthday_bonus(1, 2024);
+        let imported = BasicStampCard::restored(user_id.clone(), 2, 8);
+
+        let Ok(ImportedCard::Stamps(card)) = merged(Some(&ImportedCard::Stamps(existing.clone())), ImportedCard::Stamps(imported), ImportStrategy::Replace)
+            else { panic!("expected a stamp card") };
+        assert_eq!((card.stamps, card.capacity()), (2, 8));
+        assert_eq!((card.programme_id.as_deref(), card.referral_code(), card.birth_month()), (Some("coffee"), Some("FRIEND12"), Some(7)));
+        assert!(!card.birthday_due(7, 2024));
+        assert_eq!(card.expiries(&ExpiryPolicy::StampAge { months: 6 }), existing.expiries(&ExpiryPolicy::StampAge { months: 6 })[..2]);
+        assert_eq!(card.total_stamps(), existing.total_stamps());
+
+        let Ok(ImportedCard::Stamps(added)) = merged(Some(&ImportedCard::Stamps(existing.clone())), ImportedCard::Stamps(BasicStampCard::restored(user_id.clone(), 2, 8)), ImportStrategy::Add)
+            else { panic!("expected a stamp card") };
+        assert_eq!((added.stamps, added.capacity(), added.referral_code()), (6, DEFAULT_CAPACITY, Some("FRIEND12")));
+    }
+
+    #[test]
+    fn added_stamps_must_fit_on_the_card() {
+        let user_id = UserId("07700900001".into());
+        let existing = ImportedCard::Stamps(BasicStampCard::new(user_id.clone()).with_stamps(8, None));
+
+        let overflow = merged(Some(&existing), ImportedCard::Stamps(BasicStampCard::restored(user_id.clone(), 5, DEFAULT_CAPACITY)), ImportStrategy::Add);
+        assert_eq!(overflow.err().as_deref(), Some("5 stamps will not fit on top of the 8 already on a card of 10"));
+        assert!(merged(Some(&existing), ImportedCard::Stamps(BasicStampCard::restored(user_id, 2, DEFAULT_CAPACITY)), ImportStrategy::Add).is_ok());
+    }
+
+    #[test]
+    fn an_exported_card_comes_back_as_it_was() {
+        let stamped = DateTime::parse_rfc3339_str("2024-03-01T09:00:00Z").unwrap();
+        let card = BasicStampCard::restored(UserId("07700900001".into()), 2, 8)
+            .with_stamp_dates(vec![stamped, stamped], None)
+            .in_programme(Some("coffee".into()))
+            .with_birth_month(Some(7))
+            .with_birthday_bonus_year(Some(2024))
+            .with_lifetime(12, 1);
+
+        let cards = check(vec![stamp_record(&card, Vec::new())], false).unwrap();
+        let ImportedCard::Stamps(restored) = &cards[0].1 else { panic!("expected a stamp card") };
+        assert_eq!(stamp_record(restored, Vec::new()), stamp_record(&card, Vec::new()));
+        assert!(restored.is_dated());
+
+        let points = PointsCard::restored(UserId("07700900001".into()), 250, 900, 2).with_history(Some("beans".into()), Some(stamped));
+        let cards = check(vec![points_record(&points, Vec::new())], false).unwrap();
+        let ImportedCard::Points(restored) = &cards[0].1 else { panic!("expected a points card") };
+        assert_eq!((restored.programme_id.as_deref(), restored.last_activity()), (Some("beans"), Some(stamped)));
+
+        assert_eq!(problem_count(vec![CardRecord { stamped: vec!["2024-03-01T09:00:00Z".into()], ..record("07700900001", 2, None) }]), 1);
+        assert_eq!(problem_count(vec![CardRecord { last_activity: Some("yesterday".into()), ..record("07700900001", 2, None) }]), 1);
+    }
+
+    #[actix_web::test]
+    async fn a_bad_file_is_refused_before_the_database() {
+        let app = init_service(App::new().app_data(crate::test_state(Some("admin"))).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::post().uri("/api/admin/import/cards")
+            .insert_header((header::AUTHORIZATION, "Bearer admin"))
+            .insert_header((header::CONTENT_TYPE, "text/csv"))
+            .set_payload("user_id,stamps,capacity\n07700900001,4000000000,4000000000\n")
+            .to_request();
+
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(String::from_utf8_lossy(&read_body(response).await).contains("between 1 and 50 stamps"));
+    }
+}
@@ -1,28 +1,81 @@
-use std::fmt::Display;
-use std::sync::Mutex;
+use std::collections::HashMap;
 
 use actix_cors::Cors;
 use actix_files::NamedFile;
-use actix_web::{ Scope, web, web::ServiceConfig};
+use actix_web::{guard, Scope, web, web::ServiceConfig};
 use actix_web::dev::{fn_service, ServiceRequest, ServiceResponse};
-use actix_web::web::{get, post, resource};
+use actix_web::web::{delete, get, post, resource};
+use log::warn;
 use mongodb::Database;
 use shuttle_actix_web::ShuttleActixWeb;
+use shuttle_runtime::SecretStore;
+use tokio::sync::{broadcast, Mutex};
 
-use loyalty_core::qr_gen::CustomerQrCode;
-use crate::stampcard::BasicStampCard;
+use loyalty_core::api::v1::ThemeDetails;
+use loyalty_core::StoreId;
+use crate::activity::CardActivity;
+use crate::audit::AuditEntry;
+use crate::campaigns::Campaign;
+use crate::error::ApiError;
+use crate::customer_code::{ActiveCode, CodeUpdate};
+use crate::devices::{Device, PendingPairing};
+use crate::privacy::PendingDataRequest;
+use crate::points::PointsCard;
+use crate::programmes::{Programme, Store};
+use crate::rate_limit::{ClaimLimiter, RateLimitConfig};
+use crate::referrals::Referral;
+use crate::stamp_images::StampImage;
+use crate::stampcard::{BasicStampCard, CardNotification};
+use crate::tenants::Tenant;
 
 mod stampcard;
 mod customer_code;
 mod db;
+mod devices;
+mod auth;
+mod sse;
+mod audit;
+mod rate_limit;
+mod error;
+mod openapi;
+mod activity;
+mod programmes;
+mod analytics;
+mod export;
+mod privacy;
+mod expiry;
+mod campaigns;
+mod referrals;
+mod bonuses;
+mod tiers;
+mod points;
+mod tenants;
+mod theme;
+mod stamp_images;
 
 type AppData = web::Data<State>;
 
+/// Everything one business needs, so each tenant only ever sees its own cards, stores and staff.
 struct State
 {
-    app_name: String,
+    tenant: Tenant,
     cards: Mutex<db::MongoDbStampCardRepository>,
-    qr: Mutex<Option<CustomerQrCode>>, // TODO this could be a dictionary to allow multiple stores to display unique qr codes
+    devices: Mutex<db::MongoDbDeviceRepository>,
+    pairing: Mutex<HashMap<String, PendingPairing>>,
+    qr: Mutex<HashMap<StoreId, ActiveCode>>, // each paired store displays its own code
+    code_updates: broadcast::Sender<CodeUpdate>,
+    card_updates: broadcast::Sender<CardNotification>,
+    audit: Mutex<db::MongoDbAuditRepository>,
+    activity: Mutex<db::MongoDbActivityRepository>,
+    programmes: Mutex<db::MongoDbProgrammeRepository>,
+    stores: Mutex<db::MongoDbStoreRepository>,
+    campaigns: Mutex<db::MongoDbCampaignRepository>,
+    referrals: Mutex<db::MongoDbReferralRepository>,
+    points: Mutex<db::MongoDbCardRepository<PointsCard>>,
+    theme: Mutex<db::MongoDbThemeRepository>,
+    stamp_images: Mutex<db::MongoDbStampImageRepository>,
+    limiter: Mutex<ClaimLimiter>,
+    data_requests: Mutex<HashMap<String, PendingDataRequest>>, // keyed by the hash of the customer's token
 }
 
 
@@ -92,41 +145,195 @@ struct State
 
 
 
-#[shuttle_runtime::main]
-async fn main(
-    #[shuttle_shared_db::MongoDb] db: Database
-) -> ShuttleActixWeb<impl FnOnce(&mut ServiceConfig) + Send + Clone + 'static> {
-    // each thread with create its own instance of HttpServer so shared state needs to be instantiated outside of this factory
-    let cors = Cors::default()
-        .allowed_origin("http://localhost:8081")
-        // .allowed_origin_fn(|origin, _req_head| {
-        //     origin.as_bytes().ends_with(b".rust-lang.org")
-        // })
-        .allowed_methods(vec!["GET", "POST"])
-        // .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
-        // .allowed_header(http::header::CONTENT_TYPE)
-        .max_age(3600);
+/// Every route under `/api`, kept out of `main` so the OpenAPI tests can mount the real thing.
+fn api_routes(cfg: &mut ServiceConfig) {
+    cfg
+        .service(resource("/customercode").route(get().to(customer_code::get_code)))
+        .service(resource("/customercode/stream").route(get().to(customer_code::stream_codes)))
+        .service(resource("/customercode/claim").route(post().to(customer_code::claim_code)))
+        .service(resource("/stampcard/{id}").route(get().to(stampcard::get_card)))
+        .service(resource("/stampcard/{id}/stream").route(get().to(stampcard::stream_card)))
+        .service(resource("/stampcard/{id}/reset").route(post().to(stampcard::reset_card)))
+        .service(resource("/stampcard/{id}/redeem").route(post().to(stampcard::redeem_reward)))
+        .service(resource("/stampcard/{id}/adjust").route(post().to(stampcard::adjust_card)))
+        .service(resource("/stampcard/{id}/birthday").route(post().to(stampcard::set_birthday)))
+        .service(resource("/points/{id}").route(get().to(points::get_points)))
+        .service(resource("/points/{id}/earn").route(post().to(points::earn_points)))
+        .service(resource("/points/{id}/redeem").route(post().to(points::redeem_points)))
+        .service(resource("/stampcard/{id}/referral").route(get().to(referrals::referral_link)))
+        .service(resource("/privacy/requests").route(post().to(privacy::request_data)))
+        .service(resource("/privacy/approve").route(post().to(privacy::approve_data_request)))
+        .service(resource("/privacy/data").route(get().to(privacy::personal_data)))
+        .service(resource("/privacy/erase").route(post().to(privacy::erase_data)))
+        .service(resource("/devices/pair").route(post().to(devices::pair_device)))
+        .service(resource("/admin/pairing").route(post().to(devices::create_pairing_code)))
+        .service(resource("/admin/devices").route(get().to(devices::list_devices)))
+        .service(resource("/admin/devices/{device_id}/revoke").route(post().to(devices::revoke_device)))
+        .service(resource("/admin/cards/{id}").route(get().to(stampcard::lookup_card)))
+        .service(resource("/admin/activity").route(get().to(activity::list_activity)))
+        .service(resource("/admin/referrals").route(get().to(referrals::list_referrals)))
+        .service(resource("/admin/programmes")
+            .route(get().to(programmes::list_programmes))
+            .route(post().to(programmes::save_programme)))
+        .service(resource("/admin/programmes/{programme_id}").route(delete().to(programmes::delete_programme)))
+        .service(resource("/admin/stores")
+            .route(get().to(programmes::list_stores))
+            .route(post().to(programmes::save_store)))
+        .service(resource("/admin/stores/{store_id}").route(delete().to(programmes::delete_store)))
+        .service(resource("/admin/campaigns")
+            .route(get().to(campaigns::list_campaigns))
+            .route(post().to(campaigns::save_campaign)))
+        .service(resource("/admin/campaigns/{campaign_id}").route(delete().to(campaigns::delete_campaign)))
+        .service(resource("/campaigns/live").route(get().to(campaigns::live_campaigns)))
+        .service(resource("/admin/analytics/stamps").route(get().to(analytics::stamps_report)))
+        .service(resource("/admin/analytics/redemptions").route(get().to(analytics::redemptions_report)))
+        .service(resource("/admin/analytics/customers").route(get().to(analytics::customers_report)))
+        .service(resource("/admin/analytics/stamp-counts").route(get().to(analytics::stamp_counts_report)))
+        .service(resource("/admin/export/cards").route(get().to(export::export_cards)))
+        .service(resource("/admin/export/activity").route(get().to(export::export_activity)))
+        .service(resource("/admin/import/cards")
+            .app_data(web::PayloadConfig::new(export::IMPORT_LIMIT))
+            .route(post().to(export::import_cards)))
+        .service(resource("/tenant").route(get().to(tenants::get_tenant)))
+        .service(resource("/theme").route(get().to(theme::get_theme)))
+        .service(resource("/admin/theme").route(post().to(theme::save_theme)))
+        .service(resource("/theme/images/{slot}").route(get().to(stamp_images::get_image)))
+        .service(resource("/admin/theme/images/{slot}")
+            .app_data(web::PayloadConfig::new(stamp_images::IMAGE_LIMIT))
+            .route(post().to(stamp_images::upload_image)))
+        .service(resource("/openapi.json").route(get().to(openapi::openapi_json)))
+        .service(resource("/docs").route(get().to(openapi::docs)));
+}
+
+/// The state of one tenant, its collections kept apart from every other tenant's in the shared database.
+fn tenant_state(db: &Database, tenant: Tenant, secrets: &SecretStore) -> AppData {
+    let collection = |name: &str| tenant.collection(name);
 
     let mongo_repo = db::MongoDbStampCardRepository{
-        collection: db.collection::<BasicStampCard>("cards")
+        collection: db.collection::<BasicStampCard>(&collection("cards"))
+    };
+
+    let device_repo = db::MongoDbDeviceRepository{
+        collection: db.collection::<Device>(&collection("devices"))
+    };
+
+    let audit_repo = db::MongoDbAuditRepository{
+        collection: db.collection::<AuditEntry>(&collection("audit"))
+    };
+
+    let activity_repo = db::MongoDbActivityRepository{
+        collection: db.collection::<CardActivity>(&collection("activity"))
+    };
+
+    let programme_repo = db::MongoDbProgrammeRepository{
+        collection: db.collection::<Programme>(&collection("programmes"))
+    };
+
+    let store_repo = db::MongoDbStoreRepository{
+        collection: db.collection::<Store>(&collection("stores"))
+    };
+
+    let campaign_repo = db::MongoDbCampaignRepository{
+        collection: db.collection::<Campaign>(&collection("campaigns"))
+    };
+
+    let referral_repo = db::MongoDbReferralRepository{
+        collection: db.collection::<Referral>(&collection("referrals"))
     };
 
-    let app_data = web::Data::new(State {
-        app_name: String::from("foo"),
+    let points_repo = db::MongoDbCardRepository{
+        collection: db.collection::<PointsCard>(&collection("points_cards"))
+    };
+
+    let theme_repo = db::MongoDbThemeRepository{
+        collection: db.collection::<ThemeDetails>(&collection("theme"))
+    };
+
+    let stamp_image_repo = db::MongoDbStampImageRepository{
+        collection: db.collection::<StampImage>(&collection("stamp_images"))
+    };
+
+    if tenant.admin_key.is_none() {
+        warn!("No admin key is set for {}, its admin api will reject all requests", tenant.name);
+    }
+
+    web::Data::new(State {
+        tenant,
         cards: Mutex::new(mongo_repo),
-        qr: Mutex::new(None)
-    });
+        devices: Mutex::new(device_repo),
+        pairing: Mutex::new(HashMap::new()),
+        qr: Mutex::new(HashMap::new()),
+        code_updates: broadcast::channel(64).0,
+        card_updates: broadcast::channel(256).0,
+        audit: Mutex::new(audit_repo),
+        activity: Mutex::new(activity_repo),
+        programmes: Mutex::new(programme_repo),
+        stores: Mutex::new(store_repo),
+        campaigns: Mutex::new(campaign_repo),
+        referrals: Mutex::new(referral_repo),
+        points: Mutex::new(points_repo),
+        theme: Mutex::new(theme_repo),
+        stamp_images: Mutex::new(stamp_image_repo),
+        limiter: Mutex::new(ClaimLimiter::new(RateLimitConfig::from_secrets(secrets))),
+        data_requests: Mutex::new(HashMap::new())
+    })
+}
+
+/// A tenant whose database is never there, for testing everything a request does before it reaches a query.
+#[cfg(test)]
+fn test_state(admin_key: Option<&str>) -> AppData {
+    use std::time::Duration;
+    use mongodb::options::{ClientOptions, ServerAddress};
+
+    let options = ClientOptions::builder()
+        .hosts(vec![ServerAddress::Tcp { host: String::from("127.0.0.1"), port: Some(9) }])
+        .server_selection_timeout(Duration::from_millis(50))
+        .build();
+    let db = mongodb::Client::with_options(options).unwrap().database("test");
+    let tenant = Tenant { tenant_id: None, name: String::from("Test"), admin_key: admin_key.map(String::from) };
+
+    tenant_state(&db, tenant, &SecretStore::new(Default::default()))
+}
+
+#[shuttle_runtime::main]
+async fn main(
+    #[shuttle_shared_db::MongoDb] db: Database,
+    #[shuttle_runtime::Secrets] secrets: SecretStore
+) -> ShuttleActixWeb<impl FnOnce(&mut ServiceConfig) + Send + Clone + 'static> {
+    // each thread with create its own instance of HttpServer so shared state needs to be instantiated outside of this factory
+    let tenants: Vec<AppData> = Tenant::from_secrets(&secrets).into_iter()
+        .map(|tenant| tenant_state(&db, tenant, &secrets))
+        .collect();
+    // tenants are also served on their own subdomain, such as bakery.<TENANT_DOMAIN>
+    let tenant_domain = secrets.get("TENANT_DOMAIN");
+
+    for app_data in &tenants {
+        tokio::spawn(customer_code::expire_codes(app_data.clone()));
+        tokio::spawn(expiry::expire_stamps(app_data.clone()));
+        tokio::spawn(bonuses::award_birthdays(app_data.clone()));
+    }
 
     let config = move |cfg: &mut ServiceConfig| {
-        cfg.service(
-            Scope::new("/api")
-                .service(resource("/customercode").route(get().to(customer_code::get_code)))
-                .service(resource("/customercode/claim").route(post().to(customer_code::claim_code)))
-                .service(resource("/stampcard/{id}").route(get().to(stampcard::get_card)))
-                .service(resource("/stampcard/{id}/reset").route(post().to(stampcard::reset_card)))
-                .wrap(Cors::permissive())
-                .app_data(app_data.clone())
-        );
+        let api_scope = |path: &str, app_data: &AppData| Scope::new(path)
+            .configure(api_routes)
+            .wrap(Cors::permissive())
+            .app_data(app_data.clone())
+            .app_data(web::JsonConfig::default()
+                .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()))
+            .app_data(web::PathConfig::default()
+                .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()))
+            .app_data(web::QueryConfig::default()
+                .error_handler(|err, _| ApiError::BadRequest(err.to_string()).into()));
+
+        // scopes are matched in order so the subdomains go ahead of the server's own business on plain /api
+        for app_data in &tenants {
+            if let Some(host) = tenant_domain.as_deref().and_then(|domain| app_data.tenant.host(domain)) {
+                cfg.service(api_scope("/api", app_data).guard(guard::Host(host)));
+            }
+        }
+        for app_data in &tenants {
+            cfg.service(api_scope(&app_data.tenant.api_path(), app_data));
+        }
 
         // TODO is this needed
         // let path = env::current_dir().unwrap();
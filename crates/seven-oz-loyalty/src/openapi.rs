@@ -0,0 +1,199 @@
+use actix_web::HttpResponse;
+use utoipa::{Modify, OpenApi};
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+
+use loyalty_core::api::v1::*;
+use loyalty_core::StoreId;
+
+use crate::{activity, analytics, campaigns, customer_code, devices, export, points, privacy, programmes, referrals, stamp_images, stampcard, tenants, theme};
+
+// rapidoc is pulled from a cdn the same way the client pulls in bootstrap, and the spec is
+// found relative to the page so a tenant's docs under /t/<tenant>/api load that tenant's spec
+const DOCS_PAGE: &str = r#"<!doctype html>
+<html lang="en">
+<head>
+    <meta charset="utf-8">
+    <title>Loyalty API</title>
+    <script type="module" src="https://unpkg.com/rapidoc@9.3.4/dist/rapidoc-min.js"></script>
+</head>
+<body>
+    <rapi-doc spec-url="openapi.json" render-style="read" show-header="false"></rapi-doc>
+</body>
+</html>"#;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Loyalty API", description = "Stamp cards, QR codes and display pairing. Each tenant serves the same api under `/t/<tenant>/api` or on its own subdomain."),
+    paths(
+        customer_code::get_code,
+        customer_code::stream_codes,
+        customer_code::claim_code,
+        stampcard::get_card,
+        stampcard::stream_card,
+        stampcard::reset_card,
+        stampcard::redeem_reward,
+        stampcard::adjust_card,
+        stampcard::set_birthday,
+        points::get_points,
+        points::earn_points,
+        points::redeem_points,
+        stampcard::lookup_card,
+        activity::list_activity,
+        devices::pair_device,
+        devices::create_pairing_code,
+        devices::list_devices,
+        devices::revoke_device,
+        programmes::list_programmes,
+        programmes::save_programme,
+        programmes::delete_programme,
+        programmes::list_stores,
+        programmes::save_store,
+        programmes::delete_store,
+        campaigns::list_campaigns,
+        campaigns::save_campaign,
+        campaigns::delete_campaign,
+        campaigns::live_campaigns,
+        referrals::referral_link,
+        referrals::list_referrals,
+        tenants::get_tenant,
+        theme::get_theme,
+        theme::save_theme,
+        stamp_images::upload_image,
+        stamp_images::get_image,
+        analytics::stamps_report,
+        analytics::redemptions_report,
+        analytics::customers_report,
+        analytics::stamp_counts_report,
+        export::export_cards,
+        export::export_activity,
+        export::import_cards,
+        privacy::request_data,
+        privacy::approve_data_request,
+        privacy::personal_data,
+        privacy::erase_data,
+    ),
+    components(schemas(
+        ErrorResponse, CodeResponse, ClaimRequest, ClaimResponse, CardResponse, CardKind, StampExpiry, BirthdayRequest, TierProgress, CardEvent, CardUpdate,
+        PairingCodeRequest, PairingCodeResponse, PairRequest, PairResponse, DeviceResponse, StoreId,
+        ActivityKind, ActivityResponse, AdminCardResponse, AdjustRequest, ProgrammeDetails, ExpiryPolicy, BonusRules, MilestoneBonus, Tier, StoreDetails,
+        CatalogueReward, EarnRequest, SpendRequest,
+        CampaignDetails, CampaignReward, Weekday, ReferralLinkResponse, ReferralResponse, TenantResponse, ThemeDetails, StampSlot,
+        DailyStamps, DailyRedemptions, DailyCustomers, StampCount, CardRecord, ImportSummary,
+        DataRequest, DataRequestResponse, ApproveDataRequest, AuditRecord, PersonalDataResponse, ErasureResponse
+    )),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("admin_key", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("device_key", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("data_request", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+    }
+}
+
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+pub async fn docs() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS_PAGE)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope, test};
+    use actix_web::http::{Method, StatusCode};
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+
+    fn concrete(path: &str) -> String {
+        path.split('/')
+            .map(|segment| if segment.starts_with('{') { "x" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Every documented operation must be routed, and nothing on a documented path may be routed without being documented.
+    #[actix_web::test]
+    async fn spec_matches_routes() {
+        let app = test::init_service(App::new().service(Scope::new("/api").configure(crate::api_routes))).await;
+        let spec = ApiDoc::openapi();
+
+        assert!(!spec.paths.paths.is_empty());
+        for (path, item) in spec.paths.paths.iter() {
+            let url = concrete(path);
+            let operations = [
+                (Method::GET, item.get.is_some()),
+                (Method::POST, item.post.is_some()),
+                (Method::PUT, item.put.is_some()),
+                (Method::DELETE, item.delete.is_some()),
+            ];
+            for (method, documented) in operations {
+                let request = test::TestRequest::default().method(method.clone()).uri(&url).to_request();
+                let status = test::call_service(&app, request).await.status();
+
+                if documented {
+                    assert!(status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                            "{} {} is documented but not routed ({})", method, path, status);
+                } else {
+                    assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {} is routed but not documented", method, path);
+                }
+            }
+        }
+    }
+
+    /// Every route in `api_routes` must be documented, read from its source since actix cannot list what it has routed.
+    #[actix_web::test]
+    async fn routes_are_documented() {
+        // the spec and its page describe the api rather than being part of it
+        const UNDOCUMENTED: [&str; 2] = ["/openapi.json", "/docs"];
+        let source = include_str!("main.rs");
+        let start = source.find("fn api_routes").expect("api_routes is in main.rs");
+        let routes = &source[start..start + source[start..].find("\n}\n").expect("api_routes ends")];
+        let spec = ApiDoc::openapi();
+
+        let mut checked = 0;
+        for service in routes.split(".service(resource(\"").skip(1) {
+            let path = &service[..service.find('"').unwrap()];
+            if UNDOCUMENTED.contains(&path) {
+                continue;
+            }
+            let item = spec.paths.paths.get(&format!("/api{}", path));
+            let operations = [
+                ("get()", item.is_some_and(|item| item.get.is_some())),
+                ("post()", item.is_some_and(|item| item.post.is_some())),
+                ("put()", item.is_some_and(|item| item.put.is_some())),
+                ("delete()", item.is_some_and(|item| item.delete.is_some())),
+            ];
+            for (method, documented) in operations {
+                if service.contains(method) {
+                    assert!(documented, "{} {} is routed but not documented", method.trim_end_matches("()").to_uppercase(), path);
+                    checked += 1;
+                }
+            }
+        }
+        assert!(checked >= spec.paths.paths.len());
+    }
+
+    #[actix_web::test]
+    async fn serves_spec_and_docs() {
+        let app = test::init_service(App::new().service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        let spec: serde_json::Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri("/api/openapi.json").to_request()).await;
+        assert!(spec["paths"]["/api/customercode/claim"]["post"].is_object());
+        assert!(spec["components"]["securitySchemes"]["admin_key"].is_object());
+
+        let docs = test::call_service(&app, test::TestRequest::get().uri("/api/docs").to_request()).await;
+        assert_eq!(docs.status(), StatusCode::OK);
+        let page = test::read_body(docs).await;
+        assert!(String::from_utf8_lossy(&page).contains(r#"spec-url="openapi.json""#));
+    }
+}
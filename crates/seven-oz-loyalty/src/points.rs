@@ -0,0 +1,278 @@
+use actix_web::{HttpResponse, web};
+use log::info;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ActivityKind, CardEvent, CardKind, CardResponse, EarnRequest, ErrorResponse, SpendRequest};
+use loyalty_core::card::LoyaltyCard;
+use loyalty_core::UserId;
+
+use crate::activity::{self, CardActivity};
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use crate::programmes::Programme;
+use crate::stampcard::CardNotification;
+
+/// A card on a points programme, earning by how much is spent rather than a stamp a visit.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PointsCard {
+    user_id: UserId,
+    pub points: u32,
+    /// The programme the points were last earned in, which decides what they can be spent on.
+    #[serde(default)]
+    pub programme_id: Option<String>,
+    /// Every point earned, spent or not.
+    #[serde(default)]
+    total_points: u32,
+    #[serde(default)]
+    redemptions: u32,
+    #[serde(default)]
+    last_activity: Option<DateTime>,
+}
+
+impl PointsCard {
+    /// A card carried over from a backup, never with fewer points earned than it holds.
+    pub fn restored(user_id: UserId, points: u32, total_points: u32, redemptions: u32) -> Self {
+        PointsCard {
+            points,
+            total_points: total_points.max(points),
+            redemptions,
+            ..PointsCard::new(user_id)
+        }
+    }
+
+    /// The card in the programme and with the last activity a backup had for it.
+    pub fn with_history(&self, programme_id: Option<String>, last_activity: Option<DateTime>) -> Self {
+        PointsCard {
+            programme_id,
+            last_activity: last_activity.or(self.last_activity),
+            ..self.clone()
+        }
+    }
+
+    /// This card set to the points of `restored`, keeping its programme unless the backup had one.
+    /// Counters never go backwards.
+    pub fn restored_over(&self, restored: &PointsCard) -> Self {
+        PointsCard {
+            points: restored.points,
+            programme_id: restored.programme_id.clone().or(self.programme_id.clone()),
+            total_points: self.total_points.max(restored.total_points),
+            redemptions: self.redemptions.max(restored.redemptions),
+            last_activity: Some(DateTime::now()),
+            ..self.clone()
+        }
+    }
+
+    pub fn last_activity(&self) -> Option<DateTime> {
+        self.last_activity
+    }
+
+    pub fn total_points(&self) -> u32 {
+        self.total_points
+    }
+
+    pub fn redemptions(&self) -> u32 {
+        self.redemptions
+    }
+
+    fn in_programme(&self, programme_id: String) -> Self {
+        PointsCard {
+            programme_id: Some(programme_id),
+            ..self.clone()
+        }
+    }
+}
+
+impl LoyaltyCard for PointsCard {
+    fn new(user_id: UserId) -> Self {
+        PointsCard {
+            user_id,
+            points: 0,
+            programme_id: None,
+            total_points: 0,
+            redemptions: 0,
+            last_activity: Some(DateTime::now())
+        }
+    }
+
+    fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    fn kind(&self) -> CardKind {
+        CardKind::Points
+    }
+
+    fn balance(&self) -> u32 {
+        self.points
+    }
+
+    fn limit(&self) -> Option<u32> {
+        None
+    }
+
+    fn with_earned(&self, amount: u32) -> Self {
+        PointsCard {
+            points: self.points.saturating_add(amount),
+            total_points: self.total_points.saturating_add(amount),
+            last_activity: Some(DateTime::now()),
+            ..self.clone()
+        }
+    }
+
+    fn with_spent(&self, cost: u32) -> Option<Self> {
+        (cost <= self.points).then(|| PointsCard {
+            points: self.points - cost,
+            redemptions: self.redemptions + 1,
+            last_activity: Some(DateTime::now()),
+            ..self.clone()
+        })
+    }
+}
+
+/// Points for a spend in pence at `rate` points per whole unit, rounded down.
+pub fn points_for(spend: u32, rate: u32) -> u32 {
+    u32::try_from(spend as u64 * rate as u64 / 100).unwrap_or(u32::MAX)
+}
+
+async fn points_programme(data: &AppData, programme_id: Option<&str>) -> Result<Programme, ApiError> {
+    let programme = match programme_id {
+        Some(programme_id) => data.programmes.lock().await.find_programme(programme_id).await?,
+        None => None
+    };
+    programme
+        .filter(|programme| programme.kind == CardKind::Points)
+        .ok_or_else(|| ApiError::Validation(String::from("Points can only be used with a points programme")))
+}
+
+fn card_response(card: &PointsCard, programme: Option<Programme>) -> CardResponse {
+    CardResponse {
+        stamps: card.points,
+        capacity: 0,
+        kind: CardKind::Points,
+        expiring: Vec::new(),
+        birth_month: None,
+        total_stamps: card.total_points,
+        redemptions: card.redemptions,
+        tier: None,
+        rewards: programme.map(|programme| programme.catalogue).unwrap_or_default()
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/points/{id}",
+    tag = "points",
+    params(("id" = String, Path, description = "The customer's phone number")),
+    responses(
+        (status = 200, description = "The points card and what it can be spent on, created empty if the customer has not got one yet", body = CardResponse)
+    )
+)]
+pub async fn get_points(path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = UserId(path.into_inner());
+
+    let card = data.points.lock().await.get_or_create_card(&user_id).await?;
+    let programme = points_programme(&data, card.programme_id.as_deref()).await.ok();
+
+    Ok(HttpResponse::Ok().json(card_response(&card, programme)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/points/{id}/earn",
+    tag = "points",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    request_body = EarnRequest,
+    responses(
+        (status = 200, description = "The card with the points for the spend added", body = CardResponse),
+        (status = 400, description = "No spend, or no points programme in the request", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn earn_points(admin: Admin, path: web::Path<String>, request: web::Json<EarnRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = UserId(path.into_inner());
+    if request.spend == 0 {
+        return Err(ApiError::Validation(String::from("Enter how much the customer spent")))
+    }
+
+    let programme = points_programme(&data, request.programme_id.as_deref()).await?;
+    let points = points_for(request.spend, programme.points_rate.unwrap_or_default());
+
+    let mut tracker = data.points.lock().await;
+    let card = tracker.get_or_create_card(&user_id).await?
+        .with_earned(points)
+        .in_programme(programme.programme_id.clone());
+    tracker.save_card(&card).await?;
+    drop(tracker);
+
+    if points > 0 {
+        let reason = format!("Spent {}.{:02}", request.spend / 100, request.spend % 100);
+        // activity holds an i32, which a huge spend at a generous rate could pass
+        let earned = i32::try_from(points).unwrap_or(i32::MAX);
+        activity::record(&data, CardActivity::new(ActivityKind::Earned, &card, earned).reason(&reason).staff(&admin)).await;
+        _ = data.card_updates.send(CardNotification::new(user_id, CardEvent::Earned, &card));
+    }
+
+    info!("Card '{}' earned {} points from {} by admin", card.user_id(), points, request.spend);
+    Ok(HttpResponse::Ok().json(card_response(&card, Some(programme))))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/points/{id}/redeem",
+    tag = "points",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    request_body = SpendRequest,
+    responses(
+        (status = 200, description = "The card with the reward's cost taken off", body = CardResponse),
+        (status = 400, description = "The card is not on a points programme or cannot afford the reward", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such reward in the card's programme", body = ErrorResponse)
+    )
+)]
+pub async fn redeem_points(admin: Admin, path: web::Path<String>, request: web::Json<SpendRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = UserId(path.into_inner());
+
+    let mut tracker = data.points.lock().await;
+    let card = tracker.get_or_create_card(&user_id).await?;
+    let programme = points_programme(&data, card.programme_id.as_deref()).await?;
+    let reward = programme.reward(&request.reward_id).cloned().ok_or(ApiError::NotFound("Reward"))?;
+
+    let Some(card) = card.with_spent(reward.cost)
+        else {
+            return Err(ApiError::Validation(format!("This card has {} points but {} costs {}", card.points, reward.name, reward.cost)))
+        };
+    tracker.save_card(&card).await?;
+    drop(tracker);
+
+    activity::record(&data, CardActivity::new(ActivityKind::Redeemed, &card, -i32::try_from(reward.cost).unwrap_or(i32::MAX)).reason(&reward.name).staff(&admin)).await;
+    _ = data.card_updates.send(CardNotification::new(user_id, CardEvent::Redeemed, &card));
+
+    info!("Card '{}' spent {} points on {} by admin", card.user_id(), reward.cost, reward.reward_id);
+    Ok(HttpResponse::Ok().json(card_response(&card, Some(programme))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_are_earned_per_whole_unit_spent() {
+        assert_eq!(points_for(450, 10), 45);
+        assert_eq!(points_for(99, 1), 0);
+        assert_eq!(points_for(u32::MAX, u32::MAX), u32::MAX);
+    }
+
+    #[test]
+    fn points_can_only_be_spent_when_the_card_can_afford_it() {
+        let card = PointsCard::new(UserId("07715559999".into())).with_earned(120);
+
+        let spent = card.with_spent(50).unwrap();
+        assert_eq!((spent.points, spent.total_points, spent.redemptions), (70, 120, 1));
+        assert!(spent.with_spent(71).is_none());
+    }
+}
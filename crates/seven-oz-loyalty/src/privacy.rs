@@ -0,0 +1,226 @@
+use std::time::{Duration, Instant};
+
+use actix_web::{HttpResponse, web};
+use actix_web::dev::ConnectionInfo;
+use log::{info, warn};
+
+use loyalty_core::api::v1::{ActivityResponse, ApproveDataRequest, AuditRecord, DataRequest, DataRequestResponse, ErasureResponse, ErrorResponse, PersonalDataResponse, ReferralResponse};
+use loyalty_core::qr_gen::rand_string;
+use loyalty_core::{PhoneNumber, UserId};
+
+use crate::AppData;
+use crate::audit::{AuditAction, AuditEntry};
+use crate::auth::{Admin, VerifiedCustomer};
+use crate::customer_code::rate_limited;
+use crate::devices::hash_key;
+use crate::error::ApiError;
+use crate::rate_limit::LimitExceeded;
+use crate::export::{points_record, stamp_record};
+
+const DATA_REQUEST_TTL: Duration = Duration::from_secs(30 * 60);
+const APPROVAL_CODE_LENGTH: usize = 6;
+const TOKEN_LENGTH: usize = 40;
+/// Open requests for one phone number, so each of a customer's devices can ask without cancelling the others.
+const MAX_OPEN_PER_CARD: usize = 3;
+// requests are kept in memory so stop a flood of them using it all up
+const MAX_OPEN_REQUESTS: usize = 10_000;
+
+/// A customer's request for their own data, usable once staff have checked they hold the phone.
+pub struct PendingDataRequest {
+    pub user_id: UserId,
+    code: String,
+    pub approved: bool,
+    expires: Instant,
+}
+
+impl PendingDataRequest {
+    pub fn is_expired(&self) -> bool {
+        self.expires <= Instant::now()
+    }
+}
+
+async fn record_audit(data: &AppData, entry: AuditEntry) {
+    if let Err(err) = data.audit.lock().await.record(entry).await {
+        warn!("Failed to write audit entry: {}", err);
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    post,
+    path = "/api/privacy/requests",
+    tag = "privacy",
+    request_body = DataRequest,
+    responses(
+        (status = 200, description = "A token for the customer and a code for staff to approve", body = DataRequestResponse),
+        (status = 400, description = "Invalid phone number", body = ErrorResponse),
+        (status = 429, description = "Too many requests from the address or for the number", body = ErrorResponse)
+    )
+)]
+pub async fn request_data(request: web::Json<DataRequest>, data: AppData, conn: ConnectionInfo) -> Result<HttpResponse, ApiError> {
+    // kept under the bare digits, as claims are, so typing the number differently is not a new customer
+    let user_id = PhoneNumber::try_from(request.user_id.as_str())
+        .map(UserId::from)
+        .map_err(|message| ApiError::Validation(message.to_string()))?;
+
+    // counted with claims so asking for data is no cheaper than guessing codes
+    let mut limiter = data.limiter.lock().await;
+    let ip = limiter.config.client_address(&conn);
+    if let Err(limit) = limiter.check_attempt(&ip, &user_id) {
+        return Err(rate_limited(&data, limiter, limit, &user_id, &ip).await)
+    }
+    drop(limiter);
+
+    // a request is never replaced, it may belong to someone else who knows the number
+    let mut requests = data.data_requests.lock().await;
+    requests.retain(|_, pending| !pending.is_expired());
+    let open = requests.values().filter(|pending| pending.user_id == user_id).count();
+    if open >= MAX_OPEN_PER_CARD || requests.len() >= MAX_OPEN_REQUESTS {
+        warn!("Refused a data request for card {} with {} open and {} in total", user_id, open, requests.len());
+        return Err(ApiError::RateLimited { reason: LimitExceeded::User(user_id), retry_after: DATA_REQUEST_TTL })
+    }
+
+    let token = rand_string(TOKEN_LENGTH);
+    let code = rand_string(APPROVAL_CODE_LENGTH).to_uppercase();
+    requests.insert(hash_key(&token), PendingDataRequest {
+        user_id,
+        code: code.clone(),
+        approved: false,
+        expires: Instant::now() + DATA_REQUEST_TTL
+    });
+
+    Ok(HttpResponse::Ok().json(DataRequestResponse {
+        token,
+        code,
+        expires_in: DATA_REQUEST_TTL.as_secs()
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/privacy/approve",
+    tag = "privacy",
+    security(("admin_key" = [])),
+    request_body = ApproveDataRequest,
+    responses(
+        (status = 200, description = "The customer can now see and erase their data"),
+        (status = 400, description = "No open request matches the phone number and code", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn approve_data_request(_: Admin, request: web::Json<ApproveDataRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = PhoneNumber::try_from(request.user_id.as_str()).map(UserId::from).ok();
+    let code = request.code.trim().to_uppercase();
+
+    let mut requests = data.data_requests.lock().await;
+    let Some(pending) = requests.values_mut()
+        .find(|pending| user_id.as_ref() == Some(&pending.user_id) && pending.code == code && !pending.is_expired())
+        else {
+            warn!("Rejected approval of an unknown data request");
+            return Err(ApiError::Validation(String::from("No open request matches that phone number and code")))
+        };
+
+    pending.approved = true;
+    info!("Data request approved");
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/privacy/data",
+    tag = "privacy",
+    security(("data_request" = [])),
+    responses(
+        (status = 200, description = "Everything stored about the customer", body = PersonalDataResponse),
+        (status = 401, description = "Unknown or expired request", body = ErrorResponse),
+        (status = 403, description = "The request has not been approved yet", body = ErrorResponse)
+    )
+)]
+pub async fn personal_data(VerifiedCustomer(user_id): VerifiedCustomer, data: AppData) -> Result<HttpResponse, ApiError> {
+    let card = data.cards.lock().await.find_card(&user_id).await?;
+    let points = data.points.lock().await.find_card(&user_id).await?;
+    let history = data.activity.lock().await.for_card(&user_id, i64::MAX).await?;
+    let audit = data.audit.lock().await.for_user(&user_id).await?;
+    let referrals = data.referrals.lock().await.for_user(&user_id).await?;
+
+    // a customer with both cards has one history, which goes with their stamp card
+    let history: Vec<ActivityResponse> = history.into_iter().rev().map(ActivityResponse::from).collect();
+    let (card, points_card) = match card {
+        Some(card) => (Some(stamp_record(&card, history)), points.map(|card| points_record(&card, Vec::new()))),
+        None => (None, points.map(|card| points_record(&card, history)))
+    };
+
+    record_audit(&data, AuditEntry::new(AuditAction::DataAccessed, "customer downloaded their data").user(&user_id)).await;
+
+    Ok(HttpResponse::Ok().json(PersonalDataResponse {
+        user_id: user_id.to_string(),
+        points: points_card.as_ref().map(|card| card.stamps),
+        card,
+        points_card,
+        referrals: referrals.into_iter().map(ReferralResponse::from).collect(),
+        audit: audit.into_iter().map(AuditRecord::from).collect()
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/privacy/erase",
+    tag = "privacy",
+    security(("data_request" = [])),
+    responses(
+        (status = 200, description = "The customer's cards are deleted and their activity kept without the phone number", body = ErasureResponse),
+        (status = 401, description = "Unknown or expired request", body = ErrorResponse),
+        (status = 403, description = "The request has not been approved yet", body = ErrorResponse)
+    )
+)]
+pub async fn erase_data(VerifiedCustomer(user_id): VerifiedCustomer, data: AppData) -> Result<HttpResponse, ApiError> {
+    // a fresh pseudonym per erasure keeps one customer's visits together for the reports without saying who they were
+    let pseudonym = UserId(format!("erased-{}", rand_string(12)));
+
+    let card_deleted = data.cards.lock().await.delete_card(&user_id).await?;
+    let card_deleted = data.points.lock().await.delete_card(&user_id).await? || card_deleted;
+    let activity_anonymised = data.activity.lock().await.anonymise(&user_id, &pseudonym).await?;
+    let audit_anonymised = data.audit.lock().await.anonymise(&user_id).await?;
+    let referrals_anonymised = data.referrals.lock().await.anonymise(&user_id, &pseudonym).await?;
+    data.limiter.lock().await.forget(&user_id);
+    data.data_requests.lock().await.retain(|_, pending| pending.user_id != user_id);
+
+    let detail = format!("customer data erased, card deleted: {}, activity anonymised: {}, audit entries anonymised: {}, referrals anonymised: {}",
+                         card_deleted, activity_anonymised, audit_anonymised, referrals_anonymised);
+    record_audit(&data, AuditEntry::new(AuditAction::DataErased, detail)).await;
+
+    Ok(HttpResponse::Ok().json(ErasureResponse {
+        card_deleted,
+        activity_anonymised,
+        audit_anonymised
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+
+    use super::*;
+
+    fn request(user_id: &str) -> TestRequest {
+        TestRequest::post().uri("/api/privacy/requests").set_json(DataRequest { user_id: user_id.to_string() })
+    }
+
+    #[actix_web::test]
+    async fn requests_for_a_number_are_limited_but_never_replaced() {
+        let data = crate::test_state(None);
+        let app = init_service(App::new().app_data(data.clone()).service(Scope::new("/api").configure(crate::api_routes))).await;
+
+        for _ in 0..MAX_OPEN_PER_CARD {
+            assert_eq!(call_service(&app, request("07700900001").to_request()).await.status(), StatusCode::OK);
+        }
+        assert_eq!(call_service(&app, request("07700900001").to_request()).await.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(data.data_requests.lock().await.len(), MAX_OPEN_PER_CARD);
+
+        // another number from another address is unaffected
+        let other = request("07700900002").peer_addr("10.0.0.2:1234".parse().unwrap()).to_request();
+        assert_eq!(call_service(&app, other).await.status(), StatusCode::OK);
+    }
+}
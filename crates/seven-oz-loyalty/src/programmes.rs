@@ -0,0 +1,260 @@
+use actix_web::{HttpResponse, web};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{BonusRules, CardKind, CatalogueReward, ErrorResponse, ExpiryPolicy, ProgrammeDetails, StoreDetails, Tier};
+use loyalty_core::StoreId;
+
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use crate::tiers;
+
+/// A loyalty scheme the business runs, such as a coffee card.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Programme {
+    pub programme_id: String,
+    name: String,
+    reward: String,
+    #[serde(default)]
+    pub expiry: Option<ExpiryPolicy>,
+    #[serde(default)]
+    pub bonuses: BonusRules,
+    /// Lowest first.
+    #[serde(default)]
+    pub tiers: Vec<Tier>,
+    #[serde(default)]
+    pub kind: CardKind,
+    /// Points per whole unit of currency spent, always set on a points programme.
+    #[serde(default)]
+    pub points_rate: Option<u32>,
+    #[serde(default)]
+    pub catalogue: Vec<CatalogueReward>,
+}
+
+impl Programme {
+    pub fn reward(&self, reward_id: &str) -> Option<&CatalogueReward> {
+        self.catalogue.iter().find(|reward| reward.reward_id == reward_id)
+    }
+}
+
+/// A shop that displays codes, optionally tied to the programme its stamps count towards.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Store {
+    pub store_id: StoreId,
+    name: String,
+    pub programme_id: Option<String>,
+}
+
+impl From<Programme> for ProgrammeDetails {
+    fn from(programme: Programme) -> Self {
+        ProgrammeDetails {
+            programme_id: programme.programme_id,
+            name: programme.name,
+            reward: programme.reward,
+            expiry: programme.expiry,
+            bonuses: programme.bonuses,
+            tiers: programme.tiers,
+            kind: programme.kind,
+            points_rate: programme.points_rate,
+            catalogue: programme.catalogue
+        }
+    }
+}
+
+impl From<Store> for StoreDetails {
+    fn from(store: Store) -> Self {
+        StoreDetails {
+            store_id: store.store_id,
+            name: store.name,
+            programme_id: store.programme_id
+        }
+    }
+}
+
+/// The catalogue with ids and names trimmed, rejecting free or unnamed rewards and repeated ids.
+fn catalogue(rewards: &[CatalogueReward]) -> Result<Vec<CatalogueReward>, ApiError> {
+    let mut catalogue: Vec<CatalogueReward> = Vec::new();
+    for reward in rewards {
+        let reward = CatalogueReward {
+            reward_id: required(&reward.reward_id, "reward id")?,
+            name: required(&reward.name, "reward name")?,
+            cost: reward.cost
+        };
+        if reward.cost == 0 {
+            return Err(ApiError::Validation(format!("The {} reward must cost something", reward.name)))
+        }
+        if catalogue.iter().any(|other| other.reward_id == reward.reward_id) {
+            return Err(ApiError::Validation(format!("The reward id {} is used more than once", reward.reward_id)))
+        }
+        catalogue.push(reward);
+    }
+    Ok(catalogue)
+}
+
+pub fn required(value: &str, field: &str) -> Result<String, ApiError> {
+    let value = value.trim();
+    match value.is_empty() {
+        true => Err(ApiError::Validation(format!("A {} is required", field))),
+        false => Ok(value.to_string())
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/admin/programmes",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Every programme", body = Vec<ProgrammeDetails>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_programmes(_: Admin, data: AppData) -> Result<HttpResponse, ApiError> {
+    let programmes = data.programmes.lock().await.list_programmes().await?;
+
+    let response: Vec<ProgrammeDetails> = programmes.into_iter().map(ProgrammeDetails::from).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/programmes",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    request_body = ProgrammeDetails,
+    responses(
+        (status = 200, description = "The programme has been created or replaced", body = ProgrammeDetails),
+        (status = 400, description = "A required field is missing, the expiry period is zero or a bonus gives nothing, the tiers overlap, a points programme has no rate or a reward is free", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn save_programme(_: Admin, request: web::Json<ProgrammeDetails>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let programme = Programme {
+        programme_id: required(&request.programme_id, "programme id")?,
+        name: required(&request.name, "name")?,
+        reward: required(&request.reward, "reward")?,
+        expiry: request.expiry,
+        bonuses: request.bonuses.clone(),
+        tiers: tiers::checked(&request.tiers)?,
+        kind: request.kind,
+        points_rate: request.points_rate.filter(|_| request.kind == CardKind::Points),
+        catalogue: catalogue(&request.catalogue)?
+    };
+    if programme.kind == CardKind::Points && programme.points_rate.unwrap_or_default() == 0 {
+        return Err(ApiError::Validation(String::from("A points programme must give at least one point per unit spent")))
+    }
+    if let Some(ExpiryPolicy::StampAge { months: 0 } | ExpiryPolicy::Inactivity { months: 0 }) = programme.expiry {
+        return Err(ApiError::Validation(String::from("Stamps must be kept for at least a month")))
+    }
+    if programme.bonuses.birthday == Some(0) || programme.bonuses.milestones.iter().any(|milestone| milestone.cards == 0 || milestone.stamps == 0) {
+        return Err(ApiError::Validation(String::from("Bonuses must give at least one stamp and milestones start from the first card")))
+    }
+
+    data.programmes.lock().await.save_programme(&programme).await?;
+
+    info!("Saved programme {}", programme.programme_id);
+    Ok(HttpResponse::Ok().json(ProgrammeDetails::from(programme)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/programmes/{programme_id}",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    params(("programme_id" = String, Path, description = "The programme to delete")),
+    responses(
+        (status = 200, description = "The programme has been deleted"),
+        (status = 400, description = "A store still uses the programme", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such programme", body = ErrorResponse)
+    )
+)]
+pub async fn delete_programme(_: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let programme_id = path.into_inner();
+
+    let stores = data.stores.lock().await.list_stores().await?;
+    if let Some(store) = stores.iter().find(|store| store.programme_id.as_deref() == Some(programme_id.as_str())) {
+        return Err(ApiError::Validation(format!("Store {} still uses this programme", store.store_id)))
+    }
+
+    match data.programmes.lock().await.delete_programme(&programme_id).await? {
+        true => Ok(HttpResponse::Ok().finish()),
+        false => Err(ApiError::NotFound("Programme"))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/stores",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "Every store", body = Vec<StoreDetails>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_stores(_: Admin, data: AppData) -> Result<HttpResponse, ApiError> {
+    let stores = data.stores.lock().await.list_stores().await?;
+
+    let response: Vec<StoreDetails> = stores.into_iter().map(StoreDetails::from).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/stores",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    request_body = StoreDetails,
+    responses(
+        (status = 200, description = "The store has been created or replaced", body = StoreDetails),
+        (status = 400, description = "A required field is missing or the programme does not exist", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn save_store(_: Admin, request: web::Json<StoreDetails>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let programme_id = request.programme_id.as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string);
+
+    if let Some(programme_id) = &programme_id {
+        if data.programmes.lock().await.find_programme(programme_id).await?.is_none() {
+            return Err(ApiError::Validation(format!("There is no programme called {}", programme_id)))
+        }
+    }
+
+    let store = Store {
+        store_id: StoreId(required(&request.store_id.0, "store id")?),
+        name: required(&request.name, "name")?,
+        programme_id
+    };
+
+    data.stores.lock().await.save_store(&store).await?;
+
+    info!("Saved store {}", store.store_id);
+    Ok(HttpResponse::Ok().json(StoreDetails::from(store)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/stores/{store_id}",
+    tag = "stores and programmes",
+    security(("admin_key" = [])),
+    params(("store_id" = String, Path, description = "The store to delete")),
+    responses(
+        (status = 200, description = "The store has been deleted"),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such store", body = ErrorResponse)
+    )
+)]
+pub async fn delete_store(_: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let store_id = StoreId(path.into_inner());
+
+    match data.stores.lock().await.delete_store(&store_id).await? {
+        true => Ok(HttpResponse::Ok().finish()),
+        false => Err(ApiError::NotFound("Store"))
+    }
+}
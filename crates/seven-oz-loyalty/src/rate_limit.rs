@@ -0,0 +1,219 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use actix_web::dev::ConnectionInfo;
+use shuttle_runtime::SecretStore;
+use thiserror::Error;
+
+use loyalty_core::UserId;
+
+// stop the attempt maps growing forever when lots of different callers show up
+const PRUNE_THRESHOLD: usize = 10_000;
+
+pub struct RateLimitConfig {
+    /// Claim attempts allowed from one address within `window`.
+    pub per_ip: usize,
+    /// Claim attempts allowed for one card within `window`.
+    pub per_user: usize,
+    pub window: Duration,
+    /// Shortest time allowed between two stamps on the same card.
+    pub min_stamp_gap: Duration,
+    /// The address of a reverse proxy whose forwarded headers can be believed, anyone else could make them up.
+    pub trusted_proxy: Option<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            per_ip: 10,
+            per_user: 5,
+            window: Duration::from_secs(60),
+            min_stamp_gap: Duration::from_secs(5 * 60),
+            trusted_proxy: None
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads any overrides from the secrets file, falling back to the defaults.
+    pub fn from_secrets(secrets: &SecretStore) -> Self {
+        let default = Self::default();
+        let get = |key: &str| secrets.get(key).and_then(|value| value.parse::<u64>().ok());
+
+        RateLimitConfig {
+            per_ip: get("CLAIM_LIMIT_PER_IP").map_or(default.per_ip, |v| v as usize),
+            per_user: get("CLAIM_LIMIT_PER_USER").map_or(default.per_user, |v| v as usize),
+            window: get("CLAIM_LIMIT_WINDOW_SECS").map_or(default.window, Duration::from_secs),
+            min_stamp_gap: get("MIN_STAMP_GAP_SECS").map_or(default.min_stamp_gap, Duration::from_secs),
+            trusted_proxy: secrets.get("TRUSTED_PROXY")
+        }
+    }
+
+    /// The address a request is limited by, taken from forwarded headers only when they came through the trusted proxy.
+    pub fn client_address(&self, conn: &ConnectionInfo) -> String {
+        let peer = conn.peer_addr().unwrap_or("unknown");
+        match &self.trusted_proxy {
+            Some(proxy) if proxy == peer => conn.realip_remote_addr().unwrap_or(peer).to_string(),
+            _ => peer.to_string()
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum LimitExceeded {
+    #[error("too many claim attempts from {0}")]
+    Ip(String),
+    #[error("too many claim attempts for card {0}")]
+    User(UserId),
+    #[error("card {0} was stamped too recently")]
+    StampTooSoon(UserId, Duration)
+}
+
+impl LimitExceeded {
+    /// How long the caller should wait before trying again.
+    pub fn retry_after(&self, config: &RateLimitConfig) -> Duration {
+        match self {
+            LimitExceeded::Ip(_) | LimitExceeded::User(_) => config.window,
+            LimitExceeded::StampTooSoon(_, wait) => *wait
+        }
+    }
+}
+
+pub struct ClaimLimiter {
+    pub config: RateLimitConfig,
+    ip_attempts: HashMap<String, VecDeque<Instant>>,
+    user_attempts: HashMap<UserId, VecDeque<Instant>>,
+    last_stamp: HashMap<UserId, Instant>,
+}
+
+impl ClaimLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        ClaimLimiter {
+            config,
+            ip_attempts: HashMap::new(),
+            user_attempts: HashMap::new(),
+            last_stamp: HashMap::new()
+        }
+    }
+
+    /// Counts a claim attempt, failing if either the address or the card has made too many recently.
+    pub fn check_attempt(&mut self, ip: &str, user_id: &UserId) -> Result<(), LimitExceeded> {
+        let now = Instant::now();
+        let window = self.config.window;
+
+        if !record_attempt(&mut self.ip_attempts, ip.to_string(), now, window, self.config.per_ip) {
+            return Err(LimitExceeded::Ip(ip.to_string()));
+        }
+        if !record_attempt(&mut self.user_attempts, user_id.clone(), now, window, self.config.per_user) {
+            return Err(LimitExceeded::User(user_id.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn check_stamp_gap(&self, user_id: &UserId) -> Result<(), LimitExceeded> {
+        match self.last_stamp.get(user_id) {
+            Some(last) if last.elapsed() < self.config.min_stamp_gap => {
+                let wait = self.config.min_stamp_gap - last.elapsed();
+                Err(LimitExceeded::StampTooSoon(user_id.clone(), wait))
+            },
+            _ => Ok(())
+        }
+    }
+
+    /// Drops everything held about a card, used when its owner asks to be forgotten.
+    pub fn forget(&mut self, user_id: &UserId) {
+        self.user_attempts.remove(user_id);
+        self.last_stamp.remove(user_id);
+    }
+
+    /// Takes back a stamp that was recorded but could not be given, so the customer can try again straight away.
+    pub fn forget_stamp(&mut self, user_id: &UserId) {
+        self.last_stamp.remove(user_id);
+    }
+
+    pub fn record_stamp(&mut self, user_id: &UserId) {
+        if self.last_stamp.len() > PRUNE_THRESHOLD {
+            let gap = self.config.min_stamp_gap;
+            self.last_stamp.retain(|_, last| last.elapsed() < gap);
+        }
+        self.last_stamp.insert(user_id.clone(), Instant::now());
+    }
+}
+
+/// Adds an attempt to the sliding window for `key`, returning false if the limit was already reached.
+fn record_attempt<K: Hash + Eq>(attempts: &mut HashMap<K, VecDeque<Instant>>, key: K, now: Instant, window: Duration, limit: usize) -> bool {
+    if attempts.len() > PRUNE_THRESHOLD {
+        attempts.retain(|_, times| times.back().is_some_and(|last| now.duration_since(*last) < window));
+    }
+
+    let times = attempts.entry(key).or_default();
+    while times.front().is_some_and(|first| now.duration_since(*first) >= window) {
+        times.pop_front();
+    }
+
+    if times.len() >= limit {
+        return false;
+    }
+    times.push_back(now);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(min_stamp_gap: Duration) -> ClaimLimiter {
+        ClaimLimiter::new(RateLimitConfig { per_ip: 3, per_user: 2, window: Duration::from_secs(60), min_stamp_gap, trusted_proxy: None })
+    }
+
+    #[test]
+    fn attempts_slide_out_of_the_window() {
+        let (start, window) = (Instant::now(), Duration::from_secs(60));
+        let mut attempts: HashMap<&str, VecDeque<Instant>> = HashMap::new();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert!(record_attempt(&mut attempts, "a", at(0), window, 2));
+        assert!(record_attempt(&mut attempts, "a", at(30), window, 2));
+        assert!(!record_attempt(&mut attempts, "a", at(59), window, 2));
+        assert!(record_attempt(&mut attempts, "b", at(59), window, 2));
+        // the first attempt has left the window but the second and a refused one have not
+        assert!(record_attempt(&mut attempts, "a", at(60), window, 2));
+        assert!(!record_attempt(&mut attempts, "a", at(89), window, 2));
+        assert!(record_attempt(&mut attempts, "a", at(90), window, 2));
+        assert_eq!(attempts["a"].len(), 2);
+    }
+
+    #[test]
+    fn cards_and_addresses_are_limited_separately() {
+        let mut limiter = limiter(Duration::ZERO);
+        let (alice, bob) = (UserId("07700900001".into()), UserId("07700900002".into()));
+
+        assert_eq!(limiter.check_attempt("10.0.0.1", &alice), Ok(()));
+        assert_eq!(limiter.check_attempt("10.0.0.2", &alice), Ok(()));
+        assert_eq!(limiter.check_attempt("10.0.0.3", &alice), Err(LimitExceeded::User(alice.clone())));
+        assert_eq!(limiter.check_attempt("10.0.0.1", &bob), Ok(()));
+        assert_eq!(limiter.check_attempt("10.0.0.1", &bob), Ok(()));
+        assert_eq!(limiter.check_attempt("10.0.0.1", &bob), Err(LimitExceeded::Ip("10.0.0.1".into())));
+
+        limiter.forget(&alice);
+        assert_eq!(limiter.check_attempt("10.0.0.4", &alice), Ok(()));
+    }
+
+    #[test]
+    fn stamps_need_a_gap() {
+        let mut limiter = limiter(Duration::from_secs(300));
+        let card = UserId("07700900001".into());
+
+        assert_eq!(limiter.check_stamp_gap(&card), Ok(()));
+        limiter.record_stamp(&card);
+        let Err(LimitExceeded::StampTooSoon(_, wait)) = limiter.check_stamp_gap(&card)
+            else { panic!("a second stamp straight away should be refused") };
+        assert!(wait > Duration::from_secs(299) && wait <= Duration::from_secs(300));
+        assert_eq!(LimitExceeded::StampTooSoon(card.clone(), wait).retry_after(&limiter.config), wait);
+
+        limiter.forget(&card);
+        assert_eq!(limiter.check_stamp_gap(&card), Ok(()));
+        assert_eq!(self::limiter(Duration::ZERO).check_stamp_gap(&card), Ok(()));
+    }
+}
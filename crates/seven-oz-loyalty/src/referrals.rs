@@ -0,0 +1,169 @@
+use actix_web::{HttpResponse, web};
+use log::{info, warn};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ActivityKind, CardEvent, ErrorResponse, ReferralLinkResponse, ReferralResponse};
+use loyalty_core::qr_gen::rand_string;
+use loyalty_core::{PhoneNumber, UserId};
+
+use crate::activity::{self, CardActivity};
+use crate::AppData;
+use crate::auth::Admin;
+use crate::db::StampCardRepositoryError;
+use crate::devices::hash_key;
+use crate::error::ApiError;
+use crate::stampcard::CardNotification;
+
+const REFERRAL_CODE_LENGTH: usize = 8;
+// codes come back in imports, so allow a little more than is generated
+const MAX_CODE_LENGTH: usize = 16;
+/// Stamps given to both the new customer and whoever referred them.
+const REFERRAL_BONUS: u32 = 1;
+const LIST_LIMIT: i64 = 100;
+
+/// A new customer's first claim made through someone else's referral link.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Referral {
+    pub timestamp: DateTime,
+    pub referrer: UserId,
+    pub referred: UserId, // each customer can only ever be referred once
+    /// Outlasts `referred` being erased, so the same number cannot be referred again afterwards.
+    #[serde(default)]
+    pub referred_hash: Option<String>,
+    pub bonus: u32,
+}
+
+impl From<Referral> for ReferralResponse {
+    fn from(referral: Referral) -> Self {
+        ReferralResponse {
+            referrer: referral.referrer.to_string(),
+            referred: referral.referred.to_string(),
+            bonus: referral.bonus,
+            timestamp: referral.timestamp.try_to_rfc3339_string().unwrap_or_default()
+        }
+    }
+}
+
+/// A referral code as it is stored, or nothing when it could not be one.
+pub fn normalised_code(code: &str) -> Option<String> {
+    let code = code.trim().to_uppercase();
+    let valid = !code.is_empty() && code.len() <= MAX_CODE_LENGTH && code.chars().all(|c| c.is_ascii_alphanumeric());
+    valid.then_some(code)
+}
+
+/// A one-way stand-in for a referred number, made from its bare digits so any way of typing it matches.
+pub fn referred_hash(user_id: &UserId) -> String {
+    let digits = PhoneNumber::try_from(user_id.0.as_str()).map(UserId::from).unwrap_or_else(|_| user_id.clone());
+    hash_key(&digits.0)
+}
+
+/// Gives both customers their bonus without failing the claim, by now the stamp itself has been given.
+pub async fn reward(data: &AppData, referred: &UserId, code: &str) {
+    if let Err(err) = try_reward(data, referred, code).await {
+        warn!("Failed to reward referral of card {}: {}", referred, err);
+    }
+}
+
+async fn try_reward(data: &AppData, referred: &UserId, code: &str) -> Result<(), StampCardRepositoryError> {
+    let referrer = match normalised_code(code) {
+        Some(code) => data.cards.lock().await.find_by_referral_code(&code).await?,
+        None => None
+    };
+    let Some(referrer) = referrer
+        else {
+            info!("Card {} claimed with unknown referral code '{}'", referred, code);
+            return Ok(())
+        };
+    let referrer = referrer.user_id().clone();
+
+    // held until the referral is recorded so two claims at once cannot both count
+    let mut referrals = data.referrals.lock().await;
+    if referrer == *referred || referrals.was_referred(referred).await? {
+        return Ok(())
+    }
+    referrals.record(&Referral {
+        timestamp: DateTime::now(),
+        referrer: referrer.clone(),
+        referred: referred.clone(),
+        referred_hash: Some(referred_hash(referred)),
+        bonus: REFERRAL_BONUS
+    }).await?;
+    drop(referrals);
+
+    for (user_id, reason) in [(referred, "Referred by a friend"), (&referrer, "Referred a friend")] {
+        let mut tracker = data.cards.lock().await;
+        let previous = tracker.get_or_create_card(user_id).await?;
+        let card = tracker.adjust_card(user_id, REFERRAL_BONUS as i32).await?;
+        drop(tracker);
+        // a full card takes no more, so only what was added is recorded
+        let added = card.stamps as i32 - previous.stamps as i32;
+        activity::record(data, CardActivity::new(ActivityKind::Referral, &card, added).reason(reason)).await;
+        _ = data.card_updates.send(CardNotification::new(user_id.clone(), CardEvent::Referral, &card));
+    }
+
+    info!("Card {} was referred by card {}", referred, referrer);
+    Ok(())
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/stampcard/{id}/referral",
+    tag = "referrals",
+    params(("id" = String, Path, description = "The customer's phone number")),
+    responses(
+        (status = 200, description = "The code for the customer's referral link, created the first time it is asked for", body = ReferralLinkResponse)
+    )
+)]
+pub async fn referral_link(path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = UserId(path.into_inner());
+
+    let mut tracker = data.cards.lock().await;
+    let card = tracker.get_or_create_card(&user_id).await?;
+    if let Some(code) = card.referral_code() {
+        return Ok(HttpResponse::Ok().json(ReferralLinkResponse { code: code.to_string() }))
+    }
+
+    let code = loop {
+        let code = rand_string(REFERRAL_CODE_LENGTH).to_uppercase();
+        if tracker.find_by_referral_code(&code).await?.is_none() {
+            break code
+        }
+    };
+    tracker.save_card(&card.with_referral_code(code.clone())).await?;
+
+    Ok(HttpResponse::Ok().json(ReferralLinkResponse { code }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/referrals",
+    tag = "referrals",
+    security(("admin_key" = [])),
+    responses(
+        (status = 200, description = "The most recent referrals, newest first", body = Vec<ReferralResponse>),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn list_referrals(_: Admin, data: AppData) -> Result<HttpResponse, ApiError> {
+    let referrals = data.referrals.lock().await.recent(LIST_LIMIT).await?;
+
+    let response: Vec<ReferralResponse> = referrals.into_iter().map(ReferralResponse::from).collect();
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_referred_number_hashes_the_same_however_it_is_typed() {
+        let hash = referred_hash(&UserId("07715559999".into()));
+
+        assert_eq!(referred_hash(&UserId("07715 559999".into())), hash);
+        assert_eq!(referred_hash(&UserId("07715-559-999".into())), hash);
+        assert_ne!(referred_hash(&UserId("07715559998".into())), hash);
+        assert!(!hash.contains("07715559999"));
+    }
+}
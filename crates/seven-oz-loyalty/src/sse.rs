@@ -0,0 +1,97 @@
+use std::future::{ready, Future};
+use std::time::Duration;
+
+use actix_web::HttpResponse;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::web::Bytes;
+use futures_util::{stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{timeout, Instant};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
+pub fn event<T: Serialize>(payload: &T) -> Bytes {
+    let json = serde_json::to_string(payload).unwrap();
+    Bytes::from(format!("data: {}\n\n", json))
+}
+
+/// Builds a server-sent event response that starts with `initial` and then forwards every
+/// broadcast update that `select` turns into an event.
+pub fn event_stream<T, F>(initial: Bytes, updates: broadcast::Receiver<T>, select: F) -> HttpResponse
+where
+    T: Clone + Send + 'static,
+    F: Fn(T) -> Option<Bytes> + Clone + 'static,
+{
+    guarded_event_stream(initial, updates, select, || ready(true))
+}
+
+/// Like `event_stream`, but asks `allowed` again at least every keep-alive interval and ends the
+/// stream once it says no, so access taken away after the stream opened does not last with it.
+pub fn guarded_event_stream<T, F, A, Fut>(initial: Bytes, updates: broadcast::Receiver<T>, select: F, allowed: A) -> HttpResponse
+where
+    T: Clone + Send + 'static,
+    F: Fn(T) -> Option<Bytes> + Clone + 'static,
+    A: Fn() -> Fut + Clone + 'static,
+    Fut: Future<Output = bool>,
+{
+    let events = stream::unfold((updates, Instant::now()), move |(mut updates, mut checked)| {
+        let (select, allowed) = (select.clone(), allowed.clone());
+        async move {
+            loop {
+                // checked by the clock rather than on keep-alives, which a busy channel never sends
+                if checked.elapsed() >= KEEP_ALIVE_INTERVAL {
+                    if !allowed().await {
+                        return None
+                    }
+                    checked = Instant::now();
+                }
+
+                match timeout(KEEP_ALIVE_INTERVAL, updates.recv()).await {
+                    Ok(Ok(update)) => match select(update) {
+                        Some(event) => return Some((event, (updates, checked))),
+                        None => continue
+                    },
+                    // the latest update may be among those missed, so end the stream and let the
+                    // client reconnect for a fresh snapshot rather than show a stale one
+                    Ok(Err(RecvError::Lagged(_))) => return None,
+                    Ok(Err(RecvError::Closed)) => return None,
+                    // a comment line stops proxies from closing an idle connection
+                    Err(_) => return Some((Bytes::from_static(b": keep-alive\n\n"), (updates, checked)))
+                }
+            }
+        }
+    });
+
+    let body = stream::once(async move { initial })
+        .chain(events)
+        .map(Ok::<_, actix_web::Error>);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn a_lagging_stream_ends_for_the_client_to_resync() {
+        let (sender, updates) = broadcast::channel(1);
+        for update in 0..3 {
+            _ = sender.send(update);
+        }
+
+        let response = event_stream(event(&"snapshot"), updates, |update: i32| Some(event(&update)));
+        let body = timeout(Duration::from_secs(1), to_bytes(response.into_body())).await
+            .expect("the stream should have ended")
+            .unwrap();
+
+        assert_eq!(body, event(&"snapshot"));
+    }
+}
@@ -0,0 +1,302 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use actix_web::http::header;
+use log::info;
+use mongodb::bson::{Binary, DateTime};
+use mongodb::bson::spec::BinarySubtype;
+use serde::{Deserialize, Serialize};
+
+use loyalty_core::api::v1::{ErrorResponse, StampSlot, ThemeDetails};
+
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+
+pub const IMAGE_LIMIT: usize = 256 * 1024;
+const SVG: &str = "image/svg+xml";
+const PNG: &str = "image/png";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Elements that only draw, compared in lower case.
+const ELEMENTS: [&str; 21] = [
+    "svg", "g", "defs", "symbol", "use", "title", "desc", "path", "circle", "ellipse", "line", "polyline", "polygon",
+    "rect", "text", "tspan", "lineargradient", "radialgradient", "stop", "clippath", "mask"
+];
+
+/// Attributes that only place and paint shapes, so no event handlers, styles or outside links.
+const ATTRIBUTES: [&str; 57] = [
+    "xmlns", "xmlns:xlink", "version", "id", "class", "viewbox", "preserveaspectratio", "width", "height", "x", "y",
+    "x1", "y1", "x2", "y2", "cx", "cy", "r", "rx", "ry", "fx", "fy", "d", "points", "pathlength", "transform", "fill",
+    "fill-opacity", "fill-rule", "stroke", "stroke-width", "stroke-linecap", "stroke-linejoin", "stroke-miterlimit",
+    "stroke-dasharray", "stroke-dashoffset", "stroke-opacity", "opacity", "offset", "stop-color", "stop-opacity",
+    "gradientunits", "gradienttransform", "spreadmethod", "clip-path", "clip-rule", "clippathunits", "mask",
+    "maskunits", "font-family", "font-size", "font-weight", "font-style", "text-anchor", "dominant-baseline", "href",
+    "xlink:href"
+];
+
+/// An uploaded image for one of the stamp slots, kept in the tenant's `stamp_images` collection.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StampImage {
+    pub slot: StampSlot,
+    pub content_type: String,
+    pub data: Binary,
+    pub uploaded: DateTime
+}
+
+fn stamp_slot(path: web::Path<String>) -> Result<StampSlot, ApiError> {
+    match path.as_str() {
+        "stamped" => Ok(StampSlot::Stamped),
+        "empty" => Ok(StampSlot::Empty),
+        _ => Err(ApiError::NotFound("Stamp slot"))
+    }
+}
+
+fn unreadable() -> ApiError {
+    ApiError::Validation(String::from("The SVG could not be read"))
+}
+
+/// The SVG rebuilt from only the elements and attributes that draw shapes. Scripts, styles, foreign content
+/// and anything else not on the lists are dropped along with everything inside them.
+pub fn clean_svg(svg: &str) -> Result<String, ApiError> {
+    let mut cleaned = String::with_capacity(svg.len());
+    let mut rest = svg;
+    let mut root: Option<String> = None;
+    // how deep inside a dropped element the tokens are
+    let mut skipping = 0usize;
+
+    while let Some(start) = rest.find('<') {
+        let (text, tag) = rest.split_at(start);
+        if skipping == 0 {
+            cleaned.push_str(text);
+        }
+
+        if let Some(comment) = tag.strip_prefix("<!--") {
+            rest = comment.split_once("-->").ok_or_else(unreadable)?.1;
+            continue
+        }
+        if let Some(cdata) = tag.strip_prefix("<![CDATA[") {
+            rest = cdata.split_once("]]>").ok_or_else(unreadable)?.1;
+            continue
+        }
+        // a doctype can declare entities, which is how most xml attacks start
+        if tag.starts_with("<!") {
+            return Err(ApiError::Validation(String::from("SVGs with a DOCTYPE cannot be uploaded")))
+        }
+        if let Some(instruction) = tag.strip_prefix("<?") {
+            rest = instruction.split_once("?>").ok_or_else(unreadable)?.1;
+            continue
+        }
+
+        let end = tag_end(tag).ok_or_else(unreadable)?;
+        let inner = &tag[1..end];
+        rest = &tag[end + 1..];
+
+        if let Some(name) = inner.strip_prefix('/') {
+            let name = name.trim();
+            if skipping > 0 {
+                skipping -= 1;
+            }
+            else if ELEMENTS.contains(&name.to_lowercase().as_str()) {
+                cleaned.push_str(&format!("</{}>", name));
+            }
+            continue
+        }
+
+        let self_closing = inner.ends_with('/');
+        let inner = inner.trim_end_matches('/');
+        let (name, attributes) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+        if skipping > 0 || !ELEMENTS.contains(&name.to_lowercase().as_str()) {
+            if !self_closing {
+                skipping += 1;
+            }
+            continue
+        }
+        root.get_or_insert_with(|| name.to_lowercase());
+
+        cleaned.push('<');
+        cleaned.push_str(name);
+        for (attribute, value) in parse_attributes(attributes)? {
+            if allowed(attribute, value) {
+                cleaned.push_str(&format!(" {}=\"{}\"", attribute, value.replace('"', "&quot;").replace('<', "&lt;")));
+            }
+        }
+        cleaned.push_str(if self_closing { "/>" } else { ">" });
+    }
+    if skipping == 0 {
+        cleaned.push_str(rest);
+    }
+
+    match root.as_deref() {
+        Some("svg") => Ok(cleaned),
+        _ => Err(ApiError::Validation(String::from("The file is not an SVG image")))
+    }
+}
+
+/// Where a tag ends, ignoring any `>` inside a quoted attribute.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attributes(mut attributes: &str) -> Result<Vec<(&str, &str)>, ApiError> {
+    let mut parsed = Vec::new();
+    loop {
+        attributes = attributes.trim_start();
+        if attributes.is_empty() {
+            return Ok(parsed)
+        }
+
+        let (name, value) = attributes.split_once('=').ok_or_else(unreadable)?;
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'').ok_or_else(unreadable)?;
+        let (value, remaining) = value[1..].split_once(quote).ok_or_else(unreadable)?;
+
+        parsed.push((name.trim(), value));
+        attributes = remaining;
+    }
+}
+
+fn allowed(attribute: &str, value: &str) -> bool {
+    let attribute = attribute.to_lowercase();
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+
+    if !ATTRIBUTES.contains(&attribute.as_str()) {
+        return false
+    }
+    // links may only point at shapes inside the same image
+    if attribute.ends_with("href") {
+        return value.starts_with('#')
+    }
+    !value.contains("javascript:")
+        && !value.contains("data:")
+        && value.match_indices("url(").all(|(index, _)| value[index + 4..].starts_with('#'))
+}
+
+// route handlers
+#[utoipa::path(
+    post,
+    path = "/api/admin/theme/images/{slot}",
+    tag = "theme",
+    security(("admin_key" = [])),
+    params(("slot" = StampSlot, Path, description = "stamped for a stamp, empty for a slot still to be stamped")),
+    request_body(
+        description = "The image file as is, SVGs are cleaned before they are stored",
+        content(
+            (String = "image/svg+xml"),
+            (String = "image/png")
+        )
+    ),
+    responses(
+        (status = 200, description = "The image has been stored and the theme now uses it", body = ThemeDetails),
+        (status = 400, description = "The file is not a PNG or an SVG that can be read", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such stamp slot", body = ErrorResponse)
+    )
+)]
+pub async fn upload_image(_: Admin, req: HttpRequest, path: web::Path<String>, body: web::Bytes, data: AppData) -> Result<HttpResponse, ApiError> {
+    let slot = stamp_slot(path)?;
+    let content_type = req.headers().get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let (content_type, bytes) = if content_type.starts_with(SVG) {
+        let svg = std::str::from_utf8(&body).map_err(|_| unreadable())?;
+        (SVG, clean_svg(svg)?.into_bytes())
+    }
+    else if content_type.starts_with(PNG) && body.starts_with(PNG_SIGNATURE) {
+        (PNG, body.to_vec())
+    }
+    else {
+        return Err(ApiError::Validation(String::from("Upload an SVG or PNG image")))
+    };
+
+    let image = StampImage {
+        slot,
+        content_type: content_type.to_string(),
+        data: Binary { subtype: BinarySubtype::Generic, bytes },
+        uploaded: DateTime::now()
+    };
+    data.stamp_images.lock().await.save_image(&image).await?;
+
+    // the upload time in the address stops browsers showing an older image from their cache
+    let url = format!("{}/theme/images/{}?v={}", data.tenant.api_path(), slot.as_str(), image.uploaded.timestamp_millis());
+    let mut themes = data.theme.lock().await;
+    let mut theme = themes.find_theme().await?.unwrap_or_default();
+    match slot {
+        StampSlot::Stamped => theme.stamp_icon_url = Some(url),
+        StampSlot::Empty => theme.empty_slot_url = Some(url)
+    }
+    themes.save_theme(&theme).await?;
+
+    info!("Saved the {} stamp image for {}", slot.as_str(), data.tenant.name);
+    Ok(HttpResponse::Ok().json(theme))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/theme/images/{slot}",
+    tag = "theme",
+    params(("slot" = StampSlot, Path, description = "stamped for a stamp, empty for a slot still to be stamped")),
+    responses(
+        (status = 200, description = "The uploaded image", content(
+            (String = "image/svg+xml"),
+            (String = "image/png")
+        )),
+        (status = 404, description = "No such stamp slot, or no image has been uploaded for it", body = ErrorResponse)
+    )
+)]
+pub async fn get_image(path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let image = data.stamp_images.lock().await.find_image(stamp_slot(path)?).await?
+        .ok_or(ApiError::NotFound("Stamp image"))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(image.content_type)
+        // nothing in a cleaned svg can run, but make sure of it when the image is opened on its own
+        .insert_header((header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'"))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(image.data.bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn svgs_keep_only_their_shapes() {
+        let svg = r##"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10" onload="alert(1)">
+<!-- a cup --><script>alert(1)</script>
+<linearGradient id="g"><stop offset="0" stop-color="#fff"/></linearGradient>
+<circle cx='5' cy="5" r="4" fill="url(#g)" style="fill:url(https://example.com/x)"/>
+<foreignObject><svg><rect/></svg></foreignObject>
+<use href="https://example.com/evil.svg#x"/><use xlink:href="#g"/>
+<rect width="10" height="10" fill="url(javascript:alert(1))"/>
+</svg>"##;
+
+        assert_eq!(clean_svg(svg).unwrap(), r##"
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+
+<linearGradient id="g"><stop offset="0" stop-color="#fff"/></linearGradient>
+<circle cx="5" cy="5" r="4" fill="url(#g)"/>
+
+<use/><use xlink:href="#g"/>
+<rect width="10" height="10"/>
+</svg>"##);
+    }
+
+    #[test]
+    fn only_readable_svgs_are_accepted() {
+        assert!(clean_svg(r#"<!DOCTYPE svg [<!ENTITY x "y">]><svg>&x;</svg>"#).is_err());
+        assert!(clean_svg("<html><body>not an image</body></html>").is_err());
+        assert!(clean_svg(r#"<svg width="10></svg>"#).is_err());
+        assert!(clean_svg("<svg hidden></svg>").is_err());
+        assert!(clean_svg(r#"<svg data-x="a > b"/>"#).is_ok());
+    }
+}
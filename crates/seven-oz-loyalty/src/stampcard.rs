@@ -1,18 +1,61 @@
 use std::cmp::min;
 
 use actix_web::{HttpResponse, web};
-use mongodb::bson::doc;
+use log::info;
+use mongodb::bson::{doc, DateTime};
 use serde::{Deserialize, Serialize};
 
-use loyalty_core::UserId;
+use loyalty_core::api::v1::{ActivityKind, ActivityResponse, AdjustRequest, AdminCardResponse, BirthdayRequest, CardEvent, CardKind, CardResponse, CardUpdate, ErrorResponse, ExpiryPolicy, SpendRequest};
+use loyalty_core::card::LoyaltyCard;
+use loyalty_core::{PhoneNumber, UserId};
 
+use crate::activity::{self, CardActivity};
 use crate::AppData;
+use crate::auth::{self, Admin};
+use crate::error::ApiError;
+use crate::{bonuses, expiry, sse, tiers};
+
+const LOOKUP_ACTIVITY_LIMIT: i64 = 20;
+pub const DEFAULT_CAPACITY: u32 = 10;
+/// The biggest card there can be, which keeps the stamp dates and the customer's page a sensible size.
+pub const MAX_CAPACITY: u32 = 50;
+
+/// EventSource cannot send headers, so the card key comes in the query string.
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    access_token: Option<String>
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BasicStampCard {
     user_id: UserId,
     pub stamps: u32, // TODO should not be public
     capacity: u32,
+    /// When each stamp on the card was given, oldest first. Cards from before expiry have none.
+    #[serde(default)]
+    stamped: Vec<DateTime>,
+    #[serde(default)]
+    last_activity: Option<DateTime>,
+    /// The programme of the store the card was last stamped at, which decides when stamps expire.
+    #[serde(default)]
+    pub programme_id: Option<String>,
+    /// Shared in the customer's referral link, created the first time they ask for one.
+    #[serde(default)]
+    referral_code: Option<String>,
+    /// Given to the customer's browser when they claim a stamp, and needed to follow the card live.
+    #[serde(default)]
+    card_key: Option<String>,
+    #[serde(default)]
+    birth_month: Option<u32>,
+    /// The year the last birthday bonus was given, so it is only given once a year.
+    #[serde(default)]
+    birthday_bonus_year: Option<i32>,
+    /// How many full cards have been redeemed, which is what tiers and milestones count.
+    #[serde(default)]
+    redemptions: u32,
+    /// Every stamp given, less any taken off by hand. Expired and redeemed stamps still count.
+    #[serde(default)]
+    total_stamps: u32,
 }
 
 impl BasicStampCard {
@@ -20,49 +63,636 @@ impl BasicStampCard {
         BasicStampCard {
             user_id,
             stamps: 0,
-            capacity: 10
+            capacity: DEFAULT_CAPACITY,
+            stamped: Vec::new(),
+            last_activity: Some(DateTime::now()),
+            programme_id: None,
+            referral_code: None,
+            card_key: None,
+            birth_month: None,
+            birthday_bonus_year: None,
+            redemptions: 0,
+            total_stamps: 0
+        }
+    }
+
+    /// A card carried over from somewhere else, such as a backup or a paper card.
+    /// Its stamps are undated until `with_stamp_dates` or `dated` says when they were given.
+    pub fn restored(user_id: UserId, stamps: u32, capacity: u32) -> Self {
+        let capacity = min(capacity, MAX_CAPACITY);
+        let stamps = min(stamps, capacity);
+        BasicStampCard {
+            user_id,
+            stamps,
+            capacity,
+            stamped: Vec::new(),
+            last_activity: None,
+            programme_id: None,
+            referral_code: None,
+            card_key: None,
+            birth_month: None,
+            birthday_bonus_year: None,
+            redemptions: 0,
+            total_stamps: stamps
+        }
+    }
+
+    /// The card with the dates its stamps were given, ignored unless there is one for every stamp.
+    pub fn with_stamp_dates(&self, mut stamped: Vec<DateTime>, last_activity: Option<DateTime>) -> Self {
+        if stamped.len() != self.stamps as usize {
+            return self.clone()
+        }
+        stamped.sort();
+        BasicStampCard {
+            last_activity: last_activity.or(stamped.last().copied()).or(Some(DateTime::now())),
+            stamped,
+            ..self.clone()
+        }
+    }
+
+    pub fn with_birthday_bonus_year(&self, birthday_bonus_year: Option<i32>) -> Self {
+        BasicStampCard {
+            birthday_bonus_year,
+            ..self.clone()
+        }
+    }
+
+    /// This card set to the stamps, capacity and counters of `restored`, keeping the stamp dates,
+    /// programme and bonus year when the import has nowhere to carry them. Counters never go backwards.
+    pub fn restored_over(&self, restored: &BasicStampCard) -> Self {
+        let stamped = match restored.is_dated() {
+            true => restored.stamped.clone(),
+            false => {
+                let mut stamped = self.stamped.clone();
+                stamped.truncate(restored.stamps as usize);
+                stamped.resize(restored.stamps as usize, DateTime::now());
+                stamped
+            }
+        };
+
+        BasicStampCard {
+            stamps: restored.stamps,
+            capacity: restored.capacity,
+            stamped,
+            last_activity: restored.last_activity.or(Some(DateTime::now())),
+            programme_id: restored.programme_id.clone().or(self.programme_id.clone()),
+            referral_code: restored.referral_code.clone().or(self.referral_code.clone()),
+            birth_month: restored.birth_month.or(self.birth_month),
+            birthday_bonus_year: restored.birthday_bonus_year.or(self.birthday_bonus_year),
+            redemptions: self.redemptions.max(restored.redemptions),
+            total_stamps: self.total_stamps.max(restored.total_stamps),
+            ..self.clone()
+        }
+    }
+
+    /// Adds the stamps from a claim, usually one unless a campaign is running.
+    pub fn with_stamps(&self, stamps: u32, programme_id: Option<String>) -> Self {
+        self.with_adjustment(i32::try_from(stamps).unwrap_or(i32::MAX)).in_programme(programme_id)
+    }
+
+    /// Adds or removes stamps by hand, never going below empty or above a full card.
+    /// Removed stamps are taken from the newest so the oldest still expire first.
+    pub fn with_adjustment(&self, delta: i32) -> Self {
+        let stamps = self.stamps.saturating_add_signed(delta).min(self.capacity);
+        let mut stamped = self.stamped.clone();
+        stamped.truncate(stamps as usize);
+        stamped.resize(stamps as usize, DateTime::now());
+
+        BasicStampCard {
+            stamps,
+            stamped,
+            last_activity: Some(DateTime::now()),
+            total_stamps: self.total_stamps.saturating_add_signed(stamps as i32 - self.stamps as i32),
+            ..self.clone()
+        }
+    }
+
+    /// The lifetime counters carried over with a restored card, never fewer stamps than it holds.
+    pub fn with_lifetime(&self, total_stamps: u32, redemptions: u32) -> Self {
+        BasicStampCard {
+            total_stamps: total_stamps.max(self.stamps),
+            redemptions,
+            ..self.clone()
+        }
+    }
+
+    /// The card with a tier's capacity, which only happens once it has been emptied.
+    pub fn with_capacity(&self, capacity: u32) -> Self {
+        BasicStampCard {
+            capacity: capacity.min(MAX_CAPACITY).max(self.stamps),
+            ..self.clone()
+        }
+    }
+
+    /// The card once its reward has been redeemed.
+    pub fn emptied(&self) -> Self {
+        BasicStampCard {
+            stamps: 0,
+            stamped: Vec::new(),
+            last_activity: Some(DateTime::now()),
+            redemptions: self.redemptions + u32::from(self.stamps >= self.capacity),
+            ..self.clone()
+        }
+    }
+
+    pub fn with_birth_month(&self, birth_month: Option<u32>) -> Self {
+        BasicStampCard {
+            birth_month,
+            ..self.clone()
+        }
+    }
+
+    /// Whether the birthday bonus for `month` of `year` is still to be given.
+    pub fn birthday_due(&self, month: u32, year: i32) -> bool {
+        self.birth_month == Some(month) && self.birthday_bonus_year != Some(year)
+    }
+
+    pub fn with_birthday_bonus(&self, stamps: u32, year: i32) -> Self {
+        BasicStampCard {
+            birthday_bonus_year: Some(year),
+            ..self.with_adjustment(stamps as i32)
+        }
+    }
+
+    /// The card moved into `programme_id`, staying where it is when there is none.
+    pub fn in_programme(mut self, programme_id: Option<String>) -> Self {
+        self.programme_id = programme_id.or(self.programme_id);
+        self
+    }
+
+    /// Whether every stamp has a date, which cards saved before expiry existed do not.
+    pub fn is_dated(&self) -> bool {
+        self.stamped.len() == self.stamps as usize && self.last_activity.is_some()
+    }
+
+    /// Fills in missing dates with `now` so old cards get a full expiry period from today.
+    pub fn dated(&self, now: DateTime) -> Self {
+        let mut stamped = vec![now; (self.stamps as usize).saturating_sub(self.stamped.len())];
+        stamped.extend(self.stamped.iter().take(self.stamps as usize));
+
+        BasicStampCard {
+            stamped,
+            last_activity: self.last_activity.or(Some(now)),
+            ..self.clone()
+        }
+    }
+
+    /// When each dated stamp runs out under `policy`, soonest first.
+    pub fn expiries(&self, policy: &ExpiryPolicy) -> Vec<DateTime> {
+        match *policy {
+            ExpiryPolicy::StampAge { months } => self.stamped.iter()
+                .map(|stamped| expiry::add_months(*stamped, months))
+                .collect(),
+            ExpiryPolicy::Inactivity { months } => match self.last_activity {
+                Some(last) => vec![expiry::add_months(last, months); self.stamped.len()],
+                None => Vec::new()
+            }
+        }
+    }
+
+    /// The card with every stamp that has run out by `now` taken off.
+    pub fn without_expired(&self, policy: &ExpiryPolicy, now: DateTime) -> Self {
+        let expired = self.expiries(policy).iter().filter(|expires| **expires <= now).count();
+
+        BasicStampCard {
+            stamps: self.stamps.saturating_sub(expired as u32),
+            stamped: self.stamped[expired..].to_vec(),
+            ..self.clone()
+        }
+    }
+
+    pub fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn birth_month(&self) -> Option<u32> {
+        self.birth_month
+    }
+
+    pub fn redemptions(&self) -> u32 {
+        self.redemptions
+    }
+
+    pub fn total_stamps(&self) -> u32 {
+        self.total_stamps
+    }
+
+    /// When each stamp was given, oldest first, empty for a card from before expiry.
+    pub fn stamped(&self) -> &[DateTime] {
+        &self.stamped
+    }
+
+    pub fn last_activity(&self) -> Option<DateTime> {
+        self.last_activity
+    }
+
+    pub fn birthday_bonus_year(&self) -> Option<i32> {
+        self.birthday_bonus_year
+    }
+
+    pub fn referral_code(&self) -> Option<&str> {
+        self.referral_code.as_deref()
+    }
+
+    pub fn with_referral_code(&self, code: String) -> Self {
+        BasicStampCard {
+            referral_code: Some(code),
+            ..self.clone()
         }
     }
 
-    pub fn with_stamp(&self) -> Self {
+    pub fn card_key(&self) -> Option<&str> {
+        self.card_key.as_deref()
+    }
+
+    pub fn with_card_key(&self, key: String) -> Self {
         BasicStampCard {
-            user_id: self.user_id.clone(),
-            stamps: min(self.stamps + 1, 10),
-            capacity: self.capacity,
+            card_key: Some(key),
+            ..self.clone()
         }
     }
+
+    /// Whether `key` is the one the customer was given, which a card without a key never matches.
+    pub fn opens_with(&self, key: &str) -> bool {
+        self.card_key.as_deref().is_some_and(|card_key| auth::keys_match(card_key, key))
+    }
 }
 
+impl LoyaltyCard for BasicStampCard {
+    fn new(user_id: UserId) -> Self {
+        BasicStampCard::new(user_id)
+    }
+
+    fn user_id(&self) -> &UserId {
+        &self.user_id
+    }
+
+    fn kind(&self) -> CardKind {
+        CardKind::Stamps
+    }
+
+    fn balance(&self) -> u32 {
+        self.stamps
+    }
 
-#[derive(Serialize)]
-struct CardResponse {
-    stamps: u32
+    fn limit(&self) -> Option<u32> {
+        Some(self.capacity)
+    }
+
+    fn with_earned(&self, amount: u32) -> Self {
+        self.with_adjustment(i32::try_from(amount).unwrap_or(i32::MAX))
+    }
+
+    /// Takes the oldest stamps, which are the first to expire. A catalogue reward is not a full card
+    /// so it does not count as a redemption.
+    fn with_spent(&self, cost: u32) -> Option<Self> {
+        (cost <= self.stamps).then(|| BasicStampCard {
+            stamps: self.stamps - cost,
+            stamped: self.stamped.iter().skip(cost as usize).copied().collect(),
+            last_activity: Some(DateTime::now()),
+            ..self.clone()
+        })
+    }
+}
+
+/// Pushed to any open card pages for the customer whenever one of their cards changes.
+#[derive(Debug, Clone)]
+pub struct CardNotification {
+    user_id: UserId,
+    update: CardUpdate
+}
+
+impl CardNotification {
+    pub fn new(user_id: UserId, event: CardEvent, card: &impl LoyaltyCard) -> Self {
+        CardNotification {
+            user_id,
+            update: CardUpdate {
+                event,
+                stamps: card.balance(),
+                capacity: card.limit().unwrap_or_default(),
+                kind: card.kind()
+            }
+        }
+    }
 }
 
 // route handlers
-pub async fn get_card(path: web::Path<String>, data: AppData) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/api/stampcard/{id}",
+    tag = "stamp cards",
+    params(("id" = String, Path, description = "The customer's phone number")),
+    responses(
+        (status = 200, description = "The card, created empty if the customer has not got one yet", body = CardResponse)
+    )
+)]
+pub async fn get_card(path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = get_user_id(path);
+
+    let card = data.cards.lock().await.get_or_create_card(&user_id).await?;
+
+    Ok(HttpResponse::Ok().json(card_response(&data, &card).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/stampcard/{id}/reset",
+    tag = "stamp cards",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    responses(
+        (status = 200, description = "The reward has been redeemed and the card emptied"),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn reset_card(admin: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = get_user_id(path);
+
+    let mut tracker = data.cards.lock().await;
+    let previous = tracker.get_or_create_card(&user_id).await?;
+    let card = tracker.reset_card(&user_id).await?;
+
+    // only a full card is a redemption, emptying one that is not is recorded as an adjustment
+    let is_redemption = card.redemptions() > previous.redemptions();
+    if is_redemption {
+        activity::record(&data, CardActivity::new(ActivityKind::Redeemed, &card, -(previous.stamps as i32)).staff(&admin)).await;
+    }
+    else if previous.stamps > 0 {
+        activity::record(&data, CardActivity::new(ActivityKind::Adjusted, &card, -(previous.stamps as i32)).reason("Emptied before it was full").staff(&admin)).await;
+    }
+    _ = data.card_updates.send(CardNotification::new(user_id, CardEvent::Redeemed, &card));
+    drop(tracker);
+
+    if is_redemption {
+        redeemed(&data, &card).await;
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/stampcard/{id}/redeem",
+    tag = "stamp cards",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    request_body(content = SpendRequest, description = "The reward from the card's programme catalogue"),
+    responses(
+        (status = 200, description = "The reward has been redeemed and its cost taken off the card", body = CardResponse),
+        (status = 400, description = "The card does not have enough stamps for the reward", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse),
+        (status = 404, description = "No such reward in the card's programme", body = ErrorResponse)
+    )
+)]
+pub async fn redeem_reward(admin: Admin, path: web::Path<String>, request: web::Json<SpendRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = get_user_id(path);
+
+    let mut tracker = data.cards.lock().await;
+    let previous = tracker.get_or_create_card(&user_id).await?;
+    let programme = match &previous.programme_id {
+        Some(programme_id) => data.programmes.lock().await.find_programme(programme_id).await?,
+        None => None
+    };
+    let reward = programme.as_ref()
+        .filter(|programme| programme.kind == CardKind::Stamps)
+        .and_then(|programme| programme.reward(&request.reward_id))
+        .cloned()
+        .ok_or(ApiError::NotFound("Reward"))?;
+    let Some(card) = previous.with_spent(reward.cost)
+        else {
+            return Err(ApiError::Validation(format!("This card has {} stamps but {} needs {}", previous.stamps, reward.name, reward.cost)))
+        };
+    tracker.save_card(&card).await?;
+
+    activity::record(&data, CardActivity::new(ActivityKind::Redeemed, &card, -(reward.cost as i32)).reason(&reward.name).staff(&admin)).await;
+    _ = data.card_updates.send(CardNotification::new(user_id, CardEvent::Redeemed, &card));
+    drop(tracker);
+
+    // only full cards count towards tiers and milestones, so nothing else changes here
+    info!("Card '{}' redeemed {} for {} stamps by admin", card.user_id(), reward.reward_id, reward.cost);
+
+    Ok(HttpResponse::Ok().json(card_response(&data, &card).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stampcard/{id}/stream",
+    tag = "stamp cards",
+    params(
+        ("id" = String, Path, description = "The customer's phone number"),
+        ("access_token" = String, Query, description = "The card key the customer's browser was given when they claimed a stamp")
+    ),
+    responses(
+        (status = 200, description = "Server-sent events starting with a snapshot of the card then every stamp, redemption and manual adjustment", content_type = "text/event-stream", body = CardUpdate),
+        (status = 401, description = "Missing or wrong card key, or no such card", body = ErrorResponse)
+    )
+)]
+pub async fn stream_card(path: web::Path<String>, query: web::Query<StreamQuery>, data: AppData) -> Result<HttpResponse, ApiError> {
     let user_id = get_user_id(path);
-    
-    let mut tracker = data.cards.lock().unwrap();
-    let card = tracker.get_or_create_card(&user_id).await.unwrap();
-    
-    let response = CardResponse { stamps: card.stamps };
-    HttpResponse::Ok().json(response)
+    let key = query.into_inner().access_token.ok_or(ApiError::Unauthorized("Missing card key"))?;
+
+    // subscribe before reading the card so a stamp in between is not missed
+    let updates = data.card_updates.subscribe();
+    // a missing card is refused like a wrong key, so the stream says nothing about which numbers have cards
+    let Some(card) = data.cards.lock().await.find_card(&user_id).await?.filter(|card| card.opens_with(&key))
+        else { return Err(ApiError::Unauthorized("Invalid card key")) };
+
+    let initial = sse::event(&CardNotification::new(user_id.clone(), CardEvent::Snapshot, &card).update);
+    Ok(sse::event_stream(initial, updates, move |notification: CardNotification| {
+        (notification.user_id == user_id).then(|| sse::event(&notification.update))
+    }))
 }
 
-pub async fn reset_card(path: web::Path<String>, data: AppData) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/stampcard/{id}/adjust",
+    tag = "stamp cards",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    request_body(content = AdjustRequest, description = "A signed number of stamps to add or remove and why"),
+    responses(
+        (status = 200, description = "The card after the adjustment", body = CardResponse),
+        (status = 400, description = "No change, no reason or the card cannot hold the result", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn adjust_card(admin: Admin, path: web::Path<String>, request: web::Json<AdjustRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
     let user_id = get_user_id(path);
+    let reason = request.reason.trim();
+    if request.delta == 0 {
+        return Err(ApiError::Validation(String::from("An adjustment must add or remove at least one stamp")))
+    }
+    if reason.is_empty() {
+        return Err(ApiError::Validation(String::from("A reason is required for a manual adjustment")))
+    }
+
+    let mut tracker = data.cards.lock().await;
+    let previous = tracker.get_or_create_card(&user_id).await?;
+
+    // reject rather than clamp so the recorded delta is always what staff asked for
+    let stamps = previous.stamps as i64 + request.delta as i64;
+    if stamps < 0 || stamps > previous.capacity as i64 {
+        return Err(ApiError::Validation(format!(
+            "This card has {} of {} stamps so it can only change by {} to +{}",
+            previous.stamps, previous.capacity, -(previous.stamps as i64), previous.capacity.saturating_sub(previous.stamps)
+        )))
+    }
+
+    let card = tracker.adjust_card(&user_id, request.delta).await?;
+
+    let activity = CardActivity::new(ActivityKind::Adjusted, &card, request.delta).reason(reason).staff(&admin);
+    activity::record(&data, activity).await;
+    _ = data.card_updates.send(CardNotification::new(user_id, CardEvent::Adjusted, &card));
+
+    info!("Card '{}' adjusted by {}: {}", card.user_id(), request.delta, reason);
+
+    Ok(HttpResponse::Ok().json(card_response(&data, &card).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/stampcard/{id}/birthday",
+    tag = "stamp cards",
+    params(("id" = String, Path, description = "The customer's phone number")),
+    request_body(content = BirthdayRequest, description = "The customer's birth month, or null to forget it"),
+    responses(
+        (status = 200, description = "The card with its birth month saved", body = CardResponse),
+        (status = 400, description = "The month is not between 1 and 12", body = ErrorResponse)
+    )
+)]
+pub async fn set_birthday(path: web::Path<String>, request: web::Json<BirthdayRequest>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = get_user_id(path);
+    if request.month.is_some_and(|month| !(1..=12).contains(&month)) {
+        return Err(ApiError::Validation(String::from("The birth month must be between 1 and 12")))
+    }
+
+    let mut tracker = data.cards.lock().await;
+    let card = tracker.get_or_create_card(&user_id).await?.with_birth_month(request.month);
+    tracker.save_card(&card).await?;
+    drop(tracker);
+
+    Ok(HttpResponse::Ok().json(card_response(&data, &card).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/cards/{id}",
+    tag = "stamp cards",
+    security(("admin_key" = [])),
+    params(("id" = String, Path, description = "The customer's phone number")),
+    responses(
+        (status = 200, description = "The card, the rewards its programme offers and its recent activity", body = AdminCardResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn lookup_card(_: Admin, path: web::Path<String>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let user_id = get_user_id(path);
+
+    let card = data.cards.lock().await.get_or_create_card(&user_id).await?;
+    let activity = data.activity.lock().await.for_card(&user_id, LOOKUP_ACTIVITY_LIMIT).await?;
 
-    let mut tracker = data.cards.lock().unwrap();
-    tracker.reset_card(&user_id).await.unwrap(); // TODO error handle
-    
-    
-    HttpResponse::Ok().finish()
+    Ok(HttpResponse::Ok().json(AdminCardResponse {
+        user_id: user_id.to_string(),
+        stamps: card.stamps,
+        capacity: card.capacity,
+        total_stamps: card.total_stamps,
+        redemptions: card.redemptions,
+        rewards: card_response(&data, &card).await?.rewards,
+        activity: activity.into_iter().map(ActivityResponse::from).collect()
+    }))
+}
+
+/// Moves the card up a tier and gives any milestone bonus once a reward has been redeemed.
+async fn redeemed(data: &AppData, card: &BasicStampCard) {
+    tiers::promote(data, card).await;
+    bonuses::reward_milestone(data, card).await;
+}
+
+/// The card as the customer sees it, with any stamps about to expire under its programme's policy
+/// and how far it is through the programme's tiers.
+async fn card_response(data: &AppData, card: &BasicStampCard) -> Result<CardResponse, ApiError> {
+    let programme = match &card.programme_id {
+        Some(programme_id) => data.programmes.lock().await.find_programme(programme_id).await?,
+        None => None
+    };
+    let policy = programme.as_ref().and_then(|programme| programme.expiry);
+
+    Ok(CardResponse {
+        stamps: card.stamps,
+        capacity: card.capacity(),
+        kind: CardKind::Stamps,
+        expiring: policy.map(|policy| expiry::upcoming(&card.expiries(&policy), DateTime::now())).unwrap_or_default(),
+        birth_month: card.birth_month,
+        total_stamps: card.total_stamps,
+        redemptions: card.redemptions,
+        tier: programme.as_ref().and_then(|programme| tiers::progress(&programme.tiers, card.redemptions)),
+        rewards: programme
+            .filter(|programme| programme.kind == CardKind::Stamps)
+            .map(|programme| programme.catalogue)
+            .unwrap_or_default()
+    })
 }
 
 fn get_user_id(path: web::Path<String>) -> UserId {
     let user_id = path.into_inner();
-    UserId(user_id)
+    // the same card however the number is typed, while anything else is looked up as it is
+    match PhoneNumber::try_from(user_id.as_str()) {
+        Ok(phone) => UserId::from(phone),
+        Err(_) => UserId(user_id)
+    }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, Scope};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+
+    use super::*;
+
+    #[test]
+    fn only_full_cards_count_as_redemptions() {
+        let card = BasicStampCard::new(UserId("07700900001".into())).with_stamps(DEFAULT_CAPACITY, None);
+
+        let pastry = card.with_spent(5).unwrap();
+        assert_eq!((pastry.stamps, pastry.redemptions()), (DEFAULT_CAPACITY - 5, 0));
+        assert_eq!(card.emptied().redemptions(), 1);
+    }
+
+    #[actix_web::test]
+    async fn rewards_are_redeemed_by_staff() {
+        let app = init_service(App::new().app_data(crate::test_state(Some("admin"))).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::post().uri("/api/stampcard/07700900001/redeem")
+            .set_json(SpendRequest { reward_id: String::from("pastry") })
+            .to_request();
+
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn cards_are_only_followed_with_their_key() {
+        let app = init_service(App::new().app_data(crate::test_state(None)).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::get().uri("/api/stampcard/07700900001/stream").to_request();
+
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+
+        let card = BasicStampCard::new(UserId("07700900001".into())).with_card_key(String::from("secret"));
+        assert!(card.opens_with("secret"));
+        assert!(!card.opens_with("guess"));
+        assert!(!BasicStampCard::new(UserId("07700900001".into())).opens_with(""));
+    }
+
+    #[actix_web::test]
+    async fn full_cards_are_redeemed_by_staff() {
+        let app = init_service(App::new().app_data(crate::test_state(Some("admin"))).service(Scope::new("/api").configure(crate::api_routes))).await;
+        let request = TestRequest::post().uri("/api/stampcard/07700900001/reset").to_request();
+
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+    }
+}
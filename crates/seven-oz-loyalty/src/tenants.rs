@@ -0,0 +1,128 @@
+use actix_web::HttpResponse;
+use log::warn;
+use shuttle_runtime::SecretStore;
+
+use loyalty_core::api::v1::TenantResponse;
+
+use crate::AppData;
+
+/// The name of the server's own business when `BUSINESS_NAME` is not set.
+const DEFAULT_NAME: &str = "7oz";
+const MAX_ID_LENGTH: usize = 32;
+
+/// A business on this server with its own programmes, stores, staff and cards.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tenant {
+    /// Missing for the server's own business, which keeps the unprefixed routes and collections.
+    pub tenant_id: Option<String>,
+    pub name: String,
+    pub admin_key: Option<String>
+}
+
+impl Tenant {
+    /// The server's own business followed by each business in `TENANTS`, written as
+    /// `bakery=The Bakery, cafe=Corner Cafe`. Each tenant's admin key is `ADMIN_API_KEY_<ID>`.
+    pub fn from_secrets(secrets: &SecretStore) -> Vec<Tenant> {
+        let own = Tenant {
+            tenant_id: None,
+            name: secrets.get("BUSINESS_NAME").unwrap_or_else(|| DEFAULT_NAME.to_string()),
+            admin_key: secrets.get("ADMIN_API_KEY")
+        };
+
+        let tenants = parse(&secrets.get("TENANTS").unwrap_or_default()).into_iter()
+            .map(|(tenant_id, name)| Tenant {
+                admin_key: secrets.get(&format!("ADMIN_API_KEY_{}", tenant_id.to_uppercase().replace('-', "_"))),
+                tenant_id: Some(tenant_id),
+                name
+            });
+        std::iter::once(own).chain(tenants).collect()
+    }
+
+    /// Where the api is mounted, `/t/<id>/api` for a tenant.
+    pub fn api_path(&self) -> String {
+        match &self.tenant_id {
+            Some(tenant_id) => format!("/t/{}/api", tenant_id),
+            None => String::from("/api")
+        }
+    }
+
+    /// The tenant's own host under `domain`, such as `bakery.loyalty.example.com`.
+    pub fn host(&self, domain: &str) -> Option<String> {
+        self.tenant_id.as_ref().map(|tenant_id| format!("{}.{}", tenant_id, domain))
+    }
+
+    /// The tenant's copy of a collection; ids cannot contain dots so no two tenants can share one.
+    pub fn collection(&self, name: &str) -> String {
+        match &self.tenant_id {
+            Some(tenant_id) => format!("{}.{}", tenant_id, name),
+            None => name.to_string()
+        }
+    }
+}
+
+/// The tenants in a `TENANTS` secret, leaving out any with a bad or repeated id.
+fn parse(tenants: &str) -> Vec<(String, String)> {
+    let mut parsed: Vec<(String, String)> = Vec::new();
+    for entry in tenants.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (tenant_id, name) = entry.split_once('=').unwrap_or((entry, entry));
+        let (tenant_id, name) = (tenant_id.trim().to_lowercase(), name.trim());
+
+        if !valid_id(&tenant_id) || parsed.iter().any(|(other, _)| *other == tenant_id) {
+            warn!("Skipping tenant '{}', ids must be unique and only use letters, digits and dashes", tenant_id);
+            continue
+        }
+        parsed.push((tenant_id, name.to_string()));
+    }
+    parsed
+}
+
+// ids end up in hosts, paths and collection names so keep them to what is safe in all three
+fn valid_id(tenant_id: &str) -> bool {
+    !tenant_id.is_empty()
+        && tenant_id.len() <= MAX_ID_LENGTH
+        && !tenant_id.starts_with('-')
+        && !tenant_id.ends_with('-')
+        && tenant_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/tenant",
+    tag = "tenants",
+    responses(
+        (status = 200, description = "The business this api belongs to", body = TenantResponse)
+    )
+)]
+pub async fn get_tenant(data: AppData) -> HttpResponse {
+    HttpResponse::Ok().json(TenantResponse {
+        tenant_id: data.tenant.tenant_id.clone(),
+        name: data.tenant.name.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tenants_need_unique_safe_ids() {
+        let tenants = parse("bakery=The Bakery, Corner-Cafe = Corner Cafe,, bakery=Again, bad.id=Dots, -x=Dash, solo");
+        assert_eq!(tenants, vec![
+            ("bakery".to_string(), "The Bakery".to_string()),
+            ("corner-cafe".to_string(), "Corner Cafe".to_string()),
+            ("solo".to_string(), "solo".to_string())
+        ]);
+    }
+
+    #[test]
+    fn tenants_keep_their_own_routes_and_collections() {
+        let own = Tenant { tenant_id: None, name: "7oz".into(), admin_key: None };
+        let bakery = Tenant { tenant_id: Some("bakery".into()), ..own.clone() };
+
+        assert_eq!((own.api_path(), own.collection("cards"), own.host("example.com")), ("/api".into(), "cards".into(), None));
+        assert_eq!(bakery.api_path(), "/t/bakery/api");
+        assert_eq!(bakery.collection("cards"), "bakery.cards");
+        assert_eq!(bakery.host("example.com"), Some("bakery.example.com".into()));
+    }
+}
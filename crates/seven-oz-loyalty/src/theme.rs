@@ -0,0 +1,118 @@
+use actix_web::{HttpResponse, web};
+use log::info;
+
+use loyalty_core::api::v1::{ErrorResponse, ThemeDetails};
+
+use crate::AppData;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use crate::programmes::required;
+
+const MAX_TEXT_LENGTH: usize = 60;
+
+/// The theme with its text trimmed, rejecting anything that is not a plain hex colour or a simple url,
+/// since colours end up in the client's stylesheet and urls in its images.
+pub fn checked(theme: &ThemeDetails) -> Result<ThemeDetails, ApiError> {
+    Ok(ThemeDetails {
+        logo_url: url(theme.logo_url.as_deref(), "logo")?,
+        background_colour: colour(&theme.background_colour, "background colour")?,
+        text_colour: colour(&theme.text_colour, "text colour")?,
+        stamp_colour: colour(&theme.stamp_colour, "stamp colour")?,
+        stamp_text: text(&theme.stamp_text, "stamp text")?,
+        stamp_icon_url: url(theme.stamp_icon_url.as_deref(), "stamp icon")?,
+        empty_slot_url: url(theme.empty_slot_url.as_deref(), "empty slot image")?,
+        card_title: text(&theme.card_title, "card title")?,
+        reward_text: text(&theme.reward_text, "reward text")?
+    })
+}
+
+fn colour(value: &str, field: &str) -> Result<String, ApiError> {
+    let value = value.trim().to_lowercase();
+    let valid = value.strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    match valid {
+        true => Ok(value),
+        false => Err(ApiError::Validation(format!("The {} must be a hex colour such as #778899", field)))
+    }
+}
+
+fn url(value: Option<&str>, field: &str) -> Result<Option<String>, ApiError> {
+    let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else { return Ok(None) };
+    let allowed = ["https://", "http://", "/"].iter().any(|scheme| value.starts_with(scheme))
+        && !value.chars().any(|c| c.is_whitespace() || "\"'<>()\\".contains(c));
+    match allowed {
+        true => Ok(Some(value.to_string())),
+        false => Err(ApiError::Validation(format!("The {} must be a web address or a path on this site", field)))
+    }
+}
+
+fn text(value: &str, field: &str) -> Result<String, ApiError> {
+    let value = required(value, field)?;
+    match value.chars().count() > MAX_TEXT_LENGTH {
+        true => Err(ApiError::Validation(format!("The {} must be at most {} characters", field, MAX_TEXT_LENGTH))),
+        false => Ok(value)
+    }
+}
+
+// route handlers
+#[utoipa::path(
+    get,
+    path = "/api/theme",
+    tag = "theme",
+    responses(
+        (status = 200, description = "How the business's pages look, the default look until one is saved", body = ThemeDetails)
+    )
+)]
+pub async fn get_theme(data: AppData) -> Result<HttpResponse, ApiError> {
+    let theme = data.theme.lock().await.find_theme().await?.unwrap_or_default();
+    Ok(HttpResponse::Ok().json(theme))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/theme",
+    tag = "theme",
+    security(("admin_key" = [])),
+    request_body = ThemeDetails,
+    responses(
+        (status = 200, description = "The theme has been replaced", body = ThemeDetails),
+        (status = 400, description = "A colour, address or piece of text is missing or not allowed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid admin key", body = ErrorResponse)
+    )
+)]
+pub async fn save_theme(_: Admin, request: web::Json<ThemeDetails>, data: AppData) -> Result<HttpResponse, ApiError> {
+    let theme = checked(&request)?;
+    data.theme.lock().await.save_theme(&theme).await?;
+
+    info!("Saved the theme for {}", data.tenant.name);
+    Ok(HttpResponse::Ok().json(theme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_theme_is_valid() {
+        assert_eq!(checked(&ThemeDetails::default()).unwrap(), ThemeDetails::default());
+    }
+
+    #[test]
+    fn colours_and_urls_cannot_carry_anything_else() {
+        let theme = ThemeDetails {
+            background_colour: " #ABC ".into(),
+            logo_url: Some(" ".into()),
+            stamp_icon_url: Some("/assets/cup.svg".into()),
+            ..ThemeDetails::default()
+        };
+        let theme = checked(&theme).unwrap();
+        assert_eq!((theme.background_colour.as_str(), theme.logo_url), ("#abc", None));
+
+        let bad_colour = ThemeDetails { text_colour: "red; background: url(x)".into(), ..ThemeDetails::default() };
+        assert!(checked(&bad_colour).is_err());
+        let bad_url = ThemeDetails { logo_url: Some("javascript:alert(1)".into()), ..ThemeDetails::default() };
+        assert!(checked(&bad_url).is_err());
+        let long_title = ThemeDetails { card_title: "x".repeat(61), ..ThemeDetails::default() };
+        assert!(checked(&long_title).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use log::{info, warn};
+
+use loyalty_core::api::v1::{CardEvent, Tier, TierProgress};
+
+use crate::AppData;
+use crate::db::StampCardRepositoryError;
+use crate::error::ApiError;
+use crate::programmes::required;
+use crate::stampcard::{BasicStampCard, CardNotification, MAX_CAPACITY};
+
+/// The tiers lowest first, rejecting any without a name, with an empty or oversized card or reached at the same point as another.
+pub fn checked(tiers: &[Tier]) -> Result<Vec<Tier>, ApiError> {
+    let mut checked = tiers.iter()
+        .map(|tier| Ok(Tier {
+            name: required(&tier.name, "tier name")?,
+            perks: tier.perks.iter()
+                .map(|perk| perk.trim().to_string())
+                .filter(|perk| !perk.is_empty())
+                .collect(),
+            ..tier.clone()
+        }))
+        .collect::<Result<Vec<Tier>, ApiError>>()?;
+    checked.sort_by_key(|tier| tier.redemptions);
+
+    if checked.iter().any(|tier| tier.capacity.is_some_and(|capacity| !(1..=MAX_CAPACITY).contains(&capacity))) {
+        return Err(ApiError::Validation(format!("A tier's card must hold between 1 and {} stamps", MAX_CAPACITY)))
+    }
+    if checked.windows(2).any(|pair| pair[0].redemptions == pair[1].redemptions) {
+        return Err(ApiError::Validation(String::from("Each tier must need a different number of redeemed cards")))
+    }
+    Ok(checked)
+}
+
+/// The highest tier reached by redeeming `redemptions` full cards.
+pub fn reached(tiers: &[Tier], redemptions: u32) -> Option<&Tier> {
+    tiers.iter()
+        .filter(|tier| tier.redemptions <= redemptions)
+        .max_by_key(|tier| tier.redemptions)
+}
+
+/// The customer's current and next tier, or nothing when the programme has no tiers.
+pub fn progress(tiers: &[Tier], redemptions: u32) -> Option<TierProgress> {
+    if tiers.is_empty() {
+        return None
+    }
+    Some(TierProgress {
+        current: reached(tiers, redemptions).cloned(),
+        next: tiers.iter()
+            .filter(|tier| tier.redemptions > redemptions)
+            .min_by_key(|tier| tier.redemptions)
+            .cloned()
+    })
+}
+
+/// Moves a card that has just been redeemed onto its tier's capacity, without failing the redemption.
+pub async fn promote(data: &AppData, card: &BasicStampCard) {
+    if let Err(err) = try_promote(data, card).await {
+        warn!("Failed to update the tier of card {}: {}", card.user_id(), err);
+    }
+}
+
+async fn try_promote(data: &AppData, card: &BasicStampCard) -> Result<(), StampCardRepositoryError> {
+    let Some(programme_id) = &card.programme_id else { return Ok(()) };
+    let Some(programme) = data.programmes.lock().await.find_programme(programme_id).await? else { return Ok(()) };
+    let Some(tier) = reached(&programme.tiers, card.redemptions()) else { return Ok(()) };
+
+    // read the card again as it may have been stamped since it was redeemed
+    let mut tracker = data.cards.lock().await;
+    let current = tracker.get_or_create_card(card.user_id()).await?;
+    let capacity = tier.capacity.filter(|capacity| *capacity != current.capacity());
+    if tier.redemptions != current.redemptions() && capacity.is_none() {
+        return Ok(())
+    }
+
+    let promoted = match capacity {
+        Some(capacity) => current.with_capacity(capacity),
+        None => current
+    };
+    tracker.save_card(&promoted).await?;
+
+    info!("Card {} is in the {} tier", promoted.user_id(), tier.name);
+    _ = data.card_updates.send(CardNotification::new(promoted.user_id().clone(), CardEvent::Promoted, &promoted));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier(name: &str, redemptions: u32, capacity: Option<u32>) -> Tier {
+        Tier { name: name.into(), redemptions, capacity, perks: vec![] }
+    }
+
+    #[test]
+    fn tiers_are_sorted_and_must_not_overlap() {
+        let tiers = checked(&[tier("Gold", 10, Some(8)), tier(" Silver ", 5, None)]).unwrap();
+        assert_eq!(tiers.iter().map(|tier| tier.name.as_str()).collect::<Vec<_>>(), vec!["Silver", "Gold"]);
+
+        assert!(checked(&[tier("Silver", 5, None), tier("Gold", 5, None)]).is_err());
+        assert!(checked(&[tier("Gold", 10, Some(0))]).is_err());
+        assert!(checked(&[tier("Gold", 10, Some(MAX_CAPACITY + 1))]).is_err());
+        assert!(checked(&[tier("Gold", 10, Some(MAX_CAPACITY))]).is_ok());
+        assert!(checked(&[tier(" ", 10, None)]).is_err());
+    }
+
+    #[test]
+    fn progress_shows_the_current_and_next_tier() {
+        let tiers = vec![tier("Silver", 5, None), tier("Gold", 10, Some(8))];
+
+        let silver = progress(&tiers, 7).unwrap();
+        assert_eq!(silver.current.map(|tier| tier.name), Some("Silver".into()));
+        assert_eq!(silver.next.map(|tier| tier.name), Some("Gold".into()));
+
+        assert_eq!(progress(&tiers, 2).unwrap().current, None);
+        assert_eq!(progress(&tiers, 12).unwrap().next, None);
+        assert_eq!(progress(&[], 12), None);
+    }
+}